@@ -0,0 +1,79 @@
+//! Comparison timings on two hives of about 50 MB that differ in a handful of keys, and on
+//! two with 40 top-level keys that differ in every tenth app, sequentially and on threads.
+//!
+//! Run with `cargo bench --bench compare`.
+
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use winereg::*;
+
+const APPS: usize = 2_000;
+const KEYS_PER_APP: usize = 50;
+
+/// Apps below `prefix` (either `Software\\` or empty for top-level vendor keys), with the
+/// `Count` of `Key07` changed in every `changed`th app.
+fn hive(prefix: &str, changed: Option<usize>) -> KeyNode {
+    let root = RegistryKey::create_root();
+    RegistryKey::set_clock(&root, Rc::new(FixedClock::from_unix(1_700_000_000)));
+    for app in 0..APPS {
+        for key in 0..KEYS_PER_APP {
+            let path = format!("{}Vendor{:02}\\App{:04}\\Key{:02}", prefix, app % 40, app, key);
+            let node = RegistryKey::create_key_recursive(&root, &path);
+            let mut guard = node.borrow_mut();
+            let dll = format!("C:\\Program Files\\App{}\\bin\\{}.dll", app, key);
+            guard.set_value("Path", RegistryValue::new("Path", RegistryValueData::String(dll)));
+            guard.set_value("Data", RegistryValue::new("Data", RegistryValueData::Binary(vec![(app + key) as u8; 128], REG_BINARY)));
+            guard.set_value("Count", RegistryValue::new("Count", RegistryValueData::Dword(key as u32)));
+        }
+    }
+    if let Some(step) = changed {
+        for app in (0..APPS).step_by(step) {
+            let node = RegistryKey::find_key(&root, &format!("{}Vendor{:02}\\App{:04}\\Key07", prefix, app % 40, app)).unwrap();
+            node.borrow_mut().set_value("Count", RegistryValue::new("Count", RegistryValueData::Dword(0xffff)));
+        }
+    }
+    root
+}
+
+/// Fastest of a few runs of `run`.
+fn time<T>(mut run: impl FnMut() -> T) -> Duration {
+    (0..5)
+        .map(|_| {
+            let start = Instant::now();
+            std::hint::black_box(run());
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    let (left, right) = (hive("Software\\", None), hive("Software\\", Some(500)));
+    let size = RegistryWriter::new().write_to_string(&left).len();
+    println!("hive: {:.1} MB, {} keys", size as f64 / 1e6, APPS * KEYS_PER_APP);
+
+    let options = CompareOptions::default();
+    let diff = RegistryComparator.compare_registries_with_options(&left, &right, &options);
+    assert_eq!(diff.changes.len(), APPS / 500);
+
+    let first = time(|| RegistryComparator.compare_registries_with_options(&left, &right, &options));
+    println!("compare, fingerprinting both trees: {:?}", first);
+
+    let mut cache = FingerprintCache::new();
+    RegistryComparator.compare_registries_cached(&left, &right, &options, &mut cache);
+    let cached = time(|| RegistryComparator.compare_registries_cached(&left, &right, &options, &mut cache));
+    println!("compare, fingerprints cached:       {:?}", cached);
+
+    let (left, right) = (hive("", None), hive("", Some(10)));
+    let diff = RegistryComparator.compare_registries_with_options(&left, &right, &options);
+    assert_eq!(diff.changes.len(), APPS / 10);
+    let mut cache = FingerprintCache::new();
+    RegistryComparator.compare_registries_cached(&left, &right, &options, &mut cache);
+    for threads in [1, 2, 4, 8] {
+        let options = CompareOptions { threads, ..CompareOptions::default() };
+        let first = time(|| RegistryComparator.compare_registries_with_options(&left, &right, &options));
+        let cached = time(|| RegistryComparator.compare_registries_cached(&left, &right, &options, &mut cache));
+        println!("40 top-level keys, {} thread(s): {:?}, fingerprints cached: {:?}", threads, first, cached);
+    }
+}
@@ -1,16 +1,11 @@
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum Architecture {
+    #[default]
     Unknown,
     Win32,
     Win64,
 }
 
-impl Default for Architecture {
-    fn default() -> Self {
-        Architecture::Unknown
-    }
-}
-
 impl Architecture {
     pub fn from_tag(tag: &str) -> Option<Self> {
         match tag.to_ascii_lowercase().as_str() {
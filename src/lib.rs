@@ -1,26 +1,61 @@
 mod architecture;
 mod registry_value;
+mod registry_value_delta;
+mod registry_resource;
+mod registry_clock;
 mod registry_key;
+mod registry_tree;
+mod registry_link;
+mod registry_env;
+mod registry_dosdevices;
 mod registry_utils;
 mod registry_parser;
 mod registry_writer;
+mod registry_save;
+mod registry_lock;
+mod registry_fingerprint;
 mod registry_comparator;
+mod registry_diff_query;
 mod registry_patcher;
+mod registry_merge;
 mod registry_text_diff;
 mod registry_dsl;
 mod registry_editor;
+#[cfg(feature = "serde")]
+mod registry_serde;
 
 pub use architecture::Architecture;
 pub use registry_value::{
-    RegistryValue, RegistryValueData, REG_BINARY, REG_DWORD, REG_EXPAND_SZ, REG_MULTI_SZ, REG_QWORD,
-    REG_SZ,
+    RegistryValue, RegistryValueData, ValueError, REG_BINARY, REG_DWORD, REG_DWORD_BIG_ENDIAN, REG_EXPAND_SZ,
+    REG_FULL_RESOURCE_DESCRIPTOR, REG_LINK, REG_MULTI_SZ, REG_NONE, REG_QWORD, REG_RESOURCE_LIST,
+    REG_RESOURCE_REQUIREMENTS_LIST, REG_SZ,
 };
+pub use registry_value_delta::{ByteRange, ListEdit, ValueDelta};
+pub use registry_resource::{
+    FullResourceDescriptor, IoResourceDescriptor, IoResourceList, PartialResourceDescriptor, ResourceList,
+    ResourceRequirementsList, CM_RESOURCE_TYPE_BUS_NUMBER, CM_RESOURCE_TYPE_DEVICE_SPECIFIC, CM_RESOURCE_TYPE_DMA,
+    CM_RESOURCE_TYPE_INTERRUPT, CM_RESOURCE_TYPE_MEMORY, CM_RESOURCE_TYPE_PORT,
+};
+pub use registry_clock::{source_date_epoch, source_date_epoch_from, Clock, FixedClock, SystemClock};
 pub use registry_key::{KeyNode, RegistryKey, RegistryKeyExt};
+pub use registry_tree::KeyTree;
+pub use registry_link::{DanglingLink, LinkError, LinkOptions, SYMBOLIC_LINK_VALUE};
+pub use registry_dosdevices::{DosDevices, DosPathError};
+pub use registry_env::{Environment, Expansion, SYSTEM_ENVIRONMENT_PATH, USER_ENVIRONMENT_PATH};
 pub use registry_utils::*;
 pub use registry_parser::{LoadResult, ParseError, RegistryParser};
-pub use registry_writer::RegistryWriter;
-pub use registry_comparator::{DiffResult, KeyPropertyChange, RegistryChange, RegistryComparator};
+pub use registry_writer::{FormattedWriter, KeyOrder, LineEnding, QwordStyle, RegistryWriter, WriterOptions};
+pub use registry_save::{backup_path, restore_backup, SaveOptions, SaveReport, WineServerCheck};
+#[cfg(unix)]
+pub use registry_lock::wineserver_dir;
+pub use registry_lock::{wineserver_running, LockFile};
+pub use registry_fingerprint::{Fingerprint, FingerprintCache};
+pub use registry_comparator::{CompareOptions, ComposeError, DiffResult, KeyPropertyChange, RegistryChange, RegistryComparator};
+pub use registry_diff_query::{ChangeKind, DiffSummary};
 pub use registry_patcher::{PatchFailure, PatchOptions, PatchResult, RegistryPatcher};
-pub use registry_text_diff::{TextDiffExporter, TextDiffParser};
+pub use registry_merge::{ConflictKind, MergeConflict, MergeOptions, MergeResult, MergeSide, MergeStrategy, RegistryMerger};
+pub use registry_text_diff::{TextDiffExporter, TextDiffOptions, TextDiffParser};
 pub use registry_dsl::{load_registry, modify_registry, registry, RegistryKeyDsl, RegistryResult};
 pub use registry_editor::RegistryEditor;
+#[cfg(feature = "serde")]
+pub use registry_serde::{from_key, to_key, RegistrySerdeError};
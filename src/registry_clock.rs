@@ -0,0 +1,53 @@
+use std::fmt;
+use std::time::SystemTime;
+
+use crate::registry_utils::system_time_to_filetime;
+
+/// Source of last-write times (FILETIME ticks) stamped on keys when they are mutated.
+pub trait Clock: fmt::Debug {
+    fn now(&self) -> u64;
+}
+
+/// Wall-clock time, the default when no clock has been installed on a tree.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        system_time_to_filetime(SystemTime::now())
+    }
+}
+
+/// Always returns the same FILETIME, for tests and reproducible builds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedClock(pub u64);
+
+impl FixedClock {
+    /// A clock frozen at `timestamp` seconds since the Unix epoch.
+    pub fn from_unix(timestamp: u64) -> Self {
+        FixedClock(crate::registry_utils::timestamp_to_filetime(timestamp))
+    }
+
+    /// The clock for reproducible output: frozen at `timestamp` if given, else at
+    /// `SOURCE_DATE_EPOCH`, else at the Unix epoch.
+    pub fn reproducible(timestamp: Option<u64>) -> Self {
+        Self::from_unix(timestamp.or_else(source_date_epoch).unwrap_or(0))
+    }
+}
+
+/// The `SOURCE_DATE_EPOCH` environment variable (seconds since the Unix epoch) used by
+/// reproducible builds, if set to a valid number.
+pub fn source_date_epoch() -> Option<u64> {
+    source_date_epoch_from(std::env::var("SOURCE_DATE_EPOCH").ok().as_deref())
+}
+
+/// A `SOURCE_DATE_EPOCH` value as seconds, `None` when unset or not a number.
+pub fn source_date_epoch_from(value: Option<&str>) -> Option<u64> {
+    value?.trim().parse().ok()
+}
+
+impl Clock for FixedClock {
+    fn now(&self) -> u64 {
+        self.0
+    }
+}
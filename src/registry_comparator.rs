@@ -1,7 +1,24 @@
-use crate::registry_key::KeyNode;
-use crate::registry_value::RegistryValue;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use regex::Regex;
+use thiserror::Error;
+
+use crate::registry_fingerprint::{Fingerprint, FingerprintCache};
+use crate::registry_key::{KeyNode, RegistryKey};
+use crate::registry_tree::KeyTree;
+use crate::registry_value::{RegistryValue, REG_BINARY, REG_DWORD, REG_EXPAND_SZ, REG_MULTI_SZ, REG_NONE, REG_QWORD, REG_SZ};
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum RegistryChange {
     KeyAdded(String),
     KeyDeleted(String),
@@ -9,16 +26,30 @@ pub enum RegistryChange {
     ValueAdded(String, String, RegistryValue),
     ValueDeleted(String, String, RegistryValue),
     ValueModified(String, String, RegistryValue, RegistryValue),
+    /// A key added with everything below it, in place of `KeyAdded` and one change per
+    /// descendant (`CompareOptions::compact_subtrees`).
+    SubtreeAdded(String, KeyTree),
+    /// A key deleted with everything that was below it.
+    SubtreeDeleted(String, KeyTree),
+    /// A key and its subtree moved to a new parent (`from`, `to`); the changes that follow
+    /// describe differences below `to` (`CompareOptions::move_threshold`).
+    KeyMoved(String, String),
+    /// Like `KeyMoved`, for a key that kept its parent and only changed its name.
+    KeyRenamed(String, String),
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum KeyPropertyChange {
     ClassNameChange(Option<String>, Option<String>),
     SymlinkChange(bool, bool),
     VolatileChange(bool, bool),
+    /// Last-write time (FILETIME), only reported with `CompareOptions::report_timestamps`.
+    TimeChange(u64, u64),
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct DiffResult {
     pub changes: Vec<RegistryChange>,
 }
@@ -29,121 +60,982 @@ impl DiffResult {
     }
 
     pub fn added_keys(&self) -> Vec<&RegistryChange> {
-        self.changes.iter().filter(|c| matches!(c, RegistryChange::KeyAdded(_))).collect()
+        self.changes.iter().filter(|c| matches!(c, RegistryChange::KeyAdded(_) | RegistryChange::SubtreeAdded(..))).collect()
+    }
+
+    /// The same diff with every `SubtreeAdded`/`SubtreeDeleted` spelled out as the key and
+    /// value changes a non-compact comparison reports, plus a `KeyModified` per key carrying
+    /// the metadata (class name, flags, last-write time) a flat key change cannot.
+    pub fn expand_subtrees(&self) -> DiffResult {
+        let mut changes = Vec::new();
+        for change in &self.changes {
+            match change {
+                // The tree's own name keeps the key's case for the patcher.
+                RegistryChange::SubtreeAdded(path, tree) => expand_tree(tree, &join(parent_of(path), &tree.name), true, &mut changes),
+                RegistryChange::SubtreeDeleted(path, tree) => expand_tree(tree, &join(parent_of(path), &tree.name), false, &mut changes),
+                other => changes.push(other.clone()),
+            }
+        }
+        DiffResult { changes }
+    }
+
+    /// The patch undoing this one: additions become deletions and back, old and new data
+    /// swap, and moves go back to where they came from (with the changes found below them
+    /// rebased). Deleted subtrees come back with their values and metadata, which both
+    /// `SubtreeDeleted` and the `KeyModified` preceding a flat `KeyDeleted` carry.
+    pub fn invert(&self) -> DiffResult {
+        let mut moves: Vec<(String, String)> = Vec::new();
+        let mut changes = Vec::with_capacity(self.changes.len());
+        for change in &self.changes {
+            let at = |path: &str| rebase(path, &moves);
+            changes.push(match change {
+                RegistryChange::KeyAdded(p) => RegistryChange::KeyDeleted(at(p)),
+                RegistryChange::KeyDeleted(p) => RegistryChange::KeyAdded(at(p)),
+                RegistryChange::KeyModified(p, props) => RegistryChange::KeyModified(at(p), props.iter().map(KeyPropertyChange::invert).collect()),
+                RegistryChange::ValueAdded(k, n, v) => RegistryChange::ValueDeleted(at(k), n.clone(), v.clone()),
+                RegistryChange::ValueDeleted(k, n, v) => RegistryChange::ValueAdded(at(k), n.clone(), v.clone()),
+                RegistryChange::ValueModified(k, n, old, new) => RegistryChange::ValueModified(at(k), n.clone(), new.clone(), old.clone()),
+                RegistryChange::SubtreeAdded(p, tree) => RegistryChange::SubtreeDeleted(at(p), tree.clone()),
+                RegistryChange::SubtreeDeleted(p, tree) => RegistryChange::SubtreeAdded(at(p), tree.clone()),
+                RegistryChange::KeyMoved(from, to) | RegistryChange::KeyRenamed(from, to) => {
+                    let (from, to) = (at(from), at(to));
+                    moves.push((to.clone(), from.clone()));
+                    match change {
+                        RegistryChange::KeyMoved(..) => RegistryChange::KeyMoved(to, from),
+                        _ => RegistryChange::KeyRenamed(to, from),
+                    }
+                }
+            });
+        }
+        // A key's metadata follows its addition and precedes its deletion, as the comparator
+        // reports them.
+        let mut i = 0;
+        while i + 1 < changes.len() {
+            let swap = match (&changes[i], &changes[i + 1]) {
+                (RegistryChange::KeyModified(a, _), RegistryChange::KeyAdded(b)) | (RegistryChange::KeyDeleted(a), RegistryChange::KeyModified(b, _)) => a == b,
+                _ => false,
+            };
+            if swap {
+                changes.swap(i, i + 1);
+                i += 1;
+            }
+            i += 1;
+        }
+        DiffResult { changes }
+    }
+}
+
+/// Changes of the second diff given to `DiffResult::compose` that contradict the state the
+/// first one leaves behind.
+#[derive(Debug, Clone, Error)]
+#[error("{} change(s) do not apply after the first diff", .0.len())]
+pub struct ComposeError(pub Vec<RegistryChange>);
+
+impl DiffResult {
+    /// One diff with the effect of applying this one and then `next`: an addition later
+    /// deleted disappears, repeated modifications collapse into one, and moves of both are
+    /// kept with the other changes rebased. Subtree changes are expanded to flat ones, their
+    /// metadata kept as `KeyModified` changes (see `expand_subtrees`).
+    pub fn compose(&self, next: &DiffResult) -> Result<DiffResult, ComposeError> {
+        let (first, second) = (self.expand_subtrees(), next.expand_subtrees());
+        let next_moves: Vec<(String, String)> = second
+            .changes
+            .iter()
+            .filter_map(|c| match c {
+                RegistryChange::KeyMoved(from, to) | RegistryChange::KeyRenamed(from, to) => Some((from.clone(), to.clone())),
+                _ => None,
+            })
+            .collect();
+        let added = |path: &str| first.changes.iter().any(|c| matches!(c, RegistryChange::KeyAdded(p) if p.eq_ignore_ascii_case(path)));
+
+        let mut changes: Vec<RegistryChange> = first.changes.iter().filter(|c| is_move(c)).cloned().collect();
+        // A key added by the first diff and moved by the second is simply added at its new place.
+        changes.extend(second.changes.iter().filter(|c| matches!(c, RegistryChange::KeyMoved(from, _) | RegistryChange::KeyRenamed(from, _) if !added(from))).cloned());
+
+        let mut composition = Composition::default();
+        for change in first.changes.iter().filter(|c| !is_move(c)) {
+            composition.record(&change.rebased(|p| rebase(p, &next_moves)), true);
+        }
+        let conflicts: Vec<RegistryChange> = second.changes.iter().filter(|c| !is_move(c) && !composition.record(c, false)).cloned().collect();
+        if !conflicts.is_empty() {
+            return Err(ComposeError(conflicts));
+        }
+        changes.extend(composition.finish());
+        Ok(DiffResult { changes })
+    }
+}
+
+impl RegistryChange {
+    /// The same change with its key paths passed through `map`.
+    fn rebased(&self, map: impl Fn(&str) -> String) -> RegistryChange {
+        match self {
+            RegistryChange::KeyAdded(p) => RegistryChange::KeyAdded(map(p)),
+            RegistryChange::KeyDeleted(p) => RegistryChange::KeyDeleted(map(p)),
+            RegistryChange::KeyModified(p, props) => RegistryChange::KeyModified(map(p), props.clone()),
+            RegistryChange::ValueAdded(k, n, v) => RegistryChange::ValueAdded(map(k), n.clone(), v.clone()),
+            RegistryChange::ValueDeleted(k, n, v) => RegistryChange::ValueDeleted(map(k), n.clone(), v.clone()),
+            RegistryChange::ValueModified(k, n, old, new) => RegistryChange::ValueModified(map(k), n.clone(), old.clone(), new.clone()),
+            RegistryChange::SubtreeAdded(p, tree) => RegistryChange::SubtreeAdded(map(p), tree.clone()),
+            RegistryChange::SubtreeDeleted(p, tree) => RegistryChange::SubtreeDeleted(map(p), tree.clone()),
+            RegistryChange::KeyMoved(from, to) => RegistryChange::KeyMoved(map(from), map(to)),
+            RegistryChange::KeyRenamed(from, to) => RegistryChange::KeyRenamed(map(from), map(to)),
+        }
     }
 }
 
+fn is_move(change: &RegistryChange) -> bool {
+    matches!(change, RegistryChange::KeyMoved(..) | RegistryChange::KeyRenamed(..))
+}
+
+/// Net effect of a sequence of flat changes, per key, value and key property.
+#[derive(Default)]
+struct Composition {
+    slots: Vec<Slot>,
+    index: HashMap<String, usize>,
+}
+
+enum Slot {
+    /// `first` is whether the key exists after the first diff, if that touched it.
+    Key { path: String, first: Option<bool>, before: bool, after: bool },
+    Value { key: String, name: String, before: Option<RegistryValue>, after: Option<RegistryValue> },
+    Property { path: String, change: KeyPropertyChange },
+}
+
+impl Composition {
+    /// Fold `change` into the state; false if it contradicts the state it finds.
+    fn record(&mut self, change: &RegistryChange, first: bool) -> bool {
+        let container = match change {
+            RegistryChange::KeyAdded(p) | RegistryChange::KeyDeleted(p) => parent_of(p),
+            RegistryChange::KeyModified(p, _) => p,
+            RegistryChange::ValueAdded(k, ..) | RegistryChange::ValueDeleted(k, ..) | RegistryChange::ValueModified(k, ..) => k,
+            _ => return false,
+        };
+        // Nothing can happen inside a key the first diff deleted unless it is added back.
+        if !first
+            && let Some(&idx) = self.index.get(&format!("K:{}", container.to_ascii_uppercase()))
+            && matches!(self.slots[idx], Slot::Key { first: Some(false), after: false, .. })
+        {
+            return false;
+        }
+        match change {
+            RegistryChange::KeyAdded(p) | RegistryChange::KeyDeleted(p) => {
+                let exists = matches!(change, RegistryChange::KeyAdded(_));
+                match self.slot(format!("K:{}", p.to_ascii_uppercase())) {
+                    Some(Slot::Key { path, after, .. }) if *after != exists => {
+                        *path = p.clone();
+                        *after = exists;
+                    }
+                    Some(_) => return false,
+                    None => self.push(format!("K:{}", p.to_ascii_uppercase()), Slot::Key { path: p.clone(), first: first.then_some(exists), before: !exists, after: exists }),
+                }
+            }
+            RegistryChange::KeyModified(p, props) => {
+                for prop in props {
+                    let id = format!("P:{}:{}", p.to_ascii_uppercase(), prop.kind());
+                    match self.slot(id.clone()) {
+                        Some(Slot::Property { change, .. }) => match change.then(prop) {
+                            Some(combined) => *change = combined,
+                            None => return false,
+                        },
+                        Some(_) => return false,
+                        None => self.push(id, Slot::Property { path: p.clone(), change: prop.clone() }),
+                    }
+                }
+            }
+            RegistryChange::ValueAdded(k, n, _) | RegistryChange::ValueDeleted(k, n, _) | RegistryChange::ValueModified(k, n, _, _) => {
+                let (old, new) = match change {
+                    RegistryChange::ValueAdded(_, _, v) => (None, Some(v)),
+                    RegistryChange::ValueDeleted(_, _, v) => (Some(v), None),
+                    RegistryChange::ValueModified(_, _, old, new) => (Some(old), Some(new)),
+                    _ => unreachable!(),
+                };
+                let id = format!("V:{}:{}", k.to_ascii_uppercase(), n.to_ascii_uppercase());
+                match self.slot(id.clone()) {
+                    Some(Slot::Value { key, name, after, .. }) => {
+                        let same = match (after.as_ref(), old) {
+                            (Some(a), Some(b)) => values_equal(a, b, &CompareOptions::default()),
+                            (a, b) => a.is_none() && b.is_none(),
+                        };
+                        if !same {
+                            return false;
+                        }
+                        *key = k.clone();
+                        *name = n.clone();
+                        *after = new.cloned();
+                    }
+                    Some(_) => return false,
+                    None => self.push(id, Slot::Value { key: k.clone(), name: n.clone(), before: old.cloned(), after: new.cloned() }),
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    fn slot(&mut self, id: String) -> Option<&mut Slot> {
+        self.index.get(&id).map(|&idx| &mut self.slots[idx])
+    }
+
+    fn push(&mut self, id: String, slot: Slot) {
+        self.index.insert(id, self.slots.len());
+        self.slots.push(slot);
+    }
+
+    /// The changes with the recorded net effect, dropping those that cancel out.
+    fn finish(self) -> Vec<RegistryChange> {
+        // Keys added and deleted again keep no properties either.
+        let transient: HashSet<String> = self
+            .slots
+            .iter()
+            .filter_map(|slot| match slot {
+                Slot::Key { path, before: false, after: false, .. } => Some(path.to_ascii_uppercase()),
+                _ => None,
+            })
+            .collect();
+        let mut changes: Vec<RegistryChange> = Vec::new();
+        for slot in self.slots {
+            match slot {
+                Slot::Key { path, before, after, .. } if before != after => changes.push(match after {
+                    true => RegistryChange::KeyAdded(path),
+                    false => RegistryChange::KeyDeleted(path),
+                }),
+                Slot::Key { .. } => {}
+                Slot::Value { key, name, before, after } => match (before, after) {
+                    (None, Some(new)) => changes.push(RegistryChange::ValueAdded(key, name, new)),
+                    (Some(old), None) => changes.push(RegistryChange::ValueDeleted(key, name, old)),
+                    (Some(old), Some(new)) if !values_equal(&old, &new, &CompareOptions::default()) => {
+                        changes.push(RegistryChange::ValueModified(key, name, old, new))
+                    }
+                    _ => {}
+                },
+                Slot::Property { change, .. } if change.is_noop() => {}
+                Slot::Property { path, .. } if transient.contains(&path.to_ascii_uppercase()) => {}
+                Slot::Property { path, change } => match changes.last_mut() {
+                    Some(RegistryChange::KeyModified(last, props)) if last.eq_ignore_ascii_case(&path) => props.push(change),
+                    _ => changes.push(RegistryChange::KeyModified(path, vec![change])),
+                },
+            }
+        }
+        changes
+    }
+}
+
+impl KeyPropertyChange {
+    /// The same change with old and new swapped.
+    pub fn invert(&self) -> KeyPropertyChange {
+        match self {
+            KeyPropertyChange::ClassNameChange(old, new) => KeyPropertyChange::ClassNameChange(new.clone(), old.clone()),
+            KeyPropertyChange::SymlinkChange(old, new) => KeyPropertyChange::SymlinkChange(*new, *old),
+            KeyPropertyChange::VolatileChange(old, new) => KeyPropertyChange::VolatileChange(*new, *old),
+            KeyPropertyChange::TimeChange(old, new) => KeyPropertyChange::TimeChange(*new, *old),
+        }
+    }
+
+    fn kind(&self) -> u8 {
+        match self {
+            KeyPropertyChange::ClassNameChange(..) => 0,
+            KeyPropertyChange::SymlinkChange(..) => 1,
+            KeyPropertyChange::VolatileChange(..) => 2,
+            KeyPropertyChange::TimeChange(..) => 3,
+        }
+    }
+
+    /// This change followed by `next`, if `next` starts where this one ends.
+    fn then(&self, next: &KeyPropertyChange) -> Option<KeyPropertyChange> {
+        match (self, next) {
+            (KeyPropertyChange::ClassNameChange(a, b), KeyPropertyChange::ClassNameChange(c, d)) if b == c => Some(KeyPropertyChange::ClassNameChange(a.clone(), d.clone())),
+            (KeyPropertyChange::SymlinkChange(a, b), KeyPropertyChange::SymlinkChange(c, d)) if b == c => Some(KeyPropertyChange::SymlinkChange(*a, *d)),
+            (KeyPropertyChange::VolatileChange(a, b), KeyPropertyChange::VolatileChange(c, d)) if b == c => Some(KeyPropertyChange::VolatileChange(*a, *d)),
+            (KeyPropertyChange::TimeChange(a, b), KeyPropertyChange::TimeChange(c, d)) if b == c => Some(KeyPropertyChange::TimeChange(*a, *d)),
+            _ => None,
+        }
+    }
+
+    fn is_noop(&self) -> bool {
+        match self {
+            KeyPropertyChange::ClassNameChange(old, new) => old == new,
+            KeyPropertyChange::SymlinkChange(old, new) | KeyPropertyChange::VolatileChange(old, new) => old == new,
+            KeyPropertyChange::TimeChange(old, new) => old == new,
+        }
+    }
+}
+
+/// Where `path` (as found after the moves in `moves`, each `(to, from)`) was before them.
+fn rebase(path: &str, moves: &[(String, String)]) -> String {
+    let mut path = path.to_string();
+    for (to, from) in moves {
+        if path.get(..to.len()).is_some_and(|head| head.eq_ignore_ascii_case(to)) && (path.len() == to.len() || path[to.len()..].starts_with('\\')) {
+            path = format!("{}{}", from, &path[to.len()..]);
+        }
+    }
+    path
+}
+
 pub struct RegistryComparator;
 
+/// Options for `RegistryComparator::compare_registries_with_options`. The default reports
+/// every difference except last-write times, like `compare_registries`.
+#[derive(Debug, Clone, Default)]
+pub struct CompareOptions {
+    /// Keys (with their subtrees) to leave out, as case-insensitive patterns over the whole
+    /// path where `*` matches any text including `\` and `?` one character, e.g.
+    /// `Software\Wine\MSHTML` or `*\MRU*`.
+    pub ignore_keys: Vec<String>,
+    /// Values to leave out: patterns over the value name, or over `key path\name` when the
+    /// pattern contains a `\`.
+    pub ignore_values: Vec<String>,
+    /// Report differing last-write times of keys present on both sides as `TimeChange`.
+    pub report_timestamps: bool,
+    /// Compare `REG_SZ`, `REG_EXPAND_SZ` and `REG_MULTI_SZ` data case-insensitively.
+    pub case_insensitive_strings: bool,
+    /// Treat values with different types but the same meaning as equal: integer and binary
+    /// types with identical bytes (a DWORD and 4-byte `REG_BINARY`), and `REG_SZ` and
+    /// `REG_EXPAND_SZ` with identical text.
+    pub type_tolerant: bool,
+    /// Report an added or deleted key as one `SubtreeAdded`/`SubtreeDeleted` carrying the
+    /// subtree (minus ignored keys and values) instead of one change per descendant.
+    pub compact_subtrees: bool,
+    /// Pair deleted and added keys whose subtrees are at least this similar (0.0 to 1.0, by
+    /// the share of keys and values they have in common) and report them as
+    /// `KeyMoved`/`KeyRenamed`. Keys without values in common are never paired. Values above
+    /// 1.0 are treated as 1.0, below 0.0 as 0.0, and NaN as `None`.
+    ///
+    /// Only the topmost added and deleted keys are candidates: a key moved under a parent that
+    /// is itself new is reported as part of that parent's addition (and a deletion).
+    pub move_threshold: Option<f64>,
+    /// Compare the top-level keys that differ on up to this many threads (0 or 1, the
+    /// default, compares on the calling thread). Trees are not `Send`, so those keys are
+    /// copied first, leaving out subtrees that are identical on both sides; the result is the
+    /// same as comparing sequentially.
+    pub threads: usize,
+}
+
 impl RegistryComparator {
     pub fn compare_registries(&self, left: &KeyNode, right: &KeyNode) -> DiffResult {
+        self.compare_registries_with_options(left, right, &CompareOptions::default())
+    }
+
+    /// Compare with ignore rules and tolerances, see `CompareOptions`.
+    pub fn compare_registries_with_options(&self, left: &KeyNode, right: &KeyNode, options: &CompareOptions) -> DiffResult {
+        self.compare_registries_cached(left, right, options, &mut FingerprintCache::new())
+    }
+
+    /// Like `compare_registries_with_options`, keeping the subtree fingerprints used to skip
+    /// identical subtrees in `cache`, so comparing the same (unchanged) trees again is cheap.
+    pub fn compare_registries_cached(&self, left: &KeyNode, right: &KeyNode, options: &CompareOptions, cache: &mut FingerprintCache) -> DiffResult {
+        let rules = Rules::new(options, cache);
+        if options.threads > 1 {
+            return DiffResult { changes: compare_parallel(left, right, &rules) };
+        }
         let mut changes = Vec::new();
-        compare_keys(Some(left.clone()), Some(right.clone()), String::new(), &mut changes);
-        DiffResult { changes }
+        let mut pending = Vec::new();
+        compare_keys(Some(left.clone()), Some(right.clone()), String::new(), &rules, &mut changes, &mut pending);
+        DiffResult { changes: resolve_moves(changes, pending, &rules) }
     }
 }
 
-fn compare_keys(left: Option<KeyNode>, right: Option<KeyNode>, path: String, changes: &mut Vec<RegistryChange>) {
-    match (left, right) {
-        (None, Some(r)) => {
-            changes.push(RegistryChange::KeyAdded(path.clone()));
-            add_subtree_added(&r, &path, changes);
+struct Rules<'a> {
+    options: &'a CompareOptions,
+    keys: Vec<Regex>,
+    values: Vec<(bool, Regex)>,
+    fingerprints: RefCell<&'a mut FingerprintCache>,
+}
+
+impl<'a> Rules<'a> {
+    fn new(options: &'a CompareOptions, fingerprints: &'a mut FingerprintCache) -> Self {
+        Self {
+            options,
+            keys: options.ignore_keys.iter().map(|p| glob_regex(p)).collect(),
+            values: options.ignore_values.iter().map(|p| (p.contains('\\'), glob_regex(p))).collect(),
+            fingerprints: RefCell::new(fingerprints),
         }
-        (Some(l), None) => {
-            changes.push(RegistryChange::KeyDeleted(path.clone()));
-            add_subtree_deleted(&l, &path, changes);
+    }
+
+    /// Whether the subtrees hold the same data, in which case none of the rules can turn up
+    /// a difference below them.
+    fn identical<K: Key>(&self, left: &K, right: &K) -> bool {
+        let mut cache = self.fingerprints.borrow_mut();
+        self.same(left.fingerprint(&mut cache), right.fingerprint(&mut cache))
+    }
+
+    fn same(&self, l: Fingerprint, r: Fingerprint) -> bool {
+        match self.options.report_timestamps {
+            true => l.with_times == r.with_times,
+            false => l.content == r.content,
         }
-        (Some(l), Some(r)) => {
-            let l_guard = l.borrow();
-            let r_guard = r.borrow();
+    }
 
-            let mut prop_changes = Vec::new();
-            if l_guard.class_name != r_guard.class_name {
-                prop_changes.push(KeyPropertyChange::ClassNameChange(l_guard.class_name.clone(), r_guard.class_name.clone()));
-            }
-            if l_guard.is_symlink != r_guard.is_symlink {
-                prop_changes.push(KeyPropertyChange::SymlinkChange(l_guard.is_symlink, r_guard.is_symlink));
+    fn move_threshold(&self) -> Option<f64> {
+        self.options.move_threshold.filter(|t| !t.is_nan()).map(|t| t.clamp(0.0, 1.0))
+    }
+
+    fn ignores_key(&self, path: &str) -> bool {
+        !path.is_empty() && self.keys.iter().any(|re| re.is_match(path))
+    }
+
+    fn ignores_value(&self, path: &str, name: &str) -> bool {
+        self.values.iter().any(|(with_path, re)| match with_path {
+            true => re.is_match(&join(path, name)),
+            false => re.is_match(name),
+        })
+    }
+}
+
+/// Case-insensitive whole-string regex for a `*`/`?` wildcard pattern.
+pub fn glob_regex(pattern: &str) -> Regex {
+    let mut re = String::from("(?is)^");
+    for c in pattern.chars() {
+        match c {
+            '*' => re.push_str(".*"),
+            '?' => re.push('.'),
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    re.push('$');
+    Regex::new(&re).expect("escaped wildcard pattern is a valid regex")
+}
+
+fn join(path: &str, name: &str) -> String {
+    if path.is_empty() { name.to_string() } else { format!("{}\\{}", path, name) }
+}
+
+/// What the comparator reads from a key: implemented by `KeyNode` and by the `Send`
+/// snapshots compared on worker threads.
+trait Key: Clone {
+    type Data: KeyData<Self>;
+    fn data(&self) -> impl Deref<Target = Self::Data> + '_;
+    fn fingerprint(&self, cache: &mut FingerprintCache) -> Fingerprint;
+}
+
+trait KeyData<K> {
+    fn name(&self) -> &str;
+    fn class_name(&self) -> Option<&String>;
+    fn is_symlink(&self) -> bool;
+    fn is_volatile(&self) -> bool;
+    fn modification_time(&self) -> u64;
+    fn values(&self) -> &BTreeMap<String, RegistryValue>;
+    fn subkeys(&self) -> &BTreeMap<String, K>;
+}
+
+impl Key for KeyNode {
+    type Data = RegistryKey;
+
+    fn data(&self) -> impl Deref<Target = RegistryKey> + '_ {
+        self.borrow()
+    }
+
+    fn fingerprint(&self, cache: &mut FingerprintCache) -> Fingerprint {
+        cache.fingerprint(self)
+    }
+}
+
+impl KeyData<KeyNode> for RegistryKey {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn class_name(&self) -> Option<&String> {
+        self.class_name.as_ref()
+    }
+    fn is_symlink(&self) -> bool {
+        self.is_symlink
+    }
+    fn is_volatile(&self) -> bool {
+        self.is_volatile
+    }
+    fn modification_time(&self) -> u64 {
+        self.modification_time
+    }
+    fn values(&self) -> &BTreeMap<String, RegistryValue> {
+        RegistryKey::values(self)
+    }
+    fn subkeys(&self) -> &BTreeMap<String, KeyNode> {
+        RegistryKey::subkeys(self)
+    }
+}
+
+/// Copy of a key that can be compared on another thread, with the fingerprint computed on
+/// the calling thread. A key identical to its counterpart on the other side is copied without
+/// values and subkeys: the comparator never looks below it.
+struct Snapshot {
+    name: String,
+    class_name: Option<String>,
+    is_symlink: bool,
+    is_volatile: bool,
+    modification_time: u64,
+    values: BTreeMap<String, RegistryValue>,
+    subkeys: BTreeMap<String, Arc<Snapshot>>,
+    fingerprint: Fingerprint,
+}
+
+impl Snapshot {
+    fn of(node: &KeyNode, other: Option<&KeyNode>, rules: &Rules) -> Arc<Snapshot> {
+        let (fingerprint, full) = {
+            let mut cache = rules.fingerprints.borrow_mut();
+            let fingerprint = cache.fingerprint(node);
+            (fingerprint, other.is_none_or(|other| !rules.same(fingerprint, cache.fingerprint(other))))
+        };
+        let guard = node.borrow();
+        let (values, subkeys) = match full {
+            true => {
+                let other = other.map(|o| o.borrow());
+                let subkeys = guard.subkeys().iter().map(|(name, sub)| {
+                    let counterpart = other.as_ref().and_then(|o| o.subkeys().get(name));
+                    (name.clone(), Snapshot::of(sub, counterpart, rules))
+                });
+                (guard.values().clone(), subkeys.collect())
             }
-            if l_guard.is_volatile != r_guard.is_volatile {
-                prop_changes.push(KeyPropertyChange::VolatileChange(l_guard.is_volatile, r_guard.is_volatile));
+            false => Default::default(),
+        };
+        Arc::new(Snapshot {
+            name: guard.name.clone(),
+            class_name: guard.class_name.clone(),
+            is_symlink: guard.is_symlink,
+            is_volatile: guard.is_volatile,
+            modification_time: guard.modification_time,
+            values,
+            subkeys,
+            fingerprint,
+        })
+    }
+}
+
+impl Key for Arc<Snapshot> {
+    type Data = Snapshot;
+
+    fn data(&self) -> impl Deref<Target = Snapshot> + '_ {
+        &**self
+    }
+
+    fn fingerprint(&self, _cache: &mut FingerprintCache) -> Fingerprint {
+        self.fingerprint
+    }
+}
+
+impl KeyData<Arc<Snapshot>> for Snapshot {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn class_name(&self) -> Option<&String> {
+        self.class_name.as_ref()
+    }
+    fn is_symlink(&self) -> bool {
+        self.is_symlink
+    }
+    fn is_volatile(&self) -> bool {
+        self.is_volatile
+    }
+    fn modification_time(&self) -> u64 {
+        self.modification_time
+    }
+    fn values(&self) -> &BTreeMap<String, RegistryValue> {
+        &self.values
+    }
+    fn subkeys(&self) -> &BTreeMap<String, Arc<Snapshot>> {
+        &self.subkeys
+    }
+}
+
+/// One top-level key compared on a worker: its name and both sides.
+type Job = (String, Option<Arc<Snapshot>>, Option<Arc<Snapshot>>);
+/// A job's changes and the keys it held back for move detection.
+type JobResult = (Vec<RegistryChange>, Vec<Pending<Arc<Snapshot>>>);
+
+/// `compare_keys` for the roots with every top-level key that differs compared on a worker,
+/// `rules.options.threads` at a time. The workers' changes (and the keys they held back for
+/// move detection) are joined in name order, as a sequential comparison finds them.
+fn compare_parallel(left: &KeyNode, right: &KeyNode, rules: &Rules) -> Vec<RegistryChange> {
+    let mut changes = Vec::new();
+    if rules.identical(left, right) {
+        return changes;
+    }
+    compare_properties(left, right, "", rules, &mut changes);
+    compare_values(left, right, "", rules, &mut changes);
+
+    let mut jobs: Vec<Job> = Vec::new();
+    {
+        let (l_guard, r_guard) = (left.borrow(), right.borrow());
+        let (l_sub, r_sub) = (l_guard.subkeys(), r_guard.subkeys());
+        let mut names: Vec<&String> = l_sub.keys().chain(r_sub.keys()).collect();
+        names.sort();
+        names.dedup();
+        for name in names {
+            let (l, r) = (l_sub.get(name), r_sub.get(name));
+            if rules.ignores_key(name) || matches!((l, r), (Some(l), Some(r)) if rules.identical(l, r)) {
+                continue;
             }
-            drop(l_guard);
-            drop(r_guard);
-            if !prop_changes.is_empty() {
-                changes.push(RegistryChange::KeyModified(path.clone(), prop_changes));
+            jobs.push((name.clone(), l.map(|l| Snapshot::of(l, r, rules)), r.map(|r| Snapshot::of(r, l, rules))));
+        }
+    }
+
+    let mut results: Vec<Option<JobResult>> = Vec::new();
+    results.resize_with(jobs.len(), || None);
+    let next = AtomicUsize::new(0);
+    std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..rules.options.threads.min(jobs.len()))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut cache = FingerprintCache::new();
+                    let worker_rules = Rules::new(rules.options, &mut cache);
+                    let mut done = Vec::new();
+                    loop {
+                        let idx = next.fetch_add(1, Ordering::Relaxed);
+                        let Some((name, l, r)) = jobs.get(idx) else { break };
+                        let (mut part, mut held) = (Vec::new(), Vec::new());
+                        compare_keys(l.clone(), r.clone(), name.clone(), &worker_rules, &mut part, &mut held);
+                        done.push((idx, (part, held)));
+                    }
+                    done
+                })
+            })
+            .collect();
+        for worker in workers {
+            for (idx, result) in worker.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic)) {
+                results[idx] = Some(result);
             }
+        }
+    });
 
-            compare_values(&l, &r, &path, changes);
-            compare_subkeys(&l, &r, &path, changes);
+    let mut pending = Vec::new();
+    for (part, held) in results.into_iter().map(|r| r.expect("every job ran")) {
+        let offset = changes.len();
+        pending.extend(held.into_iter().map(|p| Pending { at: p.at + offset, ..p }));
+        changes.extend(part);
+    }
+    resolve_moves(changes, pending, rules)
+}
+
+/// An added (`added`) or deleted key held back until moves have been paired up; `at` is
+/// where in the changes it belongs.
+struct Pending<K> {
+    at: usize,
+    path: String,
+    node: K,
+    added: bool,
+}
+
+fn compare_keys<K: Key>(
+    left: Option<K>,
+    right: Option<K>,
+    path: String,
+    rules: &Rules,
+    changes: &mut Vec<RegistryChange>,
+    pending: &mut Vec<Pending<K>>,
+) {
+    if rules.ignores_key(&path) {
+        return;
+    }
+    match (left, right) {
+        (None, Some(r)) if rules.move_threshold().is_some() => {
+            pending.push(Pending { at: changes.len(), path, node: r, added: true });
+        }
+        (Some(l), None) if rules.move_threshold().is_some() => {
+            pending.push(Pending { at: changes.len(), path, node: l, added: false });
+        }
+        (None, Some(r)) => report_added(&r, path, rules, changes),
+        (Some(l), None) => report_deleted(&l, path, rules, changes),
+        (Some(l), Some(r)) if rules.identical(&l, &r) => {}
+        (Some(l), Some(r)) => {
+            compare_properties(&l, &r, &path, rules, changes);
+            compare_values(&l, &r, &path, rules, changes);
+            compare_subkeys(&l, &r, &path, rules, changes, pending);
         }
         (None, None) => {}
     }
 }
 
-fn compare_values(left: &KeyNode, right: &KeyNode, path: &str, changes: &mut Vec<RegistryChange>) {
-    let l_vals = left.borrow().values().clone();
-    let r_vals = right.borrow().values().clone();
+fn compare_properties<K: Key>(left: &K, right: &K, path: &str, rules: &Rules, changes: &mut Vec<RegistryChange>) {
+    let (l_guard, r_guard) = (left.data(), right.data());
+
+    let mut prop_changes = Vec::new();
+    if l_guard.class_name() != r_guard.class_name() {
+        prop_changes.push(KeyPropertyChange::ClassNameChange(l_guard.class_name().cloned(), r_guard.class_name().cloned()));
+    }
+    if l_guard.is_symlink() != r_guard.is_symlink() {
+        prop_changes.push(KeyPropertyChange::SymlinkChange(l_guard.is_symlink(), r_guard.is_symlink()));
+    }
+    if l_guard.is_volatile() != r_guard.is_volatile() {
+        prop_changes.push(KeyPropertyChange::VolatileChange(l_guard.is_volatile(), r_guard.is_volatile()));
+    }
+    if rules.options.report_timestamps && l_guard.modification_time() != r_guard.modification_time() {
+        prop_changes.push(KeyPropertyChange::TimeChange(l_guard.modification_time(), r_guard.modification_time()));
+    }
+    if !prop_changes.is_empty() {
+        changes.push(RegistryChange::KeyModified(path.to_string(), prop_changes));
+    }
+}
+
+fn compare_values<K: Key>(left: &K, right: &K, path: &str, rules: &Rules, changes: &mut Vec<RegistryChange>) {
+    let (l_guard, r_guard) = (left.data(), right.data());
+    let (l_vals, r_vals) = (l_guard.values(), r_guard.values());
 
     for (name, rv) in r_vals.iter() {
-        if !l_vals.contains_key(name) {
+        if !l_vals.contains_key(name) && !rules.ignores_value(path, &rv.name) {
             changes.push(RegistryChange::ValueAdded(path.to_string(), rv.name.clone(), rv.clone()));
         }
     }
     for (name, lv) in l_vals.iter() {
-        if !r_vals.contains_key(name) {
+        if !r_vals.contains_key(name) && !rules.ignores_value(path, &lv.name) {
             changes.push(RegistryChange::ValueDeleted(path.to_string(), lv.name.clone(), lv.clone()));
         }
     }
     for (name, lv) in l_vals.iter() {
-        if let Some(rv) = r_vals.get(name) {
-            if !values_equal(lv, rv) {
-                changes.push(RegistryChange::ValueModified(path.to_string(), lv.name.clone(), lv.clone(), rv.clone()));
-            }
+        if rules.ignores_value(path, &lv.name) {
+            continue;
+        }
+        if let Some(rv) = r_vals.get(name).filter(|rv| !values_equal(lv, rv, rules.options)) {
+            changes.push(RegistryChange::ValueModified(path.to_string(), lv.name.clone(), lv.clone(), rv.clone()));
         }
     }
 }
 
-fn compare_subkeys(left: &KeyNode, right: &KeyNode, path: &str, changes: &mut Vec<RegistryChange>) {
-    let l_sub = left.borrow().subkeys().clone();
-    let r_sub = right.borrow().subkeys().clone();
-    let mut names = l_sub.keys().cloned().collect::<Vec<_>>();
-    for name in r_sub.keys() {
-        if !names.contains(name) {
-            names.push(name.clone());
-        }
-    }
+fn compare_subkeys<K: Key>(left: &K, right: &K, path: &str, rules: &Rules, changes: &mut Vec<RegistryChange>, pending: &mut Vec<Pending<K>>) {
+    let (l_guard, r_guard) = (left.data(), right.data());
+    let (l_sub, r_sub) = (l_guard.subkeys(), r_guard.subkeys());
+    let mut names: Vec<&String> = l_sub.keys().chain(r_sub.keys()).collect();
     names.sort();
+    names.dedup();
     for name in names {
-        let sub_path = if path.is_empty() { name.clone() } else { format!("{}\\{}", path, name) };
-        compare_keys(l_sub.get(&name).cloned(), r_sub.get(&name).cloned(), sub_path, changes);
+        let sub_path = join(path, name);
+        compare_keys(l_sub.get(name).cloned(), r_sub.get(name).cloned(), sub_path, rules, changes, pending);
+    }
+}
+
+fn report_added<K: Key>(node: &K, path: String, rules: &Rules, changes: &mut Vec<RegistryChange>) {
+    if rules.options.compact_subtrees {
+        let tree = filtered_tree(node, &path, rules);
+        changes.push(RegistryChange::SubtreeAdded(path, tree));
+    } else {
+        changes.push(RegistryChange::KeyAdded(path.clone()));
+        changes.extend(metadata_change(node, &path, true));
+        add_subtree_added(node, &path, rules, changes);
+    }
+}
+
+fn report_deleted<K: Key>(node: &K, path: String, rules: &Rules, changes: &mut Vec<RegistryChange>) {
+    if rules.options.compact_subtrees {
+        let tree = filtered_tree(node, &path, rules);
+        changes.push(RegistryChange::SubtreeDeleted(path, tree));
+    } else {
+        changes.extend(metadata_change(node, &path, false));
+        changes.push(RegistryChange::KeyDeleted(path.clone()));
+        add_subtree_deleted(node, &path, rules, changes);
     }
 }
 
-fn add_subtree_added(node: &KeyNode, path: &str, changes: &mut Vec<RegistryChange>) {
-    let guard = node.borrow();
-    for v in guard.values().values() {
+/// Pair held-back deletions with additions whose subtrees are similar enough and splice the
+/// result into `changes`: a pair becomes `KeyMoved`/`KeyRenamed` (at the addition's place)
+/// followed by the differences between the two subtrees, everything else is reported as
+/// added or deleted.
+fn resolve_moves<K: Key>(changes: Vec<RegistryChange>, pending: Vec<Pending<K>>, rules: &Rules) -> Vec<RegistryChange> {
+    let Some(threshold) = rules.move_threshold() else {
+        return changes;
+    };
+    let signatures: Vec<HashSet<String>> = pending.iter().map(|p| signature(&p.node, &p.path, rules)).collect();
+    let mut pairs = Vec::new();
+    for (from, _) in pending.iter().enumerate().filter(|(_, p)| !p.added) {
+        for (to, _) in pending.iter().enumerate().filter(|(_, p)| p.added) {
+            let (a, b) = (&signatures[from], &signatures[to]);
+            let common = a.intersection(b).collect::<Vec<_>>();
+            if !common.iter().any(|item| item.starts_with("V:")) {
+                continue;
+            }
+            let similarity = common.len() as f64 / a.union(b).count() as f64;
+            if similarity >= threshold {
+                pairs.push((similarity, from, to));
+            }
+        }
+    }
+    // Most similar first; the stable sort keeps tree order among equals.
+    pairs.sort_by(|a, b| b.0.total_cmp(&a.0));
+    let mut partner = vec![None; pending.len()];
+    for (_, from, to) in pairs {
+        if partner[from].is_none() && partner[to].is_none() {
+            partner[from] = Some(to);
+            partner[to] = Some(from);
+        }
+    }
+
+    let mut out = Vec::with_capacity(changes.len());
+    let mut rest = changes.into_iter();
+    let mut position = 0;
+    for (idx, p) in pending.iter().enumerate() {
+        out.extend(rest.by_ref().take(p.at - position));
+        position = p.at;
+        match (partner[idx], p.added) {
+            (None, true) => report_added(&p.node, p.path.clone(), rules, &mut out),
+            (None, false) => report_deleted(&p.node, p.path.clone(), rules, &mut out),
+            (Some(_), false) => {}
+            (Some(from), true) => {
+                let source = &pending[from];
+                // Keep the names' case so the patcher (or the inverse) can recreate them.
+                let from = join(parent_of(&source.path), source.node.data().name());
+                let to = join(parent_of(&p.path), p.node.data().name());
+                out.push(match parent_of(&source.path) == parent_of(&p.path) {
+                    true => RegistryChange::KeyRenamed(from, to.clone()),
+                    false => RegistryChange::KeyMoved(from, to.clone()),
+                });
+                let mut residual = Vec::new();
+                let mut nested = Vec::new();
+                compare_keys(Some(source.node.clone()), Some(p.node.clone()), to, rules, &mut residual, &mut nested);
+                out.extend(resolve_moves(residual, nested, rules));
+            }
+        }
+    }
+    out.extend(rest);
+    out
+}
+
+/// What a subtree holds, for similarity: `K:` per key and `V:` per value (with a hash of
+/// its data), by path relative to the subtree.
+fn signature<K: Key>(node: &K, path: &str, rules: &Rules) -> HashSet<String> {
+    fn walk<K: Key>(node: &K, path: &str, rel: &str, rules: &Rules, out: &mut HashSet<String>) {
+        let guard = node.data();
+        out.insert(format!("K:{}", rel));
+        for v in guard.values().values().filter(|v| !rules.ignores_value(path, &v.name)) {
+            let mut hasher = DefaultHasher::new();
+            v.raw_bytes().hash(&mut hasher);
+            out.insert(format!("V:{}:{}:{}:{:x}", rel, v.name.to_uppercase(), v.reg_type(), hasher.finish()));
+        }
+        for (name, sub) in guard.subkeys() {
+            let sub_path = join(path, name);
+            if !rules.ignores_key(&sub_path) {
+                walk(sub, &sub_path, &join(rel, name), rules, out);
+            }
+        }
+    }
+    let mut out = HashSet::new();
+    walk(node, path, "", rules, &mut out);
+    out
+}
+
+fn parent_of(path: &str) -> &str {
+    path.rsplit_once('\\').map(|(parent, _)| parent).unwrap_or("")
+}
+
+fn add_subtree_added<K: Key>(node: &K, path: &str, rules: &Rules, changes: &mut Vec<RegistryChange>) {
+    let guard = node.data();
+    for v in guard.values().values().filter(|v| !rules.ignores_value(path, &v.name)) {
         changes.push(RegistryChange::ValueAdded(path.to_string(), v.name.clone(), v.clone()));
     }
     for (name, sub) in guard.subkeys() {
-        let sub_path = if path.is_empty() { name.clone() } else { format!("{}\\{}", path, name) };
+        let sub_path = join(path, name);
+        if rules.ignores_key(&sub_path) {
+            continue;
+        }
         changes.push(RegistryChange::KeyAdded(sub_path.clone()));
-        add_subtree_added(sub, &sub_path, changes);
+        changes.extend(metadata_change(sub, &sub_path, true));
+        add_subtree_added(sub, &sub_path, rules, changes);
     }
 }
 
-fn add_subtree_deleted(node: &KeyNode, path: &str, changes: &mut Vec<RegistryChange>) {
-    let guard = node.borrow();
-    for v in guard.values().values() {
+fn add_subtree_deleted<K: Key>(node: &K, path: &str, rules: &Rules, changes: &mut Vec<RegistryChange>) {
+    let guard = node.data();
+    for v in guard.values().values().filter(|v| !rules.ignores_value(path, &v.name)) {
         changes.push(RegistryChange::ValueDeleted(path.to_string(), v.name.clone(), v.clone()));
     }
     for (name, sub) in guard.subkeys() {
-        let sub_path = if path.is_empty() { name.clone() } else { format!("{}\\{}", path, name) };
+        let sub_path = join(path, name);
+        if rules.ignores_key(&sub_path) {
+            continue;
+        }
+        changes.extend(metadata_change(sub, &sub_path, false));
         changes.push(RegistryChange::KeyDeleted(sub_path.clone()));
-        add_subtree_deleted(sub, &sub_path, changes);
+        add_subtree_deleted(sub, &sub_path, rules, changes);
+    }
+}
+
+/// `KeyTree::from_node` without the keys and values the rules ignore.
+fn filtered_tree<K: Key>(node: &K, path: &str, rules: &Rules) -> KeyTree {
+    let guard = node.data();
+    KeyTree {
+        name: guard.name().to_string(),
+        class_name: guard.class_name().cloned(),
+        modification_time: guard.modification_time(),
+        is_symlink: guard.is_symlink(),
+        is_volatile: guard.is_volatile(),
+        values: guard.values().values().filter(|v| !rules.ignores_value(path, &v.name)).cloned().collect(),
+        subkeys: guard
+            .subkeys()
+            .iter()
+            .map(|(name, sub)| (join(path, name), sub))
+            .filter(|(sub_path, _)| !rules.ignores_key(sub_path))
+            .map(|(sub_path, sub)| filtered_tree(sub, &sub_path, rules))
+            .collect(),
+    }
+}
+
+/// The `KeyModified` taking an added key from the default metadata to `node`'s, or a
+/// deleted one back, so that flat additions and deletions lose nothing.
+fn metadata_change<K: Key>(node: &K, path: &str, added: bool) -> Option<RegistryChange> {
+    let guard = node.data();
+    metadata_props(guard.class_name(), guard.is_symlink(), guard.is_volatile(), guard.modification_time(), added)
+        .map(|props| RegistryChange::KeyModified(path.to_string(), props))
+}
+
+/// Metadata goes from the defaults to the key's when added and back when deleted.
+fn metadata_props(class_name: Option<&String>, is_symlink: bool, is_volatile: bool, modification_time: u64, added: bool) -> Option<Vec<KeyPropertyChange>> {
+    let mut props = Vec::new();
+    if let Some(class) = class_name {
+        props.push(match added {
+            true => KeyPropertyChange::ClassNameChange(None, Some(class.clone())),
+            false => KeyPropertyChange::ClassNameChange(Some(class.clone()), None),
+        });
+    }
+    if is_symlink {
+        props.push(KeyPropertyChange::SymlinkChange(!added, added));
+    }
+    if is_volatile {
+        props.push(KeyPropertyChange::VolatileChange(!added, added));
+    }
+    if modification_time != 0 {
+        props.push(match added {
+            true => KeyPropertyChange::TimeChange(0, modification_time),
+            false => KeyPropertyChange::TimeChange(modification_time, 0),
+        });
     }
+    (!props.is_empty()).then_some(props)
 }
 
-fn values_equal(a: &RegistryValue, b: &RegistryValue) -> bool {
-    a.reg_type() == b.reg_type() && a.raw_bytes() == b.raw_bytes()
+/// Flat changes for a whole subtree at `path`, in the order `add_subtree_added` uses.
+fn expand_tree(tree: &KeyTree, path: &str, added: bool, changes: &mut Vec<RegistryChange>) {
+    let props = metadata_props(tree.class_name.as_ref(), tree.is_symlink, tree.is_volatile, tree.modification_time, added)
+        .map(|props| RegistryChange::KeyModified(path.to_string(), props));
+    match added {
+        true => changes.extend([Some(RegistryChange::KeyAdded(path.to_string())), props].into_iter().flatten()),
+        false => changes.extend([props, Some(RegistryChange::KeyDeleted(path.to_string()))].into_iter().flatten()),
+    }
+    for v in &tree.values {
+        changes.push(match added {
+            true => RegistryChange::ValueAdded(path.to_string(), v.name.clone(), v.clone()),
+            false => RegistryChange::ValueDeleted(path.to_string(), v.name.clone(), v.clone()),
+        });
+    }
+    for sub in &tree.subkeys {
+        expand_tree(sub, &join(path, &sub.name), added, changes);
+    }
 }
 
+fn values_equal(a: &RegistryValue, b: &RegistryValue, options: &CompareOptions) -> bool {
+    let (ta, tb) = (a.reg_type(), b.reg_type());
+    let comparable = ta == tb || (options.type_tolerant && tolerance_group(ta).is_some() && tolerance_group(ta) == tolerance_group(tb));
+    if !comparable {
+        return false;
+    }
+    let strings = matches!(ta, REG_SZ | REG_EXPAND_SZ | REG_MULTI_SZ);
+    if strings
+        && options.case_insensitive_strings
+        && let (Ok(x), Ok(y)) = (a.as_multi(), b.as_multi())
+    {
+        return x.len() == y.len() && x.iter().zip(&y).all(|(x, y)| x.to_lowercase() == y.to_lowercase());
+    }
+    a.raw_bytes() == b.raw_bytes()
+}
+
+/// Types whose values are interchangeable when their data is the same.
+fn tolerance_group(ty: u32) -> Option<u8> {
+    match ty {
+        REG_NONE | REG_BINARY | REG_DWORD | REG_QWORD => Some(0),
+        REG_SZ | REG_EXPAND_SZ => Some(1),
+        _ => None,
+    }
+}
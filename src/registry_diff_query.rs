@@ -0,0 +1,195 @@
+use std::collections::BTreeMap;
+
+use crate::registry_comparator::{glob_regex, DiffResult, RegistryChange};
+
+/// The variant of a `RegistryChange`, for filtering.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ChangeKind {
+    KeyAdded,
+    KeyDeleted,
+    KeyModified,
+    ValueAdded,
+    ValueDeleted,
+    ValueModified,
+    SubtreeAdded,
+    SubtreeDeleted,
+    KeyMoved,
+    KeyRenamed,
+}
+
+/// Number of changes of each kind, see `DiffResult::summary`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DiffSummary {
+    /// `KeyAdded` and `SubtreeAdded`.
+    pub keys_added: usize,
+    /// `KeyDeleted` and `SubtreeDeleted`.
+    pub keys_deleted: usize,
+    pub keys_modified: usize,
+    /// `KeyMoved` and `KeyRenamed`.
+    pub keys_moved: usize,
+    pub values_added: usize,
+    pub values_deleted: usize,
+    pub values_modified: usize,
+}
+
+impl DiffSummary {
+    pub fn total(&self) -> usize {
+        self.keys_added + self.keys_deleted + self.keys_modified + self.keys_moved + self.values_added + self.values_deleted + self.values_modified
+    }
+}
+
+impl RegistryChange {
+    pub fn kind(&self) -> ChangeKind {
+        match self {
+            RegistryChange::KeyAdded(_) => ChangeKind::KeyAdded,
+            RegistryChange::KeyDeleted(_) => ChangeKind::KeyDeleted,
+            RegistryChange::KeyModified(..) => ChangeKind::KeyModified,
+            RegistryChange::ValueAdded(..) => ChangeKind::ValueAdded,
+            RegistryChange::ValueDeleted(..) => ChangeKind::ValueDeleted,
+            RegistryChange::ValueModified(..) => ChangeKind::ValueModified,
+            RegistryChange::SubtreeAdded(..) => ChangeKind::SubtreeAdded,
+            RegistryChange::SubtreeDeleted(..) => ChangeKind::SubtreeDeleted,
+            RegistryChange::KeyMoved(..) => ChangeKind::KeyMoved,
+            RegistryChange::KeyRenamed(..) => ChangeKind::KeyRenamed,
+        }
+    }
+
+    /// The key the change is about; the destination for moves and renames.
+    pub fn path(&self) -> &str {
+        match self {
+            RegistryChange::KeyAdded(p)
+            | RegistryChange::KeyDeleted(p)
+            | RegistryChange::KeyModified(p, _)
+            | RegistryChange::ValueAdded(p, _, _)
+            | RegistryChange::ValueDeleted(p, _, _)
+            | RegistryChange::ValueModified(p, _, _, _)
+            | RegistryChange::SubtreeAdded(p, _)
+            | RegistryChange::SubtreeDeleted(p, _)
+            | RegistryChange::KeyMoved(_, p)
+            | RegistryChange::KeyRenamed(_, p) => p,
+        }
+    }
+
+    /// The value name for value changes.
+    pub fn value_name(&self) -> Option<&str> {
+        match self {
+            RegistryChange::ValueAdded(_, n, _) | RegistryChange::ValueDeleted(_, n, _) | RegistryChange::ValueModified(_, n, _, _) => Some(n),
+            _ => None,
+        }
+    }
+}
+
+impl DiffResult {
+    /// The changes for which `keep` holds.
+    pub fn filter<F: FnMut(&RegistryChange) -> bool>(&self, mut keep: F) -> DiffResult {
+        DiffResult { changes: self.changes.iter().filter(|c| keep(c)).cloned().collect() }
+    }
+
+    pub fn retain<F: FnMut(&RegistryChange) -> bool>(&mut self, keep: F) {
+        self.changes.retain(keep);
+    }
+
+    /// Split into the changes for which `first` holds and the rest.
+    pub fn partition<F: FnMut(&RegistryChange) -> bool>(&self, first: F) -> (DiffResult, DiffResult) {
+        let (a, b) = self.changes.iter().cloned().partition(first);
+        (DiffResult { changes: a }, DiffResult { changes: b })
+    }
+
+    /// Split into one diff per `key`, keeping the order of the changes within each.
+    pub fn split_by<K: Ord, F: FnMut(&RegistryChange) -> K>(&self, mut key: F) -> BTreeMap<K, DiffResult> {
+        let mut parts: BTreeMap<K, DiffResult> = BTreeMap::new();
+        for change in &self.changes {
+            parts.entry(key(change)).or_insert_with(|| DiffResult { changes: Vec::new() }).changes.push(change.clone());
+        }
+        parts
+    }
+
+    pub fn of_kind(&self, kinds: &[ChangeKind]) -> DiffResult {
+        self.filter(|c| kinds.contains(&c.kind()))
+    }
+
+    /// Changes at `prefix` or below it (whole path components, case-insensitive).
+    pub fn under(&self, prefix: &str) -> DiffResult {
+        self.filter(|c| strip_path(c.path(), prefix).is_some())
+    }
+
+    /// Changes whose key path matches `pattern` (wildcards as in `CompareOptions::ignore_keys`),
+    /// or for value changes whose `key path\value name` does.
+    pub fn matching(&self, pattern: &str) -> DiffResult {
+        let re = glob_regex(pattern);
+        self.filter(|c| re.is_match(c.path()) || c.value_name().is_some_and(|n| re.is_match(&format!("{}\\{}", c.path(), n))))
+    }
+
+    /// Changes grouped by the key they are about, as `path()` spells it.
+    pub fn group_by_key(&self) -> BTreeMap<&str, Vec<&RegistryChange>> {
+        let mut groups: BTreeMap<&str, Vec<&RegistryChange>> = BTreeMap::new();
+        for change in &self.changes {
+            groups.entry(change.path()).or_default().push(change);
+        }
+        groups
+    }
+
+    /// Counts per top-level key (the first path component; `""` for the root itself).
+    pub fn summary(&self) -> BTreeMap<String, DiffSummary> {
+        let mut summary: BTreeMap<String, DiffSummary> = BTreeMap::new();
+        for change in &self.changes {
+            let top = change.path().split('\\').next().unwrap_or_default().to_ascii_uppercase();
+            let counts = summary.entry(top).or_default();
+            match change.kind() {
+                ChangeKind::KeyAdded | ChangeKind::SubtreeAdded => counts.keys_added += 1,
+                ChangeKind::KeyDeleted | ChangeKind::SubtreeDeleted => counts.keys_deleted += 1,
+                ChangeKind::KeyModified => counts.keys_modified += 1,
+                ChangeKind::KeyMoved | ChangeKind::KeyRenamed => counts.keys_moved += 1,
+                ChangeKind::ValueAdded => counts.values_added += 1,
+                ChangeKind::ValueDeleted => counts.values_deleted += 1,
+                ChangeKind::ValueModified => counts.values_modified += 1,
+            }
+        }
+        summary
+    }
+
+    /// The changes below `path` with paths made relative to it, so the result applies to
+    /// that key (e.g. a hive exported with `RegistryWriter::write_subtree_to`). Changes to
+    /// the key itself keep only its properties and values; moves crossing its boundary are
+    /// left out.
+    pub fn subtree(&self, path: &str) -> DiffResult {
+        let rel = |p: &str| strip_path(p, path).map(str::to_string);
+        let mut changes = Vec::new();
+        for change in &self.changes {
+            let rebased = match change {
+                RegistryChange::KeyAdded(p) => rel(p).filter(|r| !r.is_empty()).map(RegistryChange::KeyAdded),
+                RegistryChange::KeyDeleted(p) => rel(p).filter(|r| !r.is_empty()).map(RegistryChange::KeyDeleted),
+                RegistryChange::SubtreeAdded(p, tree) => rel(p).filter(|r| !r.is_empty()).map(|r| RegistryChange::SubtreeAdded(r, tree.clone())),
+                RegistryChange::SubtreeDeleted(p, tree) => rel(p).filter(|r| !r.is_empty()).map(|r| RegistryChange::SubtreeDeleted(r, tree.clone())),
+                RegistryChange::KeyModified(p, props) => rel(p).map(|r| RegistryChange::KeyModified(r, props.clone())),
+                RegistryChange::ValueAdded(k, n, v) => rel(k).map(|r| RegistryChange::ValueAdded(r, n.clone(), v.clone())),
+                RegistryChange::ValueDeleted(k, n, v) => rel(k).map(|r| RegistryChange::ValueDeleted(r, n.clone(), v.clone())),
+                RegistryChange::ValueModified(k, n, old, new) => rel(k).map(|r| RegistryChange::ValueModified(r, n.clone(), old.clone(), new.clone())),
+                RegistryChange::KeyMoved(from, to) | RegistryChange::KeyRenamed(from, to) => match (rel(from), rel(to)) {
+                    (Some(f), Some(t)) if !f.is_empty() && !t.is_empty() => Some(match change {
+                        RegistryChange::KeyMoved(..) => RegistryChange::KeyMoved(f, t),
+                        _ => RegistryChange::KeyRenamed(f, t),
+                    }),
+                    _ => None,
+                },
+            };
+            changes.extend(rebased);
+        }
+        DiffResult { changes }
+    }
+}
+
+/// `path` relative to `base` if it is `base` or below it.
+fn strip_path<'a>(path: &'a str, base: &str) -> Option<&'a str> {
+    let base = base.trim_matches('\\');
+    if base.is_empty() {
+        return Some(path);
+    }
+    let head = path.get(..base.len())?;
+    let rest = &path[base.len()..];
+    match head.eq_ignore_ascii_case(base) {
+        true if rest.is_empty() => Some(""),
+        true => rest.strip_prefix('\\'),
+        false => None,
+    }
+}
@@ -0,0 +1,230 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use thiserror::Error;
+
+use crate::registry_key::{KeyNode, RegistryKey};
+use crate::registry_value::{RegistryValue, RegistryValueData, REG_EXPAND_SZ, REG_MULTI_SZ, REG_SZ};
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum DosPathError {
+    #[error("'{0}' is not an absolute drive path")]
+    NotDrivePath(String),
+    #[error("drive {0}: is not mapped")]
+    UnknownDrive(char),
+    #[error("'{0}' is not reachable through any drive")]
+    NoDrive(PathBuf),
+}
+
+/// Drive letter mappings of a Wine prefix, as found in `<prefix>/dosdevices`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DosDevices {
+    drives: BTreeMap<char, PathBuf>,
+}
+
+impl DosDevices {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read the `c:`, `z:`, ... symlinks in `<prefix>/dosdevices`. Relative targets (Wine
+    /// creates `c:` -> `../drive_c`) are resolved against the `dosdevices` directory;
+    /// device entries such as `c::` or `com1` are ignored.
+    pub fn load<P: AsRef<Path>>(prefix: P) -> io::Result<Self> {
+        let dir = prefix.as_ref().join("dosdevices");
+        let mut devices = Self::new();
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_lowercase();
+            let letter = match name.as_bytes() {
+                [letter @ b'a'..=b'z', b':'] => *letter as char,
+                _ => continue,
+            };
+            let target = match fs::read_link(entry.path()) {
+                Ok(target) => target,
+                Err(_) => continue,
+            };
+            devices.insert(letter, dir.join(target));
+        }
+        Ok(devices)
+    }
+
+    /// Map `letter` to the host directory `target`.
+    pub fn insert<P: AsRef<Path>>(&mut self, letter: char, target: P) {
+        self.drives.insert(letter.to_ascii_lowercase(), normalize(target.as_ref()));
+    }
+
+    pub fn drive(&self, letter: char) -> Option<&Path> {
+        self.drives.get(&letter.to_ascii_lowercase()).map(|p| p.as_path())
+    }
+
+    /// Mapped drives as `(letter, target)`, letters in lowercase.
+    pub fn drives(&self) -> impl Iterator<Item = (char, &Path)> {
+        self.drives.iter().map(|(letter, path)| (*letter, path.as_path()))
+    }
+
+    /// Host path for a Windows path such as `C:\users\me` or `\??\Z:\tmp`. The result is
+    /// lexical: components keep the case used in the registry, see `find_existing`.
+    pub fn to_unix(&self, windows_path: &str) -> Result<PathBuf, DosPathError> {
+        let (letter, components) = split_drive_path(windows_path)?;
+        let mut path = self.drive(letter).ok_or(DosPathError::UnknownDrive(letter.to_ascii_uppercase()))?.to_path_buf();
+        for component in components {
+            path.push(component);
+        }
+        Ok(path)
+    }
+
+    /// Windows path for a host path, using the drive with the longest matching target (so a
+    /// file under `drive_c` maps to `C:` rather than `Z:`).
+    pub fn to_windows<P: AsRef<Path>>(&self, unix_path: P) -> Result<String, DosPathError> {
+        let path = normalize(unix_path.as_ref());
+        let best = self
+            .drives
+            .iter()
+            .filter_map(|(letter, target)| path.strip_prefix(target).ok().map(|rest| (letter, target, rest)))
+            .max_by_key(|(_, target, _)| target.components().count());
+        let (letter, _, rest) = best.ok_or_else(|| DosPathError::NoDrive(path.clone()))?;
+        let parts: Vec<String> = rest.components().map(|c| c.as_os_str().to_string_lossy().to_string()).collect();
+        Ok(format!("{}:\\{}", letter.to_ascii_uppercase(), parts.join("\\")))
+    }
+
+    /// Like `to_unix`, but matches each component case-insensitively against the file system
+    /// the way Wine does. `None` when the file does not exist.
+    pub fn find_existing(&self, windows_path: &str) -> Option<PathBuf> {
+        let (letter, components) = split_drive_path(windows_path).ok()?;
+        let mut path = self.drive(letter)?.to_path_buf();
+        for component in components {
+            let exact = path.join(&component);
+            if fs::symlink_metadata(&exact).is_ok() {
+                path = exact;
+                continue;
+            }
+            let wanted = component.to_lowercase();
+            let found = fs::read_dir(&path)
+                .ok()?
+                .filter_map(|entry| entry.ok())
+                .find(|entry| entry.file_name().to_string_lossy().to_lowercase() == wanted)?;
+            path = found.path();
+        }
+        fs::metadata(&path).ok().map(|_| path)
+    }
+
+    /// Rewrite a Windows path that points below `from` on the host so it points below `to`
+    /// instead, e.g. a `Z:\home\old\prefix\...` path after moving a prefix. `None` when the
+    /// path is not below `from`.
+    pub fn relocate<P: AsRef<Path>, Q: AsRef<Path>>(&self, windows_path: &str, from: P, to: Q) -> Option<String> {
+        let unix = self.to_unix(windows_path).ok()?;
+        let rest = unix.strip_prefix(normalize(from.as_ref())).ok()?;
+        self.to_windows(normalize(to.as_ref()).join(rest)).ok()
+    }
+}
+
+impl RegistryKey {
+    /// Apply `DosDevices::relocate` to every string value (and multi-string entry) under
+    /// `root` that is an absolute drive path. Paths embedded in longer strings such as command
+    /// lines are left alone. Malformed (`RawString`) values stay raw: only their cleanly decoding
+    /// strings are rewritten, every other byte is kept. Returns the number of values changed.
+    pub fn relocate_paths<P: AsRef<Path>, Q: AsRef<Path>>(root: &KeyNode, devices: &DosDevices, from: P, to: Q) -> usize {
+        let relocate = |text: &str| devices.relocate(text, from.as_ref(), to.as_ref());
+        let mut changed = 0;
+        let mut stack = vec![root.clone()];
+        while let Some(node) = stack.pop() {
+            for (_, value) in RegistryKey::snapshot_values(&node) {
+                let relocate_items = |items: &[String]| {
+                    let moved: Vec<Option<String>> = items.iter().map(|s| relocate(s)).collect();
+                    moved.iter().any(|m| m.is_some()).then(|| {
+                        let items = items.iter().zip(moved).map(|(old, new)| new.unwrap_or_else(|| old.clone()));
+                        RegistryValueData::MultiString(items.collect())
+                    })
+                };
+                let data = match &value.data {
+                    RegistryValueData::String(s) => relocate(s).map(RegistryValueData::String),
+                    RegistryValueData::ExpandString(s) => relocate(s).map(RegistryValueData::ExpandString),
+                    RegistryValueData::MultiString(items) => relocate_items(items),
+                    RegistryValueData::RawString(bytes, reg_type @ (REG_SZ | REG_EXPAND_SZ)) => {
+                        relocate_raw(bytes, 1, &relocate).map(|bytes| RegistryValueData::RawString(bytes, *reg_type))
+                    }
+                    RegistryValueData::RawString(bytes, REG_MULTI_SZ) => {
+                        relocate_raw(bytes, usize::MAX, &relocate).map(|bytes| RegistryValueData::RawString(bytes, REG_MULTI_SZ))
+                    }
+                    _ => None,
+                };
+                if let Some(data) = data.filter(|data| *data != value.data) {
+                    node.borrow_mut().set_value(value.name.clone(), RegistryValue::new(value.name, data));
+                    changed += 1;
+                }
+            }
+            stack.extend(RegistryKey::snapshot_subkeys(&node).into_iter().map(|(_, sub)| sub));
+        }
+        changed
+    }
+}
+
+/// Relocate the first `strings` NUL-separated strings of a malformed UTF-16 value in place.
+/// Strings that don't decode cleanly, anything after them and a trailing odd byte are kept
+/// unit for unit.
+fn relocate_raw(bytes: &[u8], strings: usize, relocate: &dyn Fn(&str) -> Option<String>) -> Option<Vec<u8>> {
+    let units: Vec<u16> = bytes.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+    let mut pieces: Vec<Vec<u16>> = units.split(|u| *u == 0).map(<[u16]>::to_vec).collect();
+    let mut changed = false;
+    for piece in pieces.iter_mut().take(strings) {
+        if let Some(moved) = String::from_utf16(piece).ok().and_then(|s| relocate(&s)) {
+            *piece = moved.encode_utf16().collect();
+            changed = true;
+        }
+    }
+    changed.then(|| {
+        let mut out: Vec<u8> = pieces.join(&0).into_iter().flat_map(u16::to_le_bytes).collect();
+        out.extend_from_slice(bytes.chunks_exact(2).remainder());
+        out
+    })
+}
+
+/// Drive letter and path components of `C:\a\b`, accepting `/` separators and the NT
+/// prefixes `\??\` and `\\?\`. `.` and `..` are resolved without leaving the drive root.
+fn split_drive_path(windows_path: &str) -> Result<(char, Vec<String>), DosPathError> {
+    let not_drive = || DosPathError::NotDrivePath(windows_path.to_string());
+    let path = ["\\??\\", "\\\\?\\", "\\\\.\\"]
+        .iter()
+        .find_map(|prefix| windows_path.strip_prefix(prefix))
+        .unwrap_or(windows_path);
+    let mut chars = path.chars();
+    let letter = chars.next().filter(|c| c.is_ascii_alphabetic()).ok_or_else(not_drive)?;
+    let rest = chars.as_str().strip_prefix(':').ok_or_else(not_drive)?;
+    if !(rest.is_empty() || rest.starts_with(['\\', '/'])) {
+        return Err(not_drive());
+    }
+    let mut components: Vec<String> = Vec::new();
+    for part in rest.split(['\\', '/']) {
+        match part {
+            "" | "." => {}
+            ".." => {
+                components.pop();
+            }
+            _ => components.push(part.to_string()),
+        }
+    }
+    Ok((letter.to_ascii_lowercase(), components))
+}
+
+/// Lexically resolve `.` and `..` so drive targets such as `dosdevices/../drive_c` compare
+/// equal to the paths users pass in.
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if out.parent().is_some() {
+                    out.pop();
+                } else if !out.has_root() {
+                    out.push("..");
+                }
+            }
+            other => out.push(other.as_os_str()),
+        }
+    }
+    out
+}
@@ -1,4 +1,7 @@
+use std::rc::Rc;
+
 use crate::architecture::Architecture;
+use crate::registry_clock::FixedClock;
 use crate::registry_key::{KeyNode, RegistryKey};
 use crate::registry_parser::RegistryParser;
 use crate::registry_utils::set_current_time_recursive;
@@ -33,6 +36,13 @@ impl RegistryResult {
         self
     }
 
+    /// Freeze the tree's clock at `FixedClock::reproducible(timestamp)` and stamp every key
+    /// with it, so later changes and the written file do not depend on when they were made.
+    pub fn reproducible(&self, timestamp: Option<u64>) -> &Self {
+        RegistryKey::set_clock(&self.root_key, Rc::new(FixedClock::reproducible(timestamp)));
+        self.update_times()
+    }
+
     pub fn modify<F>(&self, f: F) -> &Self
     where
         F: FnOnce(&mut RegistryKeyDsl),
@@ -74,6 +84,13 @@ impl RegistryDslContext {
         }
     }
 
+    /// Freeze the clock at `FixedClock::reproducible(timestamp)`, stamping the keys created
+    /// so far; keys and values added afterwards get the same time.
+    pub fn reproducible(&mut self, timestamp: Option<u64>) {
+        RegistryKey::set_clock(&self.root, Rc::new(FixedClock::reproducible(timestamp)));
+        set_current_time_recursive(&self.root);
+    }
+
     pub fn root<F>(&mut self, f: F)
     where
         F: FnOnce(&mut RegistryKeyDsl),
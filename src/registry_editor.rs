@@ -1,6 +1,6 @@
 use crate::{
     architecture::Architecture, registry_comparator::RegistryComparator, registry_key::KeyNode,
-    registry_parser::{LoadResult, RegistryParser}, registry_writer::RegistryWriter,
+    registry_parser::{LoadResult, RegistryParser}, registry_writer::{RegistryWriter, WriterOptions},
 };
 
 /// Options for writing/serializing registry data.
@@ -8,6 +8,7 @@ use crate::{
 pub struct EditorOptions {
     pub relative_base: String,
     pub architecture: Architecture,
+    pub writer: WriterOptions,
 }
 
 impl Default for EditorOptions {
@@ -15,6 +16,7 @@ impl Default for EditorOptions {
         Self {
             relative_base: String::new(),
             architecture: Architecture::Unknown,
+            writer: WriterOptions::default(),
         }
     }
 }
@@ -44,10 +46,21 @@ impl RegistryEditor {
         let writer = RegistryWriter {
             relative_base: options.relative_base,
             architecture: options.architecture,
-        };
+        }
+        .with_options(options.writer);
         writer.write_to_file(key, filename)
     }
 
+    /// Stream a registry tree to any writer (stdout, a pipe, a compressed stream, ...).
+    pub fn write_to_with_options<W: std::io::Write>(key: &KeyNode, out: W, options: EditorOptions) -> std::io::Result<()> {
+        let writer = RegistryWriter {
+            relative_base: options.relative_base,
+            architecture: options.architecture,
+        }
+        .with_options(options.writer);
+        writer.write_to(key, out)
+    }
+
     /// Convenience write with defaults (no relative base, unknown arch).
     pub fn write_to_file_default(key: &KeyNode, filename: &str) -> std::io::Result<()> {
         Self::write_to_file_with_options(key, filename, EditorOptions::default())
@@ -58,7 +71,8 @@ impl RegistryEditor {
         let writer = RegistryWriter {
             relative_base: options.relative_base,
             architecture: options.architecture,
-        };
+        }
+        .with_options(options.writer);
         writer.write_to_string(key)
     }
 
@@ -72,5 +86,15 @@ impl RegistryEditor {
         let comparator = RegistryComparator;
         comparator.compare_registries(key1, key2)
     }
+
+    /// Compare two registries with ignore rules and tolerances.
+    pub fn compare_registries_with_options(
+        key1: &KeyNode,
+        key2: &KeyNode,
+        options: &crate::registry_comparator::CompareOptions,
+    ) -> crate::registry_comparator::DiffResult {
+        let comparator = RegistryComparator;
+        comparator.compare_registries_with_options(key1, key2, options)
+    }
 }
 
@@ -0,0 +1,186 @@
+use std::collections::{BTreeMap, HashSet};
+
+use crate::architecture::Architecture;
+use crate::registry_key::{KeyNode, RegistryKey};
+use crate::registry_link::LinkOptions;
+use crate::registry_parser::LoadResult;
+use crate::registry_value::{RegistryValue, ValueError, REG_SZ};
+
+/// Path of the system environment inside `system.reg`.
+pub const SYSTEM_ENVIRONMENT_PATH: &str = "System\\CurrentControlSet\\Control\\Session Manager\\Environment";
+/// Path of the per-user environment inside `user.reg`.
+pub const USER_ENVIRONMENT_PATH: &str = "Environment";
+
+/// How deep variables referring to other variables are expanded.
+const MAX_NESTING: usize = 16;
+
+/// Variables used to expand `REG_EXPAND_SZ` data. Names are case-insensitive; a variable
+/// added later replaces an earlier one, so add sources from lowest to highest precedence.
+#[derive(Debug, Clone, Default)]
+pub struct Environment {
+    vars: BTreeMap<String, (String, String)>,
+}
+
+/// Result of expanding a string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expansion {
+    pub text: String,
+    /// Referenced variables that could not be resolved, in order of appearance. They are
+    /// left in `text` as written, like `ExpandEnvironmentStrings` does.
+    pub unresolved: Vec<String>,
+}
+
+impl Expansion {
+    pub fn is_complete(&self) -> bool {
+        self.unresolved.is_empty()
+    }
+}
+
+impl Environment {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The variables Wine sets up for every process of a prefix owned by `user`.
+    pub fn wine_defaults(user: &str, architecture: Architecture) -> Self {
+        let profile = format!("C:\\users\\{}", user);
+        let mut env = Self::new();
+        env.set("SystemDrive", "C:");
+        env.set("SystemRoot", "C:\\windows");
+        env.set("windir", "C:\\windows");
+        env.set("ComSpec", "C:\\windows\\system32\\cmd.exe");
+        env.set("ProgramFiles", "C:\\Program Files");
+        env.set("CommonProgramFiles", "C:\\Program Files\\Common Files");
+        if architecture != Architecture::Win32 {
+            env.set("ProgramFiles(x86)", "C:\\Program Files (x86)");
+            env.set("CommonProgramFiles(x86)", "C:\\Program Files (x86)\\Common Files");
+            env.set("ProgramW6432", "C:\\Program Files");
+            env.set("CommonProgramW6432", "C:\\Program Files\\Common Files");
+        }
+        env.set("ProgramData", "C:\\ProgramData");
+        env.set("ALLUSERSPROFILE", "C:\\ProgramData");
+        env.set("PUBLIC", "C:\\users\\Public");
+        env.set("USERNAME", user);
+        env.set("APPDATA", &format!("{}\\AppData\\Roaming", profile));
+        env.set("LOCALAPPDATA", &format!("{}\\AppData\\Local", profile));
+        env.set("TEMP", &format!("{}\\AppData\\Local\\Temp", profile));
+        env.set("TMP", &format!("{}\\AppData\\Local\\Temp", profile));
+        env.set("USERPROFILE", &profile);
+        env
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) {
+        self.vars.insert(name.to_uppercase(), (name.to_string(), value.to_string()));
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.vars.get(&name.to_uppercase()).map(|(_, value)| value.as_str())
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        self.vars.remove(&name.to_uppercase()).map(|(_, value)| value)
+    }
+
+    /// Variables as `(name, value)` pairs, sorted by name.
+    pub fn vars(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.vars.values().map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    /// Add every string value of `key`. Values keep their `%VAR%` references and are expanded
+    /// against the whole environment when used.
+    pub fn extend_from_key(&mut self, key: &KeyNode) {
+        for (_, value) in RegistryKey::snapshot_values(key) {
+            if let Some(text) = value.lossy_string() {
+                self.set(&value.name, &text);
+            }
+        }
+    }
+
+    /// Add the hive's `Session Manager\Environment` key, following links such as
+    /// `CurrentControlSet` relative to the hive's base. Returns `false` when the key does not
+    /// exist.
+    pub fn extend_from_system_hive(&mut self, hive: &LoadResult) -> bool {
+        let options = LinkOptions { relative_base: hive.relative_base.clone(), ..LinkOptions::default() };
+        match RegistryKey::find_key_following_links(&hive.root_key, SYSTEM_ENVIRONMENT_PATH, &options) {
+            Ok(key) => {
+                self.extend_from_key(&key);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Add the user hive's `Environment` key. Returns `false` when the key does not exist.
+    pub fn extend_from_user_hive(&mut self, root: &KeyNode) -> bool {
+        match RegistryKey::find_key(root, USER_ENVIRONMENT_PATH) {
+            Some(key) => {
+                self.extend_from_key(&key);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Replace `%VAR%` references in `text`. Variable values may reference other variables;
+    /// self-referencing variables are reported as unresolved.
+    pub fn expand(&self, text: &str) -> Expansion {
+        let mut unresolved = Vec::new();
+        let text = self.expand_nested(text, &mut HashSet::new(), &mut unresolved);
+        Expansion { text, unresolved }
+    }
+
+    fn expand_nested(&self, text: &str, active: &mut HashSet<String>, unresolved: &mut Vec<String>) -> String {
+        let mut out = String::new();
+        let mut rest = text;
+        while let Some(start) = rest.find('%') {
+            out.push_str(&rest[..start]);
+            let after = &rest[start + 1..];
+            let Some(len) = after.find('%') else {
+                rest = &rest[start..];
+                break;
+            };
+            let name = &after[..len];
+            let key = name.to_uppercase();
+            match self.vars.get(&key) {
+                Some((_, value)) if !name.is_empty() && active.len() < MAX_NESTING && !active.contains(&key) => {
+                    active.insert(key.clone());
+                    out.push_str(&self.expand_nested(value, active, unresolved));
+                    active.remove(&key);
+                    rest = &after[len + 1..];
+                }
+                _ => {
+                    // Keep the text and rescan from the closing '%', which may open the next
+                    // reference. Text between two references (e.g. `\` or ` and `) is not reported.
+                    let plausible = !name.is_empty() && !name.contains(|c: char| c.is_whitespace() || c == '\\');
+                    if plausible && !unresolved.iter().any(|u: &String| u.eq_ignore_ascii_case(name)) {
+                        unresolved.push(name.to_string());
+                    }
+                    out.push('%');
+                    out.push_str(name);
+                    rest = &after[len..];
+                }
+            }
+        }
+        out.push_str(rest);
+        out
+    }
+}
+
+impl RegistryValue {
+    /// The string with `%VAR%` references expanded for `REG_EXPAND_SZ`; `REG_SZ` is returned
+    /// unchanged.
+    pub fn expand(&self, env: &Environment) -> Result<Expansion, ValueError> {
+        let text = self.as_string()?;
+        Ok(match self.reg_type() {
+            REG_SZ => Expansion { text, unresolved: Vec::new() },
+            _ => env.expand(&text),
+        })
+    }
+}
+
+impl RegistryKey {
+    /// Expanded string value `name`, see `RegistryValue::expand`.
+    pub fn get_expanded(&self, name: &str, env: &Environment) -> Result<Expansion, ValueError> {
+        self.require_value(name)?.expand(env)
+    }
+}
@@ -0,0 +1,183 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+
+use crate::registry_key::{KeyNode, RegistryKey};
+use crate::registry_value::{RegistryValue, RegistryValueData};
+
+/// Seeds and multipliers of the two independent 64-bit lanes of a fingerprint.
+const SEEDS: [u64; 2] = [0xcbf2_9ce4_8422_2325, 0x6a09_e667_f3bc_c908];
+const MULTIPLIERS: [u64; 2] = [0x9e37_79b9_7f4a_7c15, 0xbf58_476d_1ce4_e5b9];
+
+/// Stable 128-bit hashes of everything below a key (the same across runs, platforms and
+/// versions). The key's own name is not included, so equal subtrees under different names
+/// match. Not cryptographic: trees built to collide on purpose can fool the comparator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Fingerprint {
+    /// Names, types and data of the values, class names, link and volatile flags, and the
+    /// names and contents of the subkeys.
+    pub content: u128,
+    /// `content` plus the last-write times.
+    pub with_times: u128,
+}
+
+impl Fingerprint {
+    /// Fingerprint of `node` without keeping the intermediate results.
+    pub fn of(node: &KeyNode) -> Fingerprint {
+        FingerprintCache::new().fingerprint(node)
+    }
+}
+
+/// Fingerprints of the keys of one or more trees, computed once per key.
+///
+/// Entries are not updated when a tree changes: call `invalidate` on every key changed
+/// (which also drops its ancestors) or `clear`.
+#[derive(Debug, Default)]
+pub struct FingerprintCache {
+    entries: HashMap<*const RefCell<RegistryKey>, (Weak<RefCell<RegistryKey>>, Fingerprint)>,
+}
+
+impl FingerprintCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fingerprint of `node`, computing (and caching) those of its subkeys as needed.
+    pub fn fingerprint(&mut self, node: &KeyNode) -> Fingerprint {
+        if let Some(fingerprint) = self.get(node) {
+            return fingerprint;
+        }
+        let guard = node.borrow();
+        let mut local = Mixer::new();
+        match &guard.class_name {
+            Some(class) => local.u64(1).str(class),
+            None => local.u64(0),
+        };
+        local.u64(guard.is_symlink as u64).u64(guard.is_volatile as u64).u64(guard.values().len() as u64);
+        for value in guard.values().values() {
+            local.str(&value.name).u64(value.reg_type() as u64);
+            value_data(&value.data, &mut local);
+        }
+        local.u64(guard.subkeys().len() as u64);
+
+        let local = local.finish();
+        let mut content = Mixer::new();
+        let mut with_times = Mixer::new();
+        content.u128(local);
+        with_times.u128(local).u64(guard.modification_time);
+        for sub in guard.subkeys().values() {
+            let child = self.fingerprint(sub);
+            let sub = sub.borrow();
+            content.str(&sub.name).u128(child.content);
+            with_times.str(&sub.name).u128(child.with_times);
+        }
+        let fingerprint = Fingerprint { content: content.finish(), with_times: with_times.finish() };
+        self.entries.insert(Rc::as_ptr(node), (Rc::downgrade(node), fingerprint));
+        fingerprint
+    }
+
+    /// The cached fingerprint of `node`, if there is one.
+    pub fn get(&self, node: &KeyNode) -> Option<Fingerprint> {
+        self.entries
+            .get(&Rc::as_ptr(node))
+            .filter(|(weak, _)| weak.upgrade().is_some_and(|live| Rc::ptr_eq(&live, node)))
+            .map(|(_, fingerprint)| *fingerprint)
+    }
+
+    /// Forget `node` and its ancestors, whose fingerprints include it.
+    pub fn invalidate(&mut self, node: &KeyNode) {
+        let mut current = Some(node.clone());
+        while let Some(key) = current {
+            self.entries.remove(&Rc::as_ptr(&key));
+            current = key.borrow().parent();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Value data in its natural form rather than `raw_bytes`, which would allocate. Equal
+/// values of the same variant hash equally, which is all skipping needs.
+fn value_data(data: &RegistryValueData, mixer: &mut Mixer) {
+    match data {
+        RegistryValueData::String(s) | RegistryValueData::ExpandString(s) | RegistryValueData::Link(s) => {
+            mixer.u64(0).str(s);
+        }
+        RegistryValueData::MultiString(items) => {
+            mixer.u64(1).u64(items.len() as u64);
+            for item in items {
+                mixer.str(item);
+            }
+        }
+        RegistryValueData::Dword(n) | RegistryValueData::DwordBigEndian(n) => {
+            mixer.u64(2).u64(*n as u64);
+        }
+        RegistryValueData::Qword(n) => {
+            mixer.u64(3).u64(*n);
+        }
+        RegistryValueData::Binary(bytes, _) => {
+            mixer.u64(4).bytes(bytes);
+        }
+        other => {
+            let raw = RegistryValue::new("", other.clone()).raw_bytes();
+            mixer.u64(5).bytes(&raw);
+        }
+    }
+}
+
+/// Word-at-a-time multiply-xorshift hashing with fixed constants, in two lanes that only
+/// differ in their constants and are joined into 128 bits at the end.
+struct Mixer([u64; 2]);
+
+impl Mixer {
+    fn new() -> Self {
+        Mixer(SEEDS)
+    }
+
+    fn u64(&mut self, word: u64) -> &mut Self {
+        for (lane, multiplier) in self.0.iter_mut().zip(MULTIPLIERS) {
+            let h = (*lane ^ word).wrapping_mul(multiplier);
+            *lane = h ^ (h >> 29);
+        }
+        self
+    }
+
+    fn u128(&mut self, word: u128) -> &mut Self {
+        self.u64(word as u64).u64((word >> 64) as u64)
+    }
+
+    /// Length-prefixed, so consecutive fields can't run into each other.
+    fn bytes(&mut self, bytes: &[u8]) -> &mut Self {
+        self.u64(bytes.len() as u64);
+        let mut chunks = bytes.chunks_exact(8);
+        for chunk in &mut chunks {
+            self.u64(u64::from_le_bytes(chunk.try_into().expect("chunk of 8")));
+        }
+        let mut tail = [0u8; 8];
+        tail[..chunks.remainder().len()].copy_from_slice(chunks.remainder());
+        self.u64(u64::from_le_bytes(tail))
+    }
+
+    fn str(&mut self, s: &str) -> &mut Self {
+        self.bytes(s.as_bytes())
+    }
+
+    fn finish(&self) -> u128 {
+        let [low, high] = self.0.map(|mut h| {
+            h ^= h >> 33;
+            h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+            h ^ (h >> 33)
+        });
+        (high as u128) << 64 | low as u128
+    }
+}
@@ -2,7 +2,8 @@ use std::collections::BTreeMap;
 use std::rc::{Rc, Weak};
 use std::cell::RefCell;
 
-use crate::registry_value::RegistryValue;
+use crate::registry_clock::{Clock, SystemClock};
+use crate::registry_value::{RegistryValue, ValueError};
 use crate::{
     registry_comparator::{DiffResult, RegistryComparator},
     registry_patcher::{PatchOptions, PatchResult, RegistryPatcher},
@@ -20,13 +21,14 @@ pub struct RegistryKey {
     pub is_volatile: bool,
     pub is_dirty: bool,
     parent: Option<Weak<RefCell<RegistryKey>>>,
+    clock: Option<Rc<dyn Clock>>,
     subkeys: BTreeMap<String, KeyNode>,
     values: BTreeMap<String, RegistryValue>,
 }
 
 impl RegistryKey {
     pub fn create_root() -> KeyNode {
-        let root = Rc::new(RefCell::new(Self {
+        Rc::new(RefCell::new(Self {
             name: String::new(),
             class_name: None,
             modification_time: 0,
@@ -34,25 +36,26 @@ impl RegistryKey {
             is_volatile: false,
             is_dirty: false,
             parent: None,
+            clock: None,
             subkeys: BTreeMap::new(),
             values: BTreeMap::new(),
-        }));
-        root
+        }))
     }
 
-    fn new_with_parent(parent: &KeyNode, name: impl Into<String>) -> KeyNode {
-        let node = Rc::new(RefCell::new(Self {
+    /// A key under `parent`, sharing its clock (passed in because `parent` may be borrowed).
+    fn new_with_parent(parent: &KeyNode, name: impl Into<String>, modification_time: u64, is_dirty: bool, clock: Option<Rc<dyn Clock>>) -> KeyNode {
+        Rc::new(RefCell::new(Self {
             name: name.into(),
             class_name: None,
-            modification_time: 0,
+            modification_time,
             is_symlink: false,
             is_volatile: false,
-            is_dirty: true,
+            is_dirty,
             parent: Some(Rc::downgrade(parent)),
+            clock,
             subkeys: BTreeMap::new(),
             values: BTreeMap::new(),
-        }));
-        node
+        }))
     }
 
     pub fn subkeys(&self) -> &BTreeMap<String, KeyNode> {
@@ -75,10 +78,76 @@ impl RegistryKey {
         self.values.get(&normalize(name))
     }
 
+    /// Look up a value for the typed getters, failing with `ValueError::Missing`.
+    pub fn require_value(&self, name: &str) -> Result<&RegistryValue, ValueError> {
+        self.get_value(name).ok_or_else(|| ValueError::Missing(name.to_string()))
+    }
+
+    /// See [`RegistryValue::as_string`] for accepted types.
+    pub fn get_string(&self, name: &str) -> Result<String, ValueError> {
+        self.require_value(name)?.as_string()
+    }
+
+    /// See [`RegistryValue::as_u32`] for accepted types.
+    pub fn get_u32(&self, name: &str) -> Result<u32, ValueError> {
+        self.require_value(name)?.as_u32()
+    }
+
+    /// See [`RegistryValue::as_u64`] for accepted types.
+    pub fn get_u64(&self, name: &str) -> Result<u64, ValueError> {
+        self.require_value(name)?.as_u64()
+    }
+
+    /// See [`RegistryValue::as_bool`] for accepted types.
+    pub fn get_bool(&self, name: &str) -> Result<bool, ValueError> {
+        self.require_value(name)?.as_bool()
+    }
+
+    /// See [`RegistryValue::as_multi`] for accepted types.
+    pub fn get_multi(&self, name: &str) -> Result<Vec<String>, ValueError> {
+        self.require_value(name)?.as_multi()
+    }
+
+    /// See [`RegistryValue::as_bytes`]; only fails if the value is missing.
+    pub fn get_bytes(&self, name: &str) -> Result<Vec<u8>, ValueError> {
+        Ok(self.require_value(name)?.as_bytes())
+    }
+
+    /// Install the clock used to stamp last-write times on mutations of `node` and its
+    /// descendants, including keys created below it later. Trees default to `SystemClock`.
+    pub fn set_clock(node: &KeyNode, clock: Rc<dyn Clock>) {
+        let subkeys: Vec<KeyNode> = {
+            let mut guard = node.borrow_mut();
+            guard.clock = Some(clock.clone());
+            guard.subkeys.values().cloned().collect()
+        };
+        for sub in &subkeys {
+            Self::set_clock(sub, clock.clone());
+        }
+    }
+
+    /// The clock installed on `node` with [`RegistryKey::set_clock`], if any.
+    pub fn clock(node: &KeyNode) -> Option<Rc<dyn Clock>> {
+        node.borrow().clock.clone()
+    }
+
+    /// Current time of the clock governing `node`.
+    pub fn current_time(node: &KeyNode) -> u64 {
+        node.borrow().now()
+    }
+
+    /// Every key keeps its own handle on the tree's clock, so stamping never walks the ancestors.
+    fn now(&self) -> u64 {
+        match &self.clock {
+            Some(clock) => clock.now(),
+            None => SystemClock.now(),
+        }
+    }
+
     pub fn set_value(&mut self, name: impl Into<String>, value: RegistryValue) {
         let key = normalize(&name.into());
         self.values.insert(key, value);
-        self.mark_dirty();
+        self.touch();
     }
 
     pub fn set_value_for_loading(&mut self, name: impl Into<String>, value: RegistryValue) {
@@ -90,7 +159,7 @@ impl RegistryKey {
         let key = normalize(name);
         let removed = self.values.remove(&key).is_some();
         if removed {
-            self.mark_dirty();
+            self.touch();
         }
         removed
     }
@@ -110,9 +179,15 @@ impl RegistryKey {
         if let Some(existing) = parent.borrow().subkeys.get(&key) {
             return existing.clone();
         }
-        let new = Self::new_with_parent(parent, name_str);
-        parent.borrow_mut().subkeys.insert(key, new.clone());
-        parent.borrow_mut().mark_dirty();
+        let (now, clock) = {
+            let guard = parent.borrow();
+            (guard.now(), guard.clock.clone())
+        };
+        let new = Self::new_with_parent(parent, name_str, now, true, clock);
+        let mut guard = parent.borrow_mut();
+        guard.subkeys.insert(key, new.clone());
+        guard.modification_time = now;
+        guard.mark_dirty();
         new
     }
 
@@ -127,8 +202,10 @@ impl RegistryKey {
                 if let Some(existing) = guard.subkeys.get(&normalize(segment)) {
                     existing.clone()
                 } else {
-                    let new = Self::new_with_parent(&current, segment);
+                    let now = guard.now();
+                    let new = Self::new_with_parent(&current, segment, now, true, guard.clock.clone());
                     guard.subkeys.insert(normalize(segment), new.clone());
+                    guard.modification_time = now;
                     guard.mark_dirty();
                     new
                 }
@@ -138,6 +215,26 @@ impl RegistryKey {
         current
     }
 
+    /// Like `create_key_recursive`, but leaves timestamps and dirty flags untouched so loaders
+    /// can restore them from the source.
+    pub fn create_key_recursive_for_loading(parent: &KeyNode, path: &str) -> KeyNode {
+        let mut current = parent.clone();
+        for segment in path.split('\\').filter(|s| !s.is_empty()) {
+            let next = {
+                let mut guard = current.borrow_mut();
+                if let Some(existing) = guard.subkeys.get(&normalize(segment)) {
+                    existing.clone()
+                } else {
+                    let new = Self::new_with_parent(&current, segment, 0, false, guard.clock.clone());
+                    guard.subkeys.insert(normalize(segment), new.clone());
+                    new
+                }
+            };
+            current = next;
+        }
+        current
+    }
+
     pub fn find_key(parent: &KeyNode, path: &str) -> Option<KeyNode> {
         if path.is_empty() {
             return Some(parent.clone());
@@ -197,7 +294,7 @@ impl RegistryKey {
         }
         let removed = guard.subkeys.remove(&key).is_some();
         if removed {
-            guard.mark_dirty();
+            guard.touch();
         }
         removed
     }
@@ -216,6 +313,12 @@ impl RegistryKey {
         parts.join("\\\\")
     }
 
+    /// Record a mutation: stamp the last-write time like wineserver's `touch_key` and mark dirty.
+    fn touch(&mut self) {
+        self.modification_time = self.now();
+        self.mark_dirty();
+    }
+
     fn mark_dirty(&mut self) {
         self.is_dirty = true;
         let mut current = self.parent.clone();
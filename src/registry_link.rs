@@ -0,0 +1,188 @@
+use std::collections::VecDeque;
+
+use thiserror::Error;
+
+use crate::registry_key::{KeyNode, RegistryKey};
+use crate::registry_value::{RegistryValue, RegistryValueData, REG_LINK};
+
+/// Name of the value holding a link key's target, as written by wineserver.
+pub const SYMBOLIC_LINK_VALUE: &str = "SymbolicLinkValue";
+
+/// Options for link-following lookups.
+#[derive(Debug, Clone)]
+pub struct LinkOptions {
+    /// The hive's `;; All keys relative to` base (e.g. `REGISTRY\\Machine`), used to map absolute
+    /// link targets such as `\Registry\Machine\Software\Classes` onto the loaded tree.
+    pub relative_base: String,
+    /// Return the link key itself when it is the last path segment instead of its target.
+    pub open_link: bool,
+    /// Maximum number of links followed during a single lookup; following more is reported as
+    /// a loop. A chain may pass through the same link more than once.
+    pub max_depth: usize,
+}
+
+impl Default for LinkOptions {
+    fn default() -> Self {
+        Self {
+            relative_base: String::new(),
+            open_link: false,
+            max_depth: 16,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum LinkError {
+    #[error("key '{0}' not found")]
+    NotFound(String),
+    #[error("link '{link}' points to missing key '{target}'")]
+    Dangling { link: String, target: String },
+    #[error("link '{link}' has no {SYMBOLIC_LINK_VALUE} of type REG_LINK")]
+    MissingTarget { link: String },
+    #[error("link '{link}' points to '{target}', outside of this hive")]
+    OutsideHive { link: String, target: String },
+    #[error("link loop detected at '{link}'")]
+    Loop { link: String },
+}
+
+/// A link key whose target cannot be resolved within the tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DanglingLink {
+    pub path: String,
+    pub error: LinkError,
+}
+
+impl RegistryKey {
+    /// Target of a link key, decoded from its UTF-16 `SymbolicLinkValue`.
+    pub fn link_target(node: &KeyNode) -> Option<String> {
+        let guard = node.borrow();
+        let value = guard.get_value(SYMBOLIC_LINK_VALUE)?;
+        match &value.data {
+            RegistryValueData::Link(target) => Some(target.trim_end_matches('\u{0}').to_string()),
+            // Malformed UTF-16 stays `Binary`; decode it lossily.
+            RegistryValueData::Binary(bytes, REG_LINK) => {
+                let units: Vec<u16> = bytes.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+                Some(String::from_utf16_lossy(&units).trim_end_matches('\u{0}').to_string())
+            }
+            _ => None,
+        }
+    }
+
+    /// Like `find_key`, but follows symbolic link keys along the way.
+    pub fn find_key_following_links(root: &KeyNode, path: &str, options: &LinkOptions) -> Result<KeyNode, LinkError> {
+        let mut remaining: VecDeque<String> = split_path(path);
+        let mut current = root.clone();
+        let mut hops = 0;
+        // Segments at the front of `remaining` that came from a link target rather than `path`.
+        let mut from_link: Option<(String, String, usize)> = None;
+
+        while let Some(segment) = remaining.pop_front() {
+            let next = current.borrow().get_subkey(&segment);
+            let next = match next {
+                Some(n) => n,
+                None => {
+                    return Err(match from_link {
+                        Some((link, target, _)) => LinkError::Dangling { link, target },
+                        None => LinkError::NotFound(path.to_string()),
+                    });
+                }
+            };
+            from_link = from_link.and_then(|(link, target, left)| (left > 1).then(|| (link, target, left - 1)));
+
+            let follow = next.borrow().is_symlink && !(remaining.is_empty() && options.open_link);
+            if !follow {
+                current = next;
+                continue;
+            }
+
+            let link = RegistryKey::get_full_path(&next).replace("\\\\", "\\");
+            hops += 1;
+            if hops > options.max_depth {
+                return Err(LinkError::Loop { link });
+            }
+            let target = RegistryKey::link_target(&next).ok_or_else(|| LinkError::MissingTarget { link: link.clone() })?;
+            let relative = relative_link_target(&target, &options.relative_base)
+                .ok_or_else(|| LinkError::OutsideHive { link: link.clone(), target: target.clone() })?;
+            let mut segments = split_path(&relative);
+            // A link to the hive base adds no segments, so what follows still came from before it.
+            if !segments.is_empty() {
+                from_link = Some((link, target, segments.len()));
+            }
+            segments.extend(remaining);
+            remaining = segments;
+            current = root.clone();
+        }
+        Ok(current)
+    }
+
+    /// Create (or convert) the key at `link_path` into a link pointing to `target_path`.
+    /// Both paths are relative to `root`; the stored target is made absolute using
+    /// `options.relative_base` the way Wine stores it.
+    pub fn create_link(root: &KeyNode, link_path: &str, target_path: &str, options: &LinkOptions) -> KeyNode {
+        let node = RegistryKey::create_key_recursive(root, link_path);
+        let target = absolute_link_target(target_path, &options.relative_base);
+        let mut guard = node.borrow_mut();
+        guard.is_symlink = true;
+        guard.set_value(
+            SYMBOLIC_LINK_VALUE,
+            RegistryValue::new(SYMBOLIC_LINK_VALUE, RegistryValueData::Link(target)),
+        );
+        drop(guard);
+        node
+    }
+
+    /// Every link key under `root` whose target cannot be resolved.
+    pub fn find_dangling_links(root: &KeyNode, options: &LinkOptions) -> Vec<DanglingLink> {
+        let mut out = Vec::new();
+        collect_dangling(root, root, String::new(), options, &mut out);
+        out
+    }
+}
+
+fn collect_dangling(root: &KeyNode, node: &KeyNode, path: String, options: &LinkOptions, out: &mut Vec<DanglingLink>) {
+    if node.borrow().is_symlink {
+        let follow = LinkOptions { open_link: false, ..options.clone() };
+        if let Err(error) = RegistryKey::find_key_following_links(root, &path, &follow) {
+            out.push(DanglingLink { path, error });
+        }
+        return;
+    }
+    for (_, sub) in RegistryKey::snapshot_subkeys(node) {
+        let name = sub.borrow().name.clone();
+        let sub_path = if path.is_empty() { name } else { format!("{}\\{}", path, name) };
+        collect_dangling(root, &sub, sub_path, options, out);
+    }
+}
+
+fn split_path(path: &str) -> VecDeque<String> {
+    path.split('\\').filter(|s| !s.is_empty()).map(|s| s.to_string()).collect()
+}
+
+/// The hive base as an absolute registry path, e.g. `\REGISTRY\Machine`.
+fn absolute_base(relative_base: &str) -> String {
+    let base = relative_base.replace("\\\\", "\\");
+    let base = base.trim_matches('\\');
+    if base.is_empty() {
+        String::new()
+    } else {
+        format!("\\{}", base)
+    }
+}
+
+fn relative_link_target(target: &str, relative_base: &str) -> Option<String> {
+    let base = absolute_base(relative_base);
+    let target = target.trim_end_matches('\\');
+    if target.len() < base.len() || !target.is_char_boundary(base.len()) {
+        return None;
+    }
+    let (head, rest) = target.split_at(base.len());
+    if !head.eq_ignore_ascii_case(&base) || !(rest.is_empty() || rest.starts_with('\\')) {
+        return None;
+    }
+    Some(rest.trim_start_matches('\\').to_string())
+}
+
+fn absolute_link_target(target_path: &str, relative_base: &str) -> String {
+    let relative = split_path(target_path).into_iter().collect::<Vec<_>>().join("\\");
+    format!("{}\\{}", absolute_base(relative_base), relative)
+}
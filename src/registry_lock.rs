@@ -0,0 +1,88 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// An advisory `name.lock` file next to a registry file, removed when dropped. Cooperating
+/// tools take it around load/modify/save so they do not overwrite each other's changes.
+#[derive(Debug)]
+pub struct LockFile {
+    path: PathBuf,
+}
+
+impl LockFile {
+    /// Lock `registry_path`. Fails with `ErrorKind::WouldBlock` while another live process
+    /// holds the lock; a lock left behind by a process that no longer exists is taken over.
+    pub fn acquire<P: AsRef<Path>>(registry_path: P) -> io::Result<Self> {
+        let path = lock_path(registry_path.as_ref());
+        for _ in 0..2 {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    writeln!(file, "{}", std::process::id())?;
+                    return Ok(Self { path });
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    if !is_stale(&path) {
+                        break;
+                    }
+                    let _ = fs::remove_file(&path);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Err(io::Error::new(io::ErrorKind::WouldBlock, format!("'{}' is locked by another process", path.display())))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for LockFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+fn lock_path(registry_path: &Path) -> PathBuf {
+    let file_name = registry_path.file_name().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    registry_path.with_file_name(format!("{}.lock", file_name))
+}
+
+/// A lock is stale when it names a process that is gone. Without `/proc` this cannot be
+/// told, so the lock is respected.
+fn is_stale(lock: &Path) -> bool {
+    let pid = match fs::read_to_string(lock).ok().and_then(|s| s.trim().parse::<u32>().ok()) {
+        Some(pid) => pid,
+        None => return false,
+    };
+    Path::new("/proc/self").exists() && !Path::new(&format!("/proc/{}", pid)).exists()
+}
+
+/// Directory in which wineserver for `prefix` creates its socket:
+/// `<socket_root>/server-<dev>-<inode>`, where `socket_root` defaults to `/tmp/.wine-<uid>`
+/// and `dev`/`inode` identify the prefix directory.
+#[cfg(unix)]
+pub fn wineserver_dir<P: AsRef<Path>>(prefix: P, socket_root: Option<&Path>) -> io::Result<PathBuf> {
+    use std::os::unix::fs::MetadataExt;
+
+    let meta = fs::metadata(prefix)?;
+    // wineserver insists on the prefix being owned by the user running it.
+    let root = match socket_root {
+        Some(root) => root.to_path_buf(),
+        None => PathBuf::from(format!("/tmp/.wine-{}", meta.uid())),
+    };
+    Ok(root.join(format!("server-{:x}-{:x}", meta.dev(), meta.ino())))
+}
+
+/// Whether a wineserver is serving `prefix`, i.e. its socket accepts connections. A socket
+/// left behind by a crashed server does not count.
+#[cfg(unix)]
+pub fn wineserver_running<P: AsRef<Path>>(prefix: P, socket_root: Option<&Path>) -> io::Result<bool> {
+    let socket = wineserver_dir(prefix, socket_root)?.join("socket");
+    Ok(std::os::unix::net::UnixStream::connect(socket).is_ok())
+}
+
+#[cfg(not(unix))]
+pub fn wineserver_running<P: AsRef<Path>>(_prefix: P, _socket_root: Option<&Path>) -> io::Result<bool> {
+    Ok(false)
+}
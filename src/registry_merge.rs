@@ -0,0 +1,306 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+
+use crate::registry_comparator::{CompareOptions, DiffResult, KeyPropertyChange, RegistryChange, RegistryComparator};
+use crate::registry_key::{KeyNode, RegistryKey};
+use crate::registry_patcher::{PatchFailure, PatchOptions, RegistryPatcher};
+use crate::registry_tree::KeyTree;
+use crate::registry_value::RegistryValue;
+
+/// Three-way merge of two trees derived from a common base.
+pub struct RegistryMerger;
+
+/// Options for `RegistryMerger::merge`.
+#[derive(Debug, Clone, Default)]
+pub struct MergeOptions {
+    /// How both sides are compared with the base; `compact_subtrees` and `move_threshold`
+    /// are ignored. Ignored keys and values keep their base state.
+    pub compare: CompareOptions,
+    /// Applied to every conflict.
+    pub strategy: MergeStrategy,
+}
+
+/// How a conflict is settled in the merged tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum MergeStrategy {
+    /// Leave the conflicting part as it is in the base.
+    #[default]
+    KeepBase,
+    Ours,
+    Theirs,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum MergeSide {
+    Ours,
+    Theirs,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MergeConflict {
+    /// Key the conflict is about.
+    pub path: String,
+    pub kind: ConflictKind,
+    /// The strategy the merged tree reflects.
+    pub resolution: MergeStrategy,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ConflictKind {
+    /// Both sides changed a value differently; `None` is absent (added or deleted).
+    Value {
+        name: String,
+        base: Option<RegistryValue>,
+        ours: Option<RegistryValue>,
+        theirs: Option<RegistryValue>,
+    },
+    /// One side deleted the key while the other changed something below it (`changes`).
+    DeleteModify { deleted_by: MergeSide, changes: Vec<RegistryChange> },
+    /// Both sides set a key property (class name, last-write time) to different values.
+    KeyProperty { ours: KeyPropertyChange, theirs: KeyPropertyChange },
+}
+
+#[derive(Debug)]
+pub struct MergeResult {
+    pub tree: KeyNode,
+    pub conflicts: Vec<MergeConflict>,
+    /// Merged changes the patcher could not apply to `tree`; the rest of the merge is still
+    /// applied, so check this (or `has_failures`) before trusting `tree`.
+    pub failed: Vec<PatchFailure>,
+}
+
+impl MergeResult {
+    pub fn has_conflicts(&self) -> bool {
+        !self.conflicts.is_empty()
+    }
+
+    pub fn has_failures(&self) -> bool {
+        !self.failed.is_empty()
+    }
+
+    /// Conflicts left at their base state.
+    pub fn unresolved(&self) -> Vec<&MergeConflict> {
+        self.conflicts.iter().filter(|c| c.resolution == MergeStrategy::KeepBase).collect()
+    }
+}
+
+impl RegistryMerger {
+    /// Apply the changes from `base` to `ours` and from `base` to `theirs` to a copy of
+    /// `base`, settling every conflict with `options.strategy`.
+    pub fn merge(&self, base: &KeyNode, ours: &KeyNode, theirs: &KeyNode, options: &MergeOptions) -> MergeResult {
+        self.merge_with(base, ours, theirs, &options.compare, |_| options.strategy)
+    }
+
+    /// Like `merge`, asking `resolve` for the strategy of each conflict in turn.
+    pub fn merge_with<F>(&self, base: &KeyNode, ours: &KeyNode, theirs: &KeyNode, compare: &CompareOptions, mut resolve: F) -> MergeResult
+    where
+        F: FnMut(&MergeConflict) -> MergeStrategy,
+    {
+        let compare = CompareOptions { compact_subtrees: false, move_threshold: None, ..compare.clone() };
+        let ours_items = items(&RegistryComparator.compare_registries_with_options(base, ours, &compare));
+        let theirs_items = items(&RegistryComparator.compare_registries_with_options(base, theirs, &compare));
+
+        let mut found: Vec<(MergeConflict, Vec<&Item>, Vec<&Item>)> = Vec::new();
+        let mut regions: Vec<&str> = Vec::new();
+        for (side, deleted, other) in [(MergeSide::Ours, &ours_items, &theirs_items), (MergeSide::Theirs, &theirs_items, &ours_items)] {
+            for root in deleted_roots(deleted) {
+                if regions.iter().any(|region| within(root, region)) {
+                    continue;
+                }
+                let changes: Vec<RegistryChange> = other
+                    .iter()
+                    .filter(|i| within(i.path(), root) && !deleted.iter().any(|d| d.target == i.target && d.same_effect(i)))
+                    .map(|i| i.change.clone())
+                    .collect();
+                if changes.is_empty() {
+                    continue;
+                }
+                regions.push(root);
+                let conflict = MergeConflict {
+                    path: root.to_string(),
+                    kind: ConflictKind::DeleteModify { deleted_by: side, changes },
+                    resolution: MergeStrategy::KeepBase,
+                };
+                let ours_in = ours_items.iter().filter(|i| within(i.path(), root)).collect();
+                let theirs_in = theirs_items.iter().filter(|i| within(i.path(), root)).collect();
+                found.push((conflict, ours_in, theirs_in));
+            }
+        }
+
+        let mut targets: BTreeMap<&Target, (Option<&Item>, Option<&Item>)> = BTreeMap::new();
+        for item in ours_items.iter().filter(|i| !regions.iter().any(|r| within(i.path(), r))) {
+            targets.entry(&item.target).or_default().0 = Some(item);
+        }
+        for item in theirs_items.iter().filter(|i| !regions.iter().any(|r| within(i.path(), r))) {
+            targets.entry(&item.target).or_default().1 = Some(item);
+        }
+
+        let mut ours_changes = Vec::new();
+        let mut theirs_changes = Vec::new();
+        for (ours_item, theirs_item) in targets.into_values() {
+            match (ours_item, theirs_item) {
+                (Some(o), Some(t)) if !o.same_effect(t) => {
+                    let kind = match (&o.change, &t.change) {
+                        (RegistryChange::KeyModified(_, op), RegistryChange::KeyModified(_, tp)) => {
+                            ConflictKind::KeyProperty { ours: op[0].clone(), theirs: tp[0].clone() }
+                        }
+                        (oc, tc) => ConflictKind::Value {
+                            name: value_name(oc).or_else(|| value_name(tc)).unwrap_or_default(),
+                            base: old_value(oc).or_else(|| old_value(tc)),
+                            ours: o.new_value().cloned(),
+                            theirs: t.new_value().cloned(),
+                        },
+                    };
+                    let conflict = MergeConflict { path: o.path().to_string(), kind, resolution: MergeStrategy::KeepBase };
+                    found.push((conflict, vec![o], vec![t]));
+                }
+                (Some(o), _) => ours_changes.push(o),
+                (None, Some(t)) => theirs_changes.push(t),
+                (None, None) => {}
+            }
+        }
+
+        found.sort_by(|a, b| a.0.path.cmp(&b.0.path));
+        let mut conflicts = Vec::with_capacity(found.len());
+        for (mut conflict, ours_in, theirs_in) in found {
+            conflict.resolution = resolve(&conflict);
+            match conflict.resolution {
+                MergeStrategy::KeepBase => {}
+                MergeStrategy::Ours => ours_changes.extend(ours_in),
+                MergeStrategy::Theirs => theirs_changes.extend(theirs_in),
+            }
+            conflicts.push(conflict);
+        }
+
+        let mut changes = Vec::new();
+        changes.extend(ours_changes.into_iter().map(|i| with_real_case(&i.change, ours)));
+        changes.extend(theirs_changes.into_iter().map(|i| with_real_case(&i.change, theirs)));
+        let tree = KeyTree::from_node(base).to_root();
+        let options = PatchOptions { ignore_failures: true, delete_empty_keys: false, ..PatchOptions::default() };
+        let failed = RegistryPatcher.apply_patch(&tree, &DiffResult { changes }, options).failed;
+        MergeResult { tree, conflicts, failed }
+    }
+}
+
+/// What a change is about: two changes with the same target either agree or conflict.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Target {
+    Key(String),
+    Value(String, String),
+    Property(String, u8),
+}
+
+/// One change of a side's diff, with `KeyModified` split into one per property.
+struct Item {
+    target: Target,
+    change: RegistryChange,
+}
+
+impl Item {
+    fn path(&self) -> &str {
+        match &self.target {
+            Target::Key(p) | Target::Value(p, _) | Target::Property(p, _) => p,
+        }
+    }
+
+    fn new_value(&self) -> Option<&RegistryValue> {
+        match &self.change {
+            RegistryChange::ValueAdded(_, _, v) | RegistryChange::ValueModified(_, _, _, v) => Some(v),
+            _ => None,
+        }
+    }
+
+    fn same_effect(&self, other: &Item) -> bool {
+        match (&self.change, &other.change) {
+            (RegistryChange::KeyModified(_, a), RegistryChange::KeyModified(_, b)) => match (&a[0], &b[0]) {
+                (KeyPropertyChange::ClassNameChange(_, x), KeyPropertyChange::ClassNameChange(_, y)) => x == y,
+                (KeyPropertyChange::SymlinkChange(_, x), KeyPropertyChange::SymlinkChange(_, y)) => x == y,
+                (KeyPropertyChange::VolatileChange(_, x), KeyPropertyChange::VolatileChange(_, y)) => x == y,
+                (KeyPropertyChange::TimeChange(_, x), KeyPropertyChange::TimeChange(_, y)) => x == y,
+                _ => false,
+            },
+            (RegistryChange::KeyAdded(_), RegistryChange::KeyAdded(_)) | (RegistryChange::KeyDeleted(_), RegistryChange::KeyDeleted(_)) => true,
+            _ => match (self.new_value(), other.new_value()) {
+                (Some(a), Some(b)) => a.reg_type() == b.reg_type() && a.raw_bytes() == b.raw_bytes(),
+                (None, None) => matches!(self.target, Target::Value(..)),
+                _ => false,
+            },
+        }
+    }
+}
+
+fn items(diff: &DiffResult) -> Vec<Item> {
+    let mut items = Vec::new();
+    for change in &diff.changes {
+        match change {
+            RegistryChange::KeyAdded(p) | RegistryChange::KeyDeleted(p) => items.push(Item { target: Target::Key(p.clone()), change: change.clone() }),
+            RegistryChange::KeyModified(p, props) => {
+                for prop in props {
+                    let kind = match prop {
+                        KeyPropertyChange::ClassNameChange(..) => 0,
+                        KeyPropertyChange::SymlinkChange(..) => 1,
+                        KeyPropertyChange::VolatileChange(..) => 2,
+                        KeyPropertyChange::TimeChange(..) => 3,
+                    };
+                    let change = RegistryChange::KeyModified(p.clone(), vec![prop.clone()]);
+                    items.push(Item { target: Target::Property(p.clone(), kind), change });
+                }
+            }
+            RegistryChange::ValueAdded(k, n, _) | RegistryChange::ValueDeleted(k, n, _) | RegistryChange::ValueModified(k, n, _, _) => {
+                items.push(Item { target: Target::Value(k.clone(), n.to_ascii_uppercase()), change: change.clone() })
+            }
+            // Not produced without `compact_subtrees`/`move_threshold`.
+            RegistryChange::SubtreeAdded(..) | RegistryChange::SubtreeDeleted(..) | RegistryChange::KeyMoved(..) | RegistryChange::KeyRenamed(..) => {}
+        }
+    }
+    items
+}
+
+/// Deleted keys whose parent was not deleted too.
+fn deleted_roots(items: &[Item]) -> Vec<&str> {
+    let deleted: Vec<&str> = items.iter().filter(|i| matches!(i.change, RegistryChange::KeyDeleted(_))).map(Item::path).collect();
+    deleted.iter().copied().filter(|p| !deleted.iter().any(|other| other != p && within(p, other))).collect()
+}
+
+fn within(path: &str, root: &str) -> bool {
+    path == root || path.strip_prefix(root).is_some_and(|rest| rest.starts_with('\\'))
+}
+
+fn value_name(change: &RegistryChange) -> Option<String> {
+    match change {
+        RegistryChange::ValueAdded(_, n, _) | RegistryChange::ValueDeleted(_, n, _) | RegistryChange::ValueModified(_, n, _, _) => Some(n.clone()),
+        _ => None,
+    }
+}
+
+fn old_value(change: &RegistryChange) -> Option<RegistryValue> {
+    match change {
+        RegistryChange::ValueDeleted(_, _, v) | RegistryChange::ValueModified(_, _, v, _) => Some(v.clone()),
+        _ => None,
+    }
+}
+
+/// Diff paths are upper-cased; give added keys the names they have in `side`.
+fn with_real_case(change: &RegistryChange, side: &KeyNode) -> RegistryChange {
+    match change {
+        RegistryChange::KeyAdded(path) => {
+            let mut node = side.clone();
+            let mut real = Vec::new();
+            for segment in path.split('\\') {
+                let Some(next) = RegistryKey::find_key(&node, segment) else {
+                    return change.clone();
+                };
+                real.push(next.borrow().name.clone());
+                node = next;
+            }
+            RegistryChange::KeyAdded(real.join("\\"))
+        }
+        other => other.clone(),
+    }
+}
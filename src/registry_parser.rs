@@ -1,10 +1,13 @@
 use std::fs;
+use std::iter::Peekable;
 use std::path::Path;
+use std::str::Chars;
 
 use crate::architecture::Architecture;
 use crate::registry_key::{KeyNode, RegistryKey};
+use crate::registry_tree::KeyTree;
 use crate::registry_utils::{timestamp_to_filetime};
-use crate::registry_value::{RegistryValue, RegistryValueData, REG_BINARY, REG_QWORD};
+use crate::registry_value::{RegistryValue, RegistryValueData, REG_BINARY, REG_EXPAND_SZ, REG_MULTI_SZ, REG_SZ};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -24,6 +27,29 @@ pub struct LoadResult {
     pub architecture: Architecture,
 }
 
+impl LoadResult {
+    /// Where this file's keys belong in a file relative to `base`: the rest of
+    /// `relative_base` below `base` (compared case-insensitively) as a `\`-separated path,
+    /// e.g. `Software\Wine` for a subtree exported from `user.reg`.
+    pub fn path_below(&self, base: &str) -> Option<String> {
+        let ours: Vec<&str> = self.relative_base.split("\\\\").filter(|s| !s.is_empty()).collect();
+        let theirs: Vec<&str> = base.split("\\\\").filter(|s| !s.is_empty()).collect();
+        if ours.len() < theirs.len() || !ours.iter().zip(&theirs).all(|(a, b)| a.eq_ignore_ascii_case(b)) {
+            return None;
+        }
+        Some(ours[theirs.len()..].join("\\"))
+    }
+
+    /// Import this file under `path` of `target`, the inverse of
+    /// `RegistryWriter::write_subtree_to`. Missing keys are created and existing ones merged
+    /// into; values from the file replace those of the same name.
+    pub fn graft_into(&self, target: &KeyNode, path: &str) -> KeyNode {
+        let node = RegistryKey::create_key_recursive(target, path);
+        KeyTree::from_node(&self.root_key).merge_into(&node);
+        node
+    }
+}
+
 pub struct RegistryParser;
 
 impl RegistryParser {
@@ -57,15 +83,15 @@ impl RegistryParser {
             if trimmed.is_empty() {
                 continue;
             }
-            if trimmed.starts_with(";; All keys relative to ") {
-                relative_base = trimmed[";; All keys relative to ".len()..].to_string();
+            if let Some(rest) = trimmed.strip_prefix(";; All keys relative to ") {
+                relative_base = rest.to_string();
                 continue;
             }
             if trimmed.starts_with(';') {
                 continue;
             }
-            if trimmed.starts_with("#arch=") {
-                if let Some(a) = Architecture::from_tag(&trimmed["#arch=".len()..]) {
+            if let Some(rest) = trimmed.strip_prefix("#arch=") {
+                if let Some(a) = Architecture::from_tag(rest) {
                     architecture = a;
                 }
                 continue;
@@ -73,7 +99,7 @@ impl RegistryParser {
             if trimmed.starts_with('[') {
                 let (path, timestamp) = parse_key_header(trimmed).map_err(|msg| ParseError::Line { line: line_idx, msg })?;
                 let key_path = unescape_key_path(&path);
-                let key_node = RegistryKey::create_key_recursive(&root, &key_path);
+                let key_node = RegistryKey::create_key_recursive_for_loading(&root, &key_path);
                 {
                     let mut guard = key_node.borrow_mut();
                     guard.modification_time = timestamp_to_filetime(timestamp);
@@ -81,17 +107,15 @@ impl RegistryParser {
                 current_key = Some(key_node);
                 continue;
             }
-            if trimmed.starts_with("#time=") {
-                if let Some(ref key) = current_key {
-                    if let Ok(val) = u64::from_str_radix(trimmed["#time=".len()..].trim(), 16) {
-                        key.borrow_mut().modification_time = val;
-                    }
+            if let Some(rest) = trimmed.strip_prefix("#time=") {
+                if let (Some(key), Ok(val)) = (&current_key, u64::from_str_radix(rest.trim(), 16)) {
+                    key.borrow_mut().modification_time = val;
                 }
                 continue;
             }
-            if trimmed.starts_with("#class=") {
+            if let Some(rest) = trimmed.strip_prefix("#class=") {
                 if let Some(ref key) = current_key {
-                    let cls = trimmed["#class=".len()..].trim();
+                    let cls = rest.trim();
                     let unquoted = cls.trim_matches('"').to_string();
                     key.borrow_mut().class_name = Some(unescape_string(&unquoted));
                 }
@@ -194,22 +218,21 @@ fn parse_value_line(first_line: &str, rest: &[&str]) -> Result<(RegistryValue, u
 }
 
 fn parse_value_data(data: &str, name: String) -> Result<RegistryValue, String> {
-    if data.starts_with("str(2):") {
-        let s = parse_quoted_string(&data["str(2):".len()..])?;
-        return Ok(RegistryValue::new(name, RegistryValueData::ExpandString(s)));
+    if let Some(rest) = data.strip_prefix("str(2):") {
+        let units = parse_quoted_units(rest)?;
+        return Ok(RegistryValue::new(name, string_data(REG_EXPAND_SZ, units)));
     }
-    if data.starts_with("str(7):") {
-        let s = parse_quoted_string(&data["str(7):".len()..])?;
-        let parts: Vec<String> = s.split('\u{0}').filter(|v| !v.is_empty()).map(|v| v.to_string()).collect();
-        return Ok(RegistryValue::new(name, RegistryValueData::MultiString(parts)));
+    if let Some(rest) = data.strip_prefix("str(7):") {
+        let units = parse_quoted_units(rest)?;
+        return Ok(RegistryValue::new(name, string_data(REG_MULTI_SZ, units)));
     }
-    if data.starts_with("dword:") {
-        let hex = data["dword:".len()..].trim();
+    if let Some(rest) = data.strip_prefix("dword:") {
+        let hex = rest.trim();
         let val = u32::from_str_radix(hex, 16).map_err(|e| e.to_string())?;
         return Ok(RegistryValue::new(name, RegistryValueData::Dword(val)));
     }
-    if data.starts_with("qword:") {
-        let hex = data["qword:".len()..].trim();
+    if let Some(rest) = data.strip_prefix("qword:") {
+        let hex = rest.trim();
         let val = u64::from_str_radix(hex, 16).map_err(|e| e.to_string())?;
         return Ok(RegistryValue::new(name, RegistryValueData::Qword(val)));
     }
@@ -218,31 +241,22 @@ fn parse_value_data(data: &str, name: String) -> Result<RegistryValue, String> {
         let type_hex = &data[4..end];
         let ty = u32::from_str_radix(type_hex, 16).map_err(|e| e.to_string())?;
         let bytes = parse_hex_bytes(&data[end + 2..])?;
-        if ty == REG_QWORD && bytes.len() == 8 {
-            let mut arr = [0u8; 8];
-            arr.copy_from_slice(&bytes[..8]);
-            let val = u64::from_le_bytes(arr);
-            return Ok(RegistryValue::new(name, RegistryValueData::Qword(val)));
-        }
-        return Ok(RegistryValue::new(name, RegistryValueData::Binary(bytes, ty)));
+        return Ok(RegistryValue::new(name, RegistryValueData::from_raw(ty, bytes)));
     }
-    if data.starts_with("hex:") {
-        let bytes = parse_hex_bytes(&data["hex:".len()..])?;
+    if let Some(rest) = data.strip_prefix("hex:") {
+        let bytes = parse_hex_bytes(rest)?;
         return Ok(RegistryValue::new(name, RegistryValueData::Binary(bytes, REG_BINARY)));
     }
-    if data.starts_with("hex(b):") {
-        let bytes = parse_hex_bytes(&data["hex(b):".len()..])?;
-        if bytes.len() == 8 {
-            let mut arr = [0u8; 8];
-            arr.copy_from_slice(&bytes[..8]);
-            let val = u64::from_le_bytes(arr);
-            return Ok(RegistryValue::new(name, RegistryValueData::Qword(val)));
-        }
-        return Ok(RegistryValue::new(name, RegistryValueData::Binary(bytes, REG_QWORD)));
-    }
     // default string
-    let s = parse_quoted_string(data)?;
-    Ok(RegistryValue::new(name, RegistryValueData::String(s)))
+    let units = parse_quoted_units(data)?;
+    Ok(RegistryValue::new(name, string_data(REG_SZ, units)))
+}
+
+/// Quoted strings are stored with the terminator Wine strips when writing them; anything that
+/// does not decode cleanly (e.g. lone surrogates written as `\x` escapes) stays byte-exact.
+fn string_data(ty: u32, mut units: Vec<u16>) -> RegistryValueData {
+    units.push(0);
+    RegistryValueData::from_raw(ty, units.iter().flat_map(|u| u.to_le_bytes()).collect())
 }
 
 fn parse_hex_bytes(s: &str) -> Result<Vec<u8>, String> {
@@ -258,40 +272,73 @@ fn parse_hex_bytes(s: &str) -> Result<Vec<u8>, String> {
     Ok(bytes)
 }
 
-fn parse_quoted_string(data: &str) -> Result<String, String> {
+fn parse_quoted_units(data: &str) -> Result<Vec<u16>, String> {
     let trimmed = data.trim();
-    if !trimmed.starts_with('"') || !trimmed.ends_with('"') {
+    if trimmed.len() < 2 || !trimmed.starts_with('"') || !trimmed.ends_with('"') {
         return Err("expected quoted string".into());
     }
-    Ok(unescape_string(&trimmed[1..trimmed.len() - 1]))
+    Ok(unescape_units(&trimmed[1..trimmed.len() - 1]))
 }
 
 fn unescape_string(s: &str) -> String {
-    let mut out = String::new();
-    let chars: Vec<char> = s.chars().collect();
-    let mut i = 0;
-    while i < chars.len() {
-        let c = chars[i];
-        if c == '\\' && i + 1 < chars.len() {
-            let next = chars[i + 1];
-            match next {
-                'n' => out.push('\n'),
-                'r' => out.push('\r'),
-                't' => out.push('\t'),
-                '0' => out.push('\u{0}'),
-                '\\' => out.push('\\'),
-                '"' => out.push('"'),
-                _ => out.push(next),
+    String::from_utf16_lossy(&unescape_units(s))
+}
+
+/// Undo Wine's `dump_strW` escaping: C escapes, up to three octal digits and `\x` with up to
+/// four hex digits. Works on UTF-16 units so lone surrogates survive.
+fn unescape_units(s: &str) -> Vec<u16> {
+    let mut out = Vec::new();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        let next = match (c, chars.peek().copied()) {
+            ('\\', Some(next)) => next,
+            _ => {
+                out.extend(c.encode_utf16(&mut [0; 2]).iter());
+                continue;
             }
-            i += 2;
-        } else {
-            out.push(c);
-            i += 1;
-        }
+        };
+        chars.next();
+        let unit = match next {
+            'n' => '\n' as u16,
+            'r' => '\r' as u16,
+            't' => '\t' as u16,
+            'a' => 0x07,
+            'b' => 0x08,
+            'e' => 0x1b,
+            'f' => 0x0c,
+            'v' => 0x0b,
+            'x' => match take_digits(&mut chars, 16, 4, 0) {
+                (_, 0) => 'x' as u16,
+                (unit, _) => unit as u16,
+            },
+            '0'..='7' => take_digits(&mut chars, 8, 2, next.to_digit(8).unwrap_or(0)).0 as u16,
+            _ => {
+                out.extend(next.encode_utf16(&mut [0; 2]).iter());
+                continue;
+            }
+        };
+        out.push(unit);
     }
     out
 }
 
+/// Read up to `max` digits in `radix`, continuing from `value`. Returns the value and the
+/// number of digits read.
+fn take_digits(chars: &mut Peekable<Chars<'_>>, radix: u32, max: usize, mut value: u32) -> (u32, usize) {
+    let mut count = 0;
+    while count < max {
+        match chars.peek().and_then(|c| c.to_digit(radix)) {
+            Some(digit) => {
+                value = value * radix + digit;
+                chars.next();
+                count += 1;
+            }
+            None => break,
+        }
+    }
+    (value, count)
+}
+
 fn unescape_key_path(s: &str) -> String {
     s.replace("\\\\", "\\")
 }
@@ -1,5 +1,9 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
 use crate::registry_comparator::{DiffResult, KeyPropertyChange, RegistryChange};
 use crate::registry_key::{KeyNode, RegistryKey};
+use crate::registry_tree::KeyTree;
 use crate::registry_value::RegistryValue;
 
 #[derive(Debug, Clone)]
@@ -24,12 +28,14 @@ impl Default for PatchOptions {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct PatchFailure {
     pub change: RegistryChange,
     pub reason: String,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct PatchResult {
     pub applied: Vec<RegistryChange>,
     pub failed: Vec<PatchFailure>,
@@ -87,22 +93,53 @@ impl RegistryPatcher {
 }
 
 fn order_changes(changes: &[RegistryChange]) -> Vec<RegistryChange> {
-    let mut additions: Vec<_> = changes.iter().filter(|c| matches!(c, RegistryChange::KeyAdded(_))).cloned().collect();
-    additions.sort_by_key(|c| match c { RegistryChange::KeyAdded(p) => p.matches('\\').count(), _ => 0 });
-    let key_mods: Vec<_> = changes.iter().filter(|c| matches!(c, RegistryChange::KeyModified(_, _))).cloned().collect();
+    // Moves go first: the changes found inside a moved subtree use its new path.
+    let moves: Vec<_> = changes.iter().filter(|c| matches!(c, RegistryChange::KeyMoved(..) | RegistryChange::KeyRenamed(..))).cloned().collect();
+    let mut additions: Vec<_> = changes.iter().filter(|c| matches!(c, RegistryChange::KeyAdded(_) | RegistryChange::SubtreeAdded(..))).cloned().collect();
+    additions.sort_by_key(depth);
+    // Last-write times go last, since every other change stamps the keys it touches.
+    let mut key_mods = Vec::new();
+    let mut times = Vec::new();
+    for change in changes {
+        if let RegistryChange::KeyModified(path, props) = change {
+            let (time, other): (Vec<_>, Vec<_>) = props.iter().cloned().partition(|p| matches!(p, KeyPropertyChange::TimeChange(..)));
+            if !other.is_empty() {
+                key_mods.push(RegistryChange::KeyModified(path.clone(), other));
+            }
+            if !time.is_empty() {
+                times.push(RegistryChange::KeyModified(path.clone(), time));
+            }
+        }
+    }
     let val_adds: Vec<_> = changes.iter().filter(|c| matches!(c, RegistryChange::ValueAdded(_, _, _))).cloned().collect();
     let val_mods: Vec<_> = changes.iter().filter(|c| matches!(c, RegistryChange::ValueModified(_, _, _, _))).cloned().collect();
     let val_dels: Vec<_> = changes.iter().filter(|c| matches!(c, RegistryChange::ValueDeleted(_, _, _))).cloned().collect();
-    let mut key_dels: Vec<_> = changes.iter().filter(|c| matches!(c, RegistryChange::KeyDeleted(_))).cloned().collect();
-    key_dels.sort_by(|a, b| depth(b).cmp(&depth(a)));
+    let mut key_dels: Vec<_> = changes.iter().filter(|c| matches!(c, RegistryChange::KeyDeleted(_) | RegistryChange::SubtreeDeleted(..))).cloned().collect();
+    key_dels.sort_by_key(|c| std::cmp::Reverse(depth(c)));
+    // The old time a deleted key's metadata carries (for `invert`) has nothing left to stamp.
+    let deleted: Vec<String> = key_dels
+        .iter()
+        .filter_map(|c| match c {
+            RegistryChange::KeyDeleted(p) | RegistryChange::SubtreeDeleted(p, _) => Some(p.to_ascii_uppercase()),
+            _ => None,
+        })
+        .collect();
+    times.retain(|c| match c {
+        RegistryChange::KeyModified(path, _) => {
+            let path = path.to_ascii_uppercase();
+            !deleted.iter().any(|d| path == *d || path.starts_with(&format!("{}\\", d)))
+        }
+        _ => true,
+    });
 
-    let mut ordered = Vec::new();
+    let mut ordered = moves;
     ordered.extend(additions);
     ordered.extend(key_mods);
     ordered.extend(val_adds);
     ordered.extend(val_mods);
     ordered.extend(val_dels);
     ordered.extend(key_dels);
+    ordered.extend(times);
     ordered
 }
 
@@ -113,7 +150,11 @@ fn depth(change: &RegistryChange) -> usize {
         | RegistryChange::KeyModified(p, _)
         | RegistryChange::ValueAdded(p, _, _)
         | RegistryChange::ValueDeleted(p, _, _)
-        | RegistryChange::ValueModified(p, _, _, _) => p.matches('\\').count(),
+        | RegistryChange::ValueModified(p, _, _, _)
+        | RegistryChange::SubtreeAdded(p, _)
+        | RegistryChange::SubtreeDeleted(p, _)
+        | RegistryChange::KeyMoved(_, p)
+        | RegistryChange::KeyRenamed(_, p) => p.matches('\\').count(),
     }
 }
 
@@ -125,6 +166,9 @@ fn apply_change(target: &KeyNode, change: &RegistryChange, options: &PatchOption
         RegistryChange::ValueAdded(key_path, value_name, value) => apply_value_added(target, key_path, value_name, value.clone(), options),
         RegistryChange::ValueDeleted(key_path, value_name, _value) => apply_value_deleted(target, key_path, value_name, options),
         RegistryChange::ValueModified(key_path, value_name, old_value, new_value) => apply_value_modified(target, key_path, value_name, old_value, new_value, options),
+        RegistryChange::SubtreeAdded(path, tree) => apply_subtree_added(target, path, tree, options),
+        RegistryChange::SubtreeDeleted(path, _) => apply_key_deleted(target, path),
+        RegistryChange::KeyMoved(from, to) | RegistryChange::KeyRenamed(from, to) => apply_key_moved(target, from, to, options),
     }
 }
 
@@ -134,10 +178,7 @@ fn apply_key_added(target: &KeyNode, path: &str, options: &PatchOptions) -> Resu
         Ok(true)
     } else {
         let parent_path = path.rsplit_once('\\').map(|(p, _)| p.to_string()).unwrap_or_else(|| "".into());
-        if parent_path.is_empty() {
-            RegistryKey::create_key_recursive(target, path);
-            Ok(true)
-        } else if RegistryKey::find_key(target, &parent_path).is_some() {
+        if parent_path.is_empty() || RegistryKey::find_key(target, &parent_path).is_some() {
             RegistryKey::create_key_recursive(target, path);
             Ok(true)
         } else {
@@ -146,6 +187,43 @@ fn apply_key_added(target: &KeyNode, path: &str, options: &PatchOptions) -> Resu
     }
 }
 
+fn apply_subtree_added(target: &KeyNode, path: &str, tree: &KeyTree, options: &PatchOptions) -> Result<bool, String> {
+    let (parent_path, key_name) = path.rsplit_once('\\').unwrap_or(("", path));
+    if !key_name.eq_ignore_ascii_case(&tree.name) {
+        return Err(format!("subtree '{}' does not match path '{}'", tree.name, path));
+    }
+    let parent = if options.create_missing_keys {
+        RegistryKey::create_key_recursive(target, parent_path)
+    } else {
+        match RegistryKey::find_key(target, parent_path) {
+            Some(parent) => parent,
+            None => return Ok(false),
+        }
+    };
+    tree.graft_into(&parent);
+    Ok(true)
+}
+
+fn apply_key_moved(target: &KeyNode, from: &str, to: &str, options: &PatchOptions) -> Result<bool, String> {
+    let Some(source) = RegistryKey::find_key(target, from) else {
+        return Ok(false);
+    };
+    if RegistryKey::find_key(target, to).is_some() {
+        return Err(format!("destination '{}' already exists", to));
+    }
+    let (parent_path, key_name) = to.rsplit_once('\\').unwrap_or(("", to));
+    let parent = match RegistryKey::find_key(target, parent_path) {
+        Some(parent) => parent,
+        None if options.create_missing_keys => RegistryKey::create_key_recursive(target, parent_path),
+        None => return Ok(false),
+    };
+    let mut tree = KeyTree::from_node(&source);
+    tree.name = key_name.to_string();
+    apply_key_deleted(target, from)?;
+    tree.graft_into(&parent);
+    Ok(true)
+}
+
 fn apply_key_deleted(target: &KeyNode, path: &str) -> Result<bool, String> {
     let (parent_path, key_name) = path.rsplit_once('\\').map(|(p, n)| (p.to_string(), n.to_string())).unwrap_or_else(|| ("".into(), path.to_string()));
     if let Some(parent) = if parent_path.is_empty() { Some(target.clone()) } else { RegistryKey::find_key(target, &parent_path) } {
@@ -164,6 +242,7 @@ fn apply_key_modified(target: &KeyNode, path: &str, props: &[KeyPropertyChange])
                 KeyPropertyChange::ClassNameChange(_, new) => guard.class_name = new.clone(),
                 KeyPropertyChange::SymlinkChange(_, new) => guard.is_symlink = *new,
                 KeyPropertyChange::VolatileChange(_, new) => guard.is_volatile = *new,
+                KeyPropertyChange::TimeChange(_, new) => guard.modification_time = *new,
             }
         }
     }
@@ -222,10 +301,9 @@ fn delete_empty_chain(root: &KeyNode, path: &str) {
             let is_empty = { node.borrow().values().is_empty() && node.borrow().subkeys().is_empty() };
             if is_empty {
                 let (parent_path, name) = current_path.rsplit_once('\\').map(|(p, n)| (p.to_string(), n.to_string())).unwrap_or_else(|| ("".into(), current_path.clone()));
-                if let Some(parent) = if parent_path.is_empty() { Some(root.clone()) } else { RegistryKey::find_key(root, &parent_path) } {
-                    if !RegistryKey::delete_subkey(&parent, &name, false) {
-                        break;
-                    }
+                let parent = if parent_path.is_empty() { Some(root.clone()) } else { RegistryKey::find_key(root, &parent_path) };
+                if parent.is_some_and(|parent| !RegistryKey::delete_subkey(&parent, &name, false)) {
+                    break;
                 }
                 current_path = parent_path;
             } else {
@@ -0,0 +1,229 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Partial resource types from `CM_PARTIAL_RESOURCE_DESCRIPTOR::Type`.
+pub const CM_RESOURCE_TYPE_PORT: u8 = 1;
+pub const CM_RESOURCE_TYPE_INTERRUPT: u8 = 2;
+pub const CM_RESOURCE_TYPE_MEMORY: u8 = 3;
+pub const CM_RESOURCE_TYPE_DMA: u8 = 4;
+pub const CM_RESOURCE_TYPE_DEVICE_SPECIFIC: u8 = 5;
+pub const CM_RESOURCE_TYPE_BUS_NUMBER: u8 = 6;
+
+/// Decoded `REG_RESOURCE_LIST` (`CM_RESOURCE_LIST`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ResourceList {
+    pub descriptors: Vec<FullResourceDescriptor>,
+}
+
+/// Decoded `REG_FULL_RESOURCE_DESCRIPTOR` (`CM_FULL_RESOURCE_DESCRIPTOR`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FullResourceDescriptor {
+    pub interface_type: i32,
+    pub bus_number: u32,
+    pub version: u16,
+    pub revision: u16,
+    pub partial_descriptors: Vec<PartialResourceDescriptor>,
+}
+
+/// One `CM_PARTIAL_RESOURCE_DESCRIPTOR`. `data` is the raw union; `device_specific_data` holds
+/// the trailing bytes of a `CmResourceTypeDeviceSpecific` entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PartialResourceDescriptor {
+    pub resource_type: u8,
+    pub share_disposition: u8,
+    pub flags: u16,
+    pub data: Vec<u8>,
+    pub device_specific_data: Vec<u8>,
+}
+
+impl PartialResourceDescriptor {
+    /// Start and length of port, memory and bus-number ranges.
+    pub fn range(&self) -> Option<(u64, u32)> {
+        match self.resource_type {
+            CM_RESOURCE_TYPE_PORT | CM_RESOURCE_TYPE_MEMORY => Some((read_u64(&self.data, 0)?, read_u32(&self.data, 8)?)),
+            CM_RESOURCE_TYPE_BUS_NUMBER => Some((read_u32(&self.data, 0)? as u64, read_u32(&self.data, 4)?)),
+            _ => None,
+        }
+    }
+}
+
+/// Decoded `REG_RESOURCE_REQUIREMENTS_LIST` (`IO_RESOURCE_REQUIREMENTS_LIST`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ResourceRequirementsList {
+    pub interface_type: i32,
+    pub bus_number: u32,
+    pub slot_number: u32,
+    pub alternative_lists: Vec<IoResourceList>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct IoResourceList {
+    pub version: u16,
+    pub revision: u16,
+    pub descriptors: Vec<IoResourceDescriptor>,
+}
+
+/// One `IO_RESOURCE_DESCRIPTOR`; `data` is the raw 24-byte union.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct IoResourceDescriptor {
+    pub option: u8,
+    pub resource_type: u8,
+    pub share_disposition: u8,
+    pub flags: u16,
+    pub data: Vec<u8>,
+}
+
+const IO_RESOURCE_DESCRIPTOR_SIZE: usize = 32;
+/// The partial descriptor union is 12 bytes for 32-bit writers and 16 bytes for 64-bit ones
+/// (the interrupt affinity is pointer sized), so both layouts are tried.
+const PARTIAL_UNION_SIZE_32: usize = 12;
+const PARTIAL_UNION_SIZE_64: usize = 16;
+
+impl ResourceList {
+    pub fn parse(bytes: &[u8]) -> Result<Self, String> {
+        Self::parse_with(bytes, PARTIAL_UNION_SIZE_32).or_else(|err| Self::parse_with(bytes, PARTIAL_UNION_SIZE_64).map_err(|_| err))
+    }
+
+    fn parse_with(bytes: &[u8], union_size: usize) -> Result<Self, String> {
+        let mut reader = Reader::new(bytes);
+        let count = reader.u32()?;
+        let mut descriptors = Vec::new();
+        for _ in 0..count {
+            descriptors.push(FullResourceDescriptor::read(&mut reader, union_size)?);
+        }
+        reader.finish()?;
+        Ok(Self { descriptors })
+    }
+}
+
+impl FullResourceDescriptor {
+    pub fn parse(bytes: &[u8]) -> Result<Self, String> {
+        Self::parse_with(bytes, PARTIAL_UNION_SIZE_32).or_else(|err| Self::parse_with(bytes, PARTIAL_UNION_SIZE_64).map_err(|_| err))
+    }
+
+    fn parse_with(bytes: &[u8], union_size: usize) -> Result<Self, String> {
+        let mut reader = Reader::new(bytes);
+        let descriptor = Self::read(&mut reader, union_size)?;
+        reader.finish()?;
+        Ok(descriptor)
+    }
+
+    fn read(reader: &mut Reader<'_>, union_size: usize) -> Result<Self, String> {
+        let interface_type = reader.u32()? as i32;
+        let bus_number = reader.u32()?;
+        let version = reader.u16()?;
+        let revision = reader.u16()?;
+        let count = reader.u32()?;
+        let mut partial_descriptors = Vec::new();
+        for _ in 0..count {
+            let resource_type = reader.u8()?;
+            let share_disposition = reader.u8()?;
+            let flags = reader.u16()?;
+            let data = reader.bytes(union_size)?.to_vec();
+            let device_specific_data = if resource_type == CM_RESOURCE_TYPE_DEVICE_SPECIFIC {
+                let size = read_u32(&data, 0).unwrap_or(0) as usize;
+                reader.bytes(size)?.to_vec()
+            } else {
+                Vec::new()
+            };
+            partial_descriptors.push(PartialResourceDescriptor {
+                resource_type,
+                share_disposition,
+                flags,
+                data,
+                device_specific_data,
+            });
+        }
+        Ok(Self { interface_type, bus_number, version, revision, partial_descriptors })
+    }
+}
+
+impl ResourceRequirementsList {
+    pub fn parse(bytes: &[u8]) -> Result<Self, String> {
+        let mut reader = Reader::new(bytes);
+        let list_size = reader.u32()? as usize;
+        if list_size != bytes.len() {
+            return Err(format!("list size {} does not match data length {}", list_size, bytes.len()));
+        }
+        let interface_type = reader.u32()? as i32;
+        let bus_number = reader.u32()?;
+        let slot_number = reader.u32()?;
+        reader.bytes(12)?;
+        let count = reader.u32()?;
+        let mut alternative_lists = Vec::new();
+        for _ in 0..count {
+            let version = reader.u16()?;
+            let revision = reader.u16()?;
+            let descriptor_count = reader.u32()?;
+            let mut descriptors = Vec::new();
+            for _ in 0..descriptor_count {
+                let raw = reader.bytes(IO_RESOURCE_DESCRIPTOR_SIZE)?;
+                descriptors.push(IoResourceDescriptor {
+                    option: raw[0],
+                    resource_type: raw[1],
+                    share_disposition: raw[2],
+                    flags: u16::from_le_bytes([raw[4], raw[5]]),
+                    data: raw[8..].to_vec(),
+                });
+            }
+            alternative_lists.push(IoResourceList { version, revision, descriptors });
+        }
+        reader.finish()?;
+        Ok(Self { interface_type, bus_number, slot_number, alternative_lists })
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let end = self.pos.checked_add(len).filter(|end| *end <= self.bytes.len());
+        let end = end.ok_or_else(|| format!("truncated resource data at offset {}", self.pos))?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        let b = self.bytes(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let b = self.bytes(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn finish(&self) -> Result<(), String> {
+        if self.pos == self.bytes.len() {
+            Ok(())
+        } else {
+            Err(format!("{} trailing bytes in resource data", self.bytes.len() - self.pos))
+        }
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(bytes.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(bytes.get(offset..offset + 8)?.try_into().ok()?))
+}
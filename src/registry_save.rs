@@ -0,0 +1,169 @@
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::registry_key::KeyNode;
+use crate::registry_lock::{wineserver_running, LockFile};
+use crate::registry_writer::{FormattedWriter, RegistryWriter, WriterOptions};
+
+/// Options for `RegistryWriter::save`.
+#[derive(Debug, Clone)]
+pub struct SaveOptions {
+    /// fsync the new file before renaming it into place and the directory afterwards.
+    pub fsync: bool,
+    /// Number of previous versions kept as `name.bak`, `name.bak.2`, ... (0 keeps none).
+    pub backups: usize,
+    /// Give the new file the permissions of the file it replaces.
+    pub preserve_permissions: bool,
+    /// Hold a `LockFile` (`name.lock`) while saving; fails if another process holds it.
+    pub lock: bool,
+    /// Look for a wineserver serving the prefix before writing.
+    pub wineserver: Option<WineServerCheck>,
+}
+
+impl Default for SaveOptions {
+    fn default() -> Self {
+        Self {
+            fsync: true,
+            backups: 0,
+            preserve_permissions: true,
+            lock: false,
+            wineserver: None,
+        }
+    }
+}
+
+/// A running wineserver keeps the registry in memory and overwrites the files when it
+/// flushes, so changes written underneath it are lost.
+#[derive(Debug, Clone)]
+pub struct WineServerCheck {
+    pub prefix: PathBuf,
+    /// Replaces `/tmp/.wine-<uid>` when locating the server socket.
+    pub socket_root: Option<PathBuf>,
+    /// Fail with `ErrorKind::ResourceBusy` instead of writing and reporting it.
+    pub refuse: bool,
+}
+
+/// What `RegistryWriter::save` found while saving.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SaveReport {
+    /// A wineserver was serving the prefix; the file was written anyway (`refuse` unset).
+    pub wineserver_running: bool,
+}
+
+impl RegistryWriter {
+    /// Write `root` to `path` durably: the data goes to `name.tmp`, which is synced and renamed
+    /// over `path` after the previous version has been rotated into the backups, so `path`
+    /// always holds either the old or the new complete file.
+    pub fn save<P: AsRef<Path>>(&self, root: &KeyNode, path: P, options: &SaveOptions) -> io::Result<SaveReport> {
+        self.clone().with_options(WriterOptions::default()).save(root, path, options)
+    }
+}
+
+impl FormattedWriter {
+    /// See `RegistryWriter::save`.
+    pub fn save<P: AsRef<Path>>(&self, root: &KeyNode, path: P, options: &SaveOptions) -> io::Result<SaveReport> {
+        let path = path.as_ref();
+        let _lock = if options.lock { Some(LockFile::acquire(path)?) } else { None };
+        let mut report = SaveReport::default();
+        if let Some(check) = &options.wineserver {
+            report.wineserver_running = wineserver_running(&check.prefix, check.socket_root.as_deref())?;
+            if report.wineserver_running && check.refuse {
+                return Err(io::Error::new(
+                    io::ErrorKind::ResourceBusy,
+                    format!("wineserver is running for '{}'", check.prefix.display()),
+                ));
+            }
+        }
+
+        let tmp = sibling(path, ".tmp");
+        let result = self.write_tmp(root, path, &tmp, options);
+        if result.is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+        result?;
+
+        if options.backups > 0 && path.exists() {
+            rotate_backups(path, options.backups)?;
+        }
+        fs::rename(&tmp, path)?;
+        if options.fsync {
+            sync_parent_dir(path)?;
+        }
+        Ok(report)
+    }
+
+    fn write_tmp(&self, root: &KeyNode, path: &Path, tmp: &Path, options: &SaveOptions) -> io::Result<()> {
+        let mut file = File::create(tmp)?;
+        self.write_to(root, &mut file)?;
+        if options.preserve_permissions
+            && let Ok(meta) = fs::metadata(path)
+        {
+            file.set_permissions(meta.permissions())?;
+        }
+        if options.fsync {
+            file.sync_all()?;
+        }
+        Ok(())
+    }
+}
+
+/// Path of backup `generation` (1 is the most recent) of `path`.
+pub fn backup_path<P: AsRef<Path>>(path: P, generation: usize) -> PathBuf {
+    match generation {
+        0 | 1 => sibling(path.as_ref(), ".bak"),
+        n => sibling(path.as_ref(), &format!(".bak.{}", n)),
+    }
+}
+
+/// Put backup `generation` back in place of `path`, durably. The current file is not kept;
+/// save it first if it may still be needed.
+pub fn restore_backup<P: AsRef<Path>>(path: P, generation: usize) -> io::Result<()> {
+    let path = path.as_ref();
+    let backup = backup_path(path, generation);
+    let tmp = sibling(path, ".tmp");
+    fs::copy(&backup, &tmp)?;
+    File::open(&tmp)?.sync_all()?;
+    fs::rename(&tmp, path)?;
+    sync_parent_dir(path)
+}
+
+/// Shift `name.bak` -> `name.bak.2` -> ... dropping the oldest, then keep the current file as
+/// `name.bak`. The current file is hard-linked (copied where that fails) so it never goes
+/// missing before the new version is renamed over it.
+fn rotate_backups(path: &Path, generations: usize) -> io::Result<()> {
+    let oldest = backup_path(path, generations);
+    if oldest.exists() {
+        fs::remove_file(&oldest)?;
+    }
+    for generation in (1..generations).rev() {
+        let from = backup_path(path, generation);
+        if from.exists() {
+            fs::rename(&from, backup_path(path, generation + 1))?;
+        }
+    }
+    let newest = backup_path(path, 1);
+    if fs::hard_link(path, &newest).is_err() {
+        fs::copy(path, &newest)?;
+    }
+    Ok(())
+}
+
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let file_name = path.file_name().map(|s| s.to_string_lossy().to_string()).unwrap_or_else(|| "registry.reg".into());
+    path.with_file_name(format!("{}{}", file_name, suffix))
+}
+
+#[cfg(unix)]
+fn sync_parent_dir(path: &Path) -> io::Result<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    File::open(dir)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_parent_dir(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
@@ -0,0 +1,1012 @@
+use std::fmt;
+
+use serde::de::{self, DeserializeOwned, IntoDeserializer, Visitor};
+use serde::ser::{self, Impossible, Serialize};
+use thiserror::Error;
+
+use crate::registry_key::{KeyNode, RegistryKey};
+use crate::registry_tree::KeyTree;
+use crate::registry_value::{RegistryValue, RegistryValueData, ValueError, REG_BINARY, REG_MULTI_SZ};
+
+#[derive(Debug, Error)]
+pub enum RegistrySerdeError {
+    #[error(transparent)]
+    Value(#[from] ValueError),
+    #[error("{0}")]
+    Message(String),
+    #[error("{0} cannot be stored in the registry")]
+    Unsupported(&'static str),
+}
+
+impl de::Error for RegistrySerdeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        RegistrySerdeError::Message(msg.to_string())
+    }
+}
+
+impl ser::Error for RegistrySerdeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        RegistrySerdeError::Message(msg.to_string())
+    }
+}
+
+type Result<T> = std::result::Result<T, RegistrySerdeError>;
+
+/// Deserialize `T` from the values and subkeys of `key`.
+///
+/// Struct fields are read from values of the same name (`#[serde(rename = "...")]` maps a field
+/// to its registry spelling), nested structs and maps from subkeys, and sequences from
+/// `REG_MULTI_SZ` values or numbered subkeys `0`, `1`, .... Scalars use the same coercions as
+/// the typed getters, e.g. integers from `REG_DWORD`, `REG_QWORD` or numeric strings.
+pub fn from_key<T: DeserializeOwned>(key: &KeyNode) -> Result<T> {
+    T::deserialize(KeyDeserializer { key: key.clone() })
+}
+
+/// Serialize `value` into `key`, creating values and subkeys as needed.
+///
+/// `bool` and integers up to 32 bits are stored as `REG_DWORD`, 64-bit integers as `REG_QWORD`,
+/// floats and strings as `REG_SZ`, byte buffers as `REG_BINARY`, and a `None` field removes the
+/// value. Values and subkeys not mentioned by `value` are left untouched.
+pub fn to_key<T: Serialize + ?Sized>(value: &T, key: &KeyNode) -> Result<()> {
+    value.serialize(KeySerializer { key: key.clone() })
+}
+
+struct KeyDeserializer {
+    key: KeyNode,
+}
+
+enum Entry {
+    Value(RegistryValue),
+    Key(KeyNode),
+}
+
+impl Entry {
+    fn into_deserialize<'de, T: de::DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> {
+        match self {
+            Entry::Value(value) => seed.deserialize(ValueDeserializer { value }),
+            Entry::Key(key) => seed.deserialize(KeyDeserializer { key }),
+        }
+    }
+}
+
+struct KeyMapAccess {
+    entries: std::vec::IntoIter<(String, Entry)>,
+    pending: Option<Entry>,
+}
+
+impl KeyMapAccess {
+    fn for_fields(key: &KeyNode, fields: &[&str]) -> Self {
+        let guard = key.borrow();
+        let mut entries = Vec::new();
+        for field in fields {
+            if let Some(value) = guard.get_value(field) {
+                entries.push((field.to_string(), Entry::Value(value.clone())));
+            } else if let Some(sub) = guard.get_subkey(field) {
+                entries.push((field.to_string(), Entry::Key(sub)));
+            }
+        }
+        Self { entries: entries.into_iter(), pending: None }
+    }
+
+    fn all(key: &KeyNode) -> Self {
+        let guard = key.borrow();
+        let mut entries: Vec<(String, Entry)> =
+            guard.values().values().map(|v| (v.name.clone(), Entry::Value(v.clone()))).collect();
+        for sub in guard.subkeys().values() {
+            let name = sub.borrow().name.clone();
+            entries.push((name, Entry::Key(sub.clone())));
+        }
+        Self { entries: entries.into_iter(), pending: None }
+    }
+}
+
+impl<'de> de::MapAccess<'de> for KeyMapAccess {
+    type Error = RegistrySerdeError;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        match self.entries.next() {
+            Some((name, entry)) => {
+                self.pending = Some(entry);
+                seed.deserialize(name.into_deserializer()).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        match self.pending.take() {
+            Some(entry) => entry.into_deserialize(seed),
+            None => Err(de::Error::custom("value requested before key")),
+        }
+    }
+}
+
+struct EntrySeqAccess {
+    entries: std::vec::IntoIter<Entry>,
+}
+
+impl<'de> de::SeqAccess<'de> for EntrySeqAccess {
+    type Error = RegistrySerdeError;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        match self.entries.next() {
+            Some(entry) => entry.into_deserialize(seed).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Subkeys of `key` ordered by their numeric name, non-numeric names last.
+fn numbered_subkeys(key: &KeyNode) -> Vec<KeyNode> {
+    let mut subkeys: Vec<(Option<u64>, String, KeyNode)> = RegistryKey::snapshot_subkeys(key)
+        .into_iter()
+        .map(|(name, node)| (name.parse::<u64>().ok(), name, node))
+        .collect();
+    subkeys.sort_by(|a, b| match (a.0, b.0) {
+        (Some(x), Some(y)) => x.cmp(&y),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => a.1.cmp(&b.1),
+    });
+    subkeys.into_iter().map(|(_, _, node)| node).collect()
+}
+
+impl<'de> de::Deserializer<'de> for KeyDeserializer {
+    type Error = RegistrySerdeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_map(KeyMapAccess::all(&self.key))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_map(KeyMapAccess::for_fields(&self.key, fields))
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let entries: Vec<Entry> = numbered_subkeys(&self.key).into_iter().map(Entry::Key).collect();
+        visitor.visit_seq(EntrySeqAccess { entries: entries.into_iter() })
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf unit
+        unit_struct tuple tuple_struct enum identifier
+    }
+}
+
+struct ValueDeserializer {
+    value: RegistryValue,
+}
+
+impl ValueDeserializer {
+    fn invalid(&self, expected: &'static str, reason: String) -> RegistrySerdeError {
+        ValueError::Invalid { name: self.value.name.clone(), expected, reason }.into()
+    }
+
+    fn wrong_type(&self, expected: &'static str) -> RegistrySerdeError {
+        ValueError::WrongType { name: self.value.name.clone(), expected, actual: self.value.reg_type() }.into()
+    }
+
+    /// Signed reading where a `REG_DWORD` is reinterpreted as `i32` for every signed width,
+    /// matching how Windows applications store negative numbers.
+    fn signed(&self, expected: &'static str) -> Result<i64> {
+        match &self.value.data {
+            RegistryValueData::Dword(v) | RegistryValueData::DwordBigEndian(v) => Ok(*v as i32 as i64),
+            RegistryValueData::Qword(v) => Ok(*v as i64),
+            RegistryValueData::String(s) | RegistryValueData::ExpandString(s) => {
+                parse_signed(s).ok_or_else(|| self.invalid(expected, format!("'{}' is not a number", s)))
+            }
+            RegistryValueData::RawString(..) => {
+                let s = self.value.lossy_string().ok_or_else(|| self.wrong_type(expected))?;
+                parse_signed(&s).ok_or_else(|| self.invalid(expected, format!("'{}' is not a number", s)))
+            }
+            RegistryValueData::Binary(bytes, _) if bytes.len() == 4 => Ok(self.value.as_u32()? as i32 as i64),
+            _ => Ok(self.value.as_u64()? as i64),
+        }
+    }
+
+    fn narrow<T: TryFrom<i64>>(&self, expected: &'static str) -> Result<T> {
+        let v = self.signed(expected)?;
+        T::try_from(v).map_err(|_| self.invalid(expected, format!("{} is out of range", v)))
+    }
+
+    fn narrow_unsigned<T: TryFrom<u32>>(&self, expected: &'static str) -> Result<T> {
+        let v = self.value.as_u32()?;
+        T::try_from(v).map_err(|_| self.invalid(expected, format!("{} is out of range", v)))
+    }
+
+    fn float(&self) -> Result<f64> {
+        if let Some(s) = self.value.lossy_string() {
+            return s.trim().parse::<f64>().map_err(|_| self.invalid("float", format!("'{}' is not a number", s)));
+        }
+        match &self.value.data {
+            RegistryValueData::Dword(v) | RegistryValueData::DwordBigEndian(v) => Ok(*v as f64),
+            RegistryValueData::Qword(v) => Ok(*v as f64),
+            _ => Err(self.wrong_type("float")),
+        }
+    }
+}
+
+fn parse_signed(s: &str) -> Option<i64> {
+    let trimmed = s.trim();
+    let (negative, digits) = match trimmed.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, trimmed),
+    };
+    let magnitude = match digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        Some(hex) => i64::from_str_radix(hex, 16).ok()?,
+        None => digits.parse::<i64>().ok()?,
+    };
+    Some(if negative { -magnitude } else { magnitude })
+}
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer {
+    type Error = RegistrySerdeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let bytes = self.value.raw_bytes();
+        match self.value.data {
+            RegistryValueData::String(s) | RegistryValueData::ExpandString(s) | RegistryValueData::Link(s) => {
+                visitor.visit_string(s)
+            }
+            RegistryValueData::MultiString(_) | RegistryValueData::RawString(_, REG_MULTI_SZ) => self.deserialize_seq(visitor),
+            RegistryValueData::RawString(..) => visitor.visit_string(self.value.as_string()?),
+            RegistryValueData::Dword(v) | RegistryValueData::DwordBigEndian(v) => visitor.visit_u32(v),
+            RegistryValueData::Qword(v) => visitor.visit_u64(v),
+            RegistryValueData::Binary(..)
+            | RegistryValueData::None(_)
+            | RegistryValueData::ResourceList(_)
+            | RegistryValueData::FullResourceDescriptor(_)
+            | RegistryValueData::ResourceRequirementsList(_) => visitor.visit_byte_buf(bytes),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_bool(self.value.as_bool()?)
+    }
+
+    fn deserialize_i8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_i8(self.narrow("i8")?)
+    }
+
+    fn deserialize_i16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_i16(self.narrow("i16")?)
+    }
+
+    fn deserialize_i32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_i32(self.narrow("i32")?)
+    }
+
+    fn deserialize_i64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_i64(self.signed("i64")?)
+    }
+
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_u8(self.narrow_unsigned("u8")?)
+    }
+
+    fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_u16(self.narrow_unsigned("u16")?)
+    }
+
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_u32(self.value.as_u32()?)
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_u64(self.value.as_u64()?)
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_f32(self.float()? as f32)
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_f64(self.float()?)
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let s = self.value.as_string()?;
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => visitor.visit_char(c),
+            _ => Err(self.invalid("char", format!("'{}' is not a single character", s))),
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_string(self.value.as_string()?)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_string(self.value.as_string()?)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_byte_buf(self.value.as_bytes())
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_byte_buf(self.value.as_bytes())
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let name = self.value.name.clone();
+        let items = match self.value.data {
+            RegistryValueData::Binary(..)
+            | RegistryValueData::None(_)
+            | RegistryValueData::ResourceList(_)
+            | RegistryValueData::FullResourceDescriptor(_)
+            | RegistryValueData::ResourceRequirementsList(_) => {
+                let bytes = self.value.raw_bytes();
+                return visitor.visit_seq(de::value::SeqDeserializer::new(bytes.into_iter()));
+            }
+            _ => self.value.as_multi()?,
+        };
+        let entries: Vec<Entry> = items
+            .into_iter()
+            .map(|item| Entry::Value(RegistryValue::new(name.clone(), RegistryValueData::String(item))))
+            .collect();
+        visitor.visit_seq(EntrySeqAccess { entries: entries.into_iter() })
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
+        Err(self.wrong_type("subkey"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value> {
+        Err(self.wrong_type("subkey"))
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        let variant: de::value::StringDeserializer<RegistrySerdeError> = self.value.as_string()?.into_deserializer();
+        visitor.visit_enum(variant)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+}
+
+/// Serializes a struct or map into the fields of a key.
+struct KeySerializer {
+    key: KeyNode,
+}
+
+macro_rules! unsupported_at_key {
+    ($($method:ident($($arg:ty),*) => $what:literal;)*) => {
+        $(fn $method(self, $(_: $arg),*) -> Result<()> {
+            Err(RegistrySerdeError::Unsupported($what))
+        })*
+    };
+}
+
+impl ser::Serializer for KeySerializer {
+    type Ok = ();
+    type Error = RegistrySerdeError;
+    type SerializeSeq = Impossible<(), RegistrySerdeError>;
+    type SerializeTuple = Impossible<(), RegistrySerdeError>;
+    type SerializeTupleStruct = Impossible<(), RegistrySerdeError>;
+    type SerializeTupleVariant = Impossible<(), RegistrySerdeError>;
+    type SerializeMap = KeyMapSerializer;
+    type SerializeStruct = KeyStructSerializer;
+    type SerializeStructVariant = Impossible<(), RegistrySerdeError>;
+
+    unsupported_at_key! {
+        serialize_bool(bool) => "a bare bool";
+        serialize_i8(i8) => "a bare integer";
+        serialize_i16(i16) => "a bare integer";
+        serialize_i32(i32) => "a bare integer";
+        serialize_i64(i64) => "a bare integer";
+        serialize_u8(u8) => "a bare integer";
+        serialize_u16(u16) => "a bare integer";
+        serialize_u32(u32) => "a bare integer";
+        serialize_u64(u64) => "a bare integer";
+        serialize_f32(f32) => "a bare float";
+        serialize_f64(f64) => "a bare float";
+        serialize_char(char) => "a bare char";
+        serialize_str(&str) => "a bare string";
+        serialize_bytes(&[u8]) => "bare bytes";
+        serialize_unit_variant(&'static str, u32, &'static str) => "a bare enum";
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<()> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        Ok(())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<()> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<()> {
+        Err(RegistrySerdeError::Unsupported("an enum with data"))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Err(RegistrySerdeError::Unsupported("a bare sequence"))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+        Err(RegistrySerdeError::Unsupported("a bare tuple"))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeTupleStruct> {
+        Err(RegistrySerdeError::Unsupported("a bare tuple struct"))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Err(RegistrySerdeError::Unsupported("an enum with data"))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Ok(KeyMapSerializer { key: self.key, next_name: None })
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        Ok(KeyStructSerializer { key: self.key })
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Err(RegistrySerdeError::Unsupported("an enum with data"))
+    }
+}
+
+struct KeyStructSerializer {
+    key: KeyNode,
+}
+
+impl ser::SerializeStruct for KeyStructSerializer {
+    type Ok = ();
+    type Error = RegistrySerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, name: &'static str, value: &T) -> Result<()> {
+        write_field(&self.key, name, value)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+struct KeyMapSerializer {
+    key: KeyNode,
+    next_name: Option<String>,
+}
+
+impl ser::SerializeMap for KeyMapSerializer {
+    type Ok = ();
+    type Error = RegistrySerdeError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        let name = match key.serialize(ScalarSerializer) {
+            Ok(Some(data)) => scalar_text(&data).ok_or(RegistrySerdeError::Unsupported("a non-text map key"))?,
+            Ok(None) | Err(ScalarError::NotScalar) => return Err(RegistrySerdeError::Unsupported("a non-text map key")),
+            Err(ScalarError::Other(e)) => return Err(e),
+        };
+        self.next_name = Some(name);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let name = self.next_name.take().ok_or_else(|| RegistrySerdeError::Message("map value without key".into()))?;
+        write_field(&self.key, &name, value)
+    }
+
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Store one named field: scalars as values, compound data as subkeys. Whichever form the
+/// field had before is replaced, so nothing stale is read back.
+fn write_field<T: Serialize + ?Sized>(key: &KeyNode, name: &str, value: &T) -> Result<()> {
+    match value.serialize(ScalarSerializer) {
+        Ok(Some(data)) => {
+            RegistryKey::delete_subkey(key, name, true);
+            key.borrow_mut().set_value(name.to_string(), RegistryValue::new(name.to_string(), data));
+            Ok(())
+        }
+        Ok(None) => {
+            RegistryKey::delete_subkey(key, name, true);
+            key.borrow_mut().delete_value(name);
+            Ok(())
+        }
+        Err(ScalarError::NotScalar) => value.serialize(FieldSerializer { key: key.clone(), name: name.to_string() }),
+        Err(ScalarError::Other(e)) => Err(e),
+    }
+}
+
+fn scalar_text(data: &RegistryValueData) -> Option<String> {
+    match data {
+        RegistryValueData::String(s) | RegistryValueData::ExpandString(s) => Some(s.clone()),
+        RegistryValueData::RawString(..) => RegistryValue::new("", data.clone()).lossy_string(),
+        RegistryValueData::Dword(v) | RegistryValueData::DwordBigEndian(v) => Some(v.to_string()),
+        RegistryValueData::Qword(v) => Some(v.to_string()),
+        _ => None,
+    }
+}
+
+/// Serializes the compound data of a named field: nested structs and maps become subkeys,
+/// sequences become multi-strings or numbered subkeys.
+struct FieldSerializer {
+    key: KeyNode,
+    name: String,
+}
+
+impl FieldSerializer {
+    /// An empty subkey for the field, replacing any value or subkey it was stored as before.
+    fn subkey(&self) -> KeyNode {
+        self.key.borrow_mut().delete_value(&self.name);
+        RegistryKey::delete_subkey(&self.key, &self.name, true);
+        RegistryKey::create_subkey(&self.key, self.name.clone())
+    }
+}
+
+impl ser::Serializer for FieldSerializer {
+    type Ok = ();
+    type Error = RegistrySerdeError;
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = Impossible<(), RegistrySerdeError>;
+    type SerializeMap = KeyMapSerializer;
+    type SerializeStruct = KeyStructSerializer;
+    type SerializeStructVariant = Impossible<(), RegistrySerdeError>;
+
+    unsupported_at_key! {
+        serialize_bool(bool) => "a bool";
+        serialize_i8(i8) => "an integer";
+        serialize_i16(i16) => "an integer";
+        serialize_i32(i32) => "an integer";
+        serialize_i64(i64) => "an integer";
+        serialize_u8(u8) => "an integer";
+        serialize_u16(u16) => "an integer";
+        serialize_u32(u32) => "an integer";
+        serialize_u64(u64) => "an integer";
+        serialize_f32(f32) => "a float";
+        serialize_f64(f64) => "a float";
+        serialize_char(char) => "a char";
+        serialize_str(&str) => "a string";
+        serialize_bytes(&[u8]) => "bytes";
+        serialize_unit_variant(&'static str, u32, &'static str) => "an enum";
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<()> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        Ok(())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<()> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<()> {
+        Err(RegistrySerdeError::Unsupported("an enum with data"))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Ok(SeqSerializer { key: self.key, name: self.name, items: Vec::new(), elements: Vec::new(), index: 0 })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<Self::SerializeTupleStruct> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Err(RegistrySerdeError::Unsupported("an enum with data"))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Ok(KeyMapSerializer { key: self.subkey(), next_name: None })
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        Ok(KeyStructSerializer { key: self.subkey() })
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Err(RegistrySerdeError::Unsupported("an enum with data"))
+    }
+}
+
+/// Collects a list before touching the tree: scalars as multi-string entries, structs as
+/// detached keys that are grafted under numbered subkeys once the whole list is known.
+struct SeqSerializer {
+    key: KeyNode,
+    name: String,
+    items: Vec<String>,
+    elements: Vec<KeyTree>,
+    index: usize,
+}
+
+impl SeqSerializer {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let index = self.index;
+        self.index += 1;
+        match value.serialize(ScalarSerializer) {
+            Ok(Some(data)) => {
+                let text = scalar_text(&data).ok_or(RegistrySerdeError::Unsupported("binary data in a list"))?;
+                self.items.push(text);
+            }
+            Ok(None) => {}
+            Err(ScalarError::NotScalar) => {
+                let element = RegistryKey::create_root();
+                if let Some(clock) = RegistryKey::clock(&self.key) {
+                    RegistryKey::set_clock(&element, clock);
+                }
+                element.borrow_mut().modification_time = RegistryKey::current_time(&self.key);
+                value.serialize(KeySerializer { key: element.clone() })?;
+                let mut tree = KeyTree::from_node(&element);
+                tree.name = index.to_string();
+                self.elements.push(tree);
+            }
+            Err(ScalarError::Other(e)) => return Err(e),
+        }
+        if !self.items.is_empty() && !self.elements.is_empty() {
+            return Err(RegistrySerdeError::Unsupported("a list mixing values and structs"));
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<()> {
+        RegistryKey::delete_subkey(&self.key, &self.name, true);
+        if self.elements.is_empty() {
+            self.key.borrow_mut().set_value(
+                self.name.clone(),
+                RegistryValue::new(self.name, RegistryValueData::MultiString(self.items)),
+            );
+        } else {
+            self.key.borrow_mut().delete_value(&self.name);
+            let list_key = RegistryKey::create_subkey(&self.key, self.name);
+            for element in &self.elements {
+                element.graft_into(&list_key);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ser::SerializeSeq for SeqSerializer {
+    type Ok = ();
+    type Error = RegistrySerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for SeqSerializer {
+    type Ok = ();
+    type Error = RegistrySerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer {
+    type Ok = ();
+    type Error = RegistrySerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+/// Internal error of [`ScalarSerializer`]; `NotScalar` means the caller should store the data
+/// as a subkey instead.
+#[derive(Debug)]
+enum ScalarError {
+    NotScalar,
+    Other(RegistrySerdeError),
+}
+
+impl fmt::Display for ScalarError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScalarError::NotScalar => f.write_str("not a scalar"),
+            ScalarError::Other(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for ScalarError {}
+
+impl ser::Error for ScalarError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        ScalarError::Other(RegistrySerdeError::Message(msg.to_string()))
+    }
+}
+
+/// Converts scalar data to a value payload; `Ok(None)` stands for an absent value.
+struct ScalarSerializer;
+
+type ScalarResult = std::result::Result<Option<RegistryValueData>, ScalarError>;
+
+impl ser::Serializer for ScalarSerializer {
+    type Ok = Option<RegistryValueData>;
+    type Error = ScalarError;
+    type SerializeSeq = Impossible<Self::Ok, ScalarError>;
+    type SerializeTuple = Impossible<Self::Ok, ScalarError>;
+    type SerializeTupleStruct = Impossible<Self::Ok, ScalarError>;
+    type SerializeTupleVariant = Impossible<Self::Ok, ScalarError>;
+    type SerializeMap = Impossible<Self::Ok, ScalarError>;
+    type SerializeStruct = Impossible<Self::Ok, ScalarError>;
+    type SerializeStructVariant = Impossible<Self::Ok, ScalarError>;
+
+    fn serialize_bool(self, v: bool) -> ScalarResult {
+        Ok(Some(RegistryValueData::Dword(v as u32)))
+    }
+
+    fn serialize_i8(self, v: i8) -> ScalarResult {
+        self.serialize_i32(v as i32)
+    }
+
+    fn serialize_i16(self, v: i16) -> ScalarResult {
+        self.serialize_i32(v as i32)
+    }
+
+    fn serialize_i32(self, v: i32) -> ScalarResult {
+        Ok(Some(RegistryValueData::Dword(v as u32)))
+    }
+
+    fn serialize_i64(self, v: i64) -> ScalarResult {
+        Ok(Some(RegistryValueData::Qword(v as u64)))
+    }
+
+    fn serialize_u8(self, v: u8) -> ScalarResult {
+        self.serialize_u32(v as u32)
+    }
+
+    fn serialize_u16(self, v: u16) -> ScalarResult {
+        self.serialize_u32(v as u32)
+    }
+
+    fn serialize_u32(self, v: u32) -> ScalarResult {
+        Ok(Some(RegistryValueData::Dword(v)))
+    }
+
+    fn serialize_u64(self, v: u64) -> ScalarResult {
+        Ok(Some(RegistryValueData::Qword(v)))
+    }
+
+    fn serialize_f32(self, v: f32) -> ScalarResult {
+        Ok(Some(RegistryValueData::String(v.to_string())))
+    }
+
+    fn serialize_f64(self, v: f64) -> ScalarResult {
+        Ok(Some(RegistryValueData::String(v.to_string())))
+    }
+
+    fn serialize_char(self, v: char) -> ScalarResult {
+        Ok(Some(RegistryValueData::String(v.to_string())))
+    }
+
+    fn serialize_str(self, v: &str) -> ScalarResult {
+        Ok(Some(RegistryValueData::String(v.to_string())))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> ScalarResult {
+        Ok(Some(RegistryValueData::Binary(v.to_vec(), REG_BINARY)))
+    }
+
+    fn serialize_none(self) -> ScalarResult {
+        Ok(None)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> ScalarResult {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> ScalarResult {
+        Ok(None)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> ScalarResult {
+        Ok(None)
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, variant: &'static str) -> ScalarResult {
+        Ok(Some(RegistryValueData::String(variant.to_string())))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> ScalarResult {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> ScalarResult {
+        Err(ScalarError::Other(RegistrySerdeError::Unsupported("an enum with data")))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> std::result::Result<Self::SerializeSeq, ScalarError> {
+        Err(ScalarError::NotScalar)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> std::result::Result<Self::SerializeTuple, ScalarError> {
+        Err(ScalarError::NotScalar)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> std::result::Result<Self::SerializeTupleStruct, ScalarError> {
+        Err(ScalarError::NotScalar)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> std::result::Result<Self::SerializeTupleVariant, ScalarError> {
+        Err(ScalarError::Other(RegistrySerdeError::Unsupported("an enum with data")))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> std::result::Result<Self::SerializeMap, ScalarError> {
+        Err(ScalarError::NotScalar)
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> std::result::Result<Self::SerializeStruct, ScalarError> {
+        Err(ScalarError::NotScalar)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> std::result::Result<Self::SerializeStructVariant, ScalarError> {
+        Err(ScalarError::Other(RegistrySerdeError::Unsupported("an enum with data")))
+    }
+}
@@ -1,14 +1,52 @@
+use crate::registry_clock::source_date_epoch;
 use crate::registry_comparator::{DiffResult, KeyPropertyChange, RegistryChange};
-use crate::registry_value::{RegistryValue, RegistryValueData, REG_BINARY};
+use crate::registry_tree::KeyTree;
+use crate::registry_value::{RegistryValue, RegistryValueData, REG_BINARY, REG_QWORD};
+use crate::registry_value_delta::{ByteRange, ListEdit, ValueDelta};
 
 pub struct TextDiffExporter;
 
+/// Options for `TextDiffExporter::export_with_options`, built from `default()` so new
+/// options can be added without breaking callers.
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct TextDiffOptions {
+    /// Unix time written (in UTC) as `# Generated:`. `None` uses `SOURCE_DATE_EPOCH` when it
+    /// is set and the local wall-clock time otherwise.
+    pub timestamp: Option<u64>,
+    /// Write modified multi-strings and binary values as their old data followed by the
+    /// items or byte ranges that changed (`->edit` and `*` lines) instead of old and new data.
+    pub value_deltas: bool,
+}
+
+impl TextDiffOptions {
+    pub fn timestamp(mut self, timestamp: u64) -> Self {
+        self.timestamp = Some(timestamp);
+        self
+    }
+
+    pub fn value_deltas(mut self, value_deltas: bool) -> Self {
+        self.value_deltas = value_deltas;
+        self
+    }
+}
+
 impl TextDiffExporter {
     pub fn export(&self, diff: &DiffResult, from_file: Option<&str>, to_file: Option<&str>) -> String {
+        self.export_with_options(diff, from_file, to_file, &TextDiffOptions::default())
+    }
+
+    pub fn export_with_options(
+        &self,
+        diff: &DiffResult,
+        from_file: Option<&str>,
+        to_file: Option<&str>,
+        options: &TextDiffOptions,
+    ) -> String {
         let mut out = String::new();
         out.push_str("# Registry Patch File\n");
         out.push_str("# Generated: ");
-        out.push_str(&chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string());
+        out.push_str(&generated_time(options.timestamp));
         out.push('\n');
         if let (Some(f1), Some(f2)) = (from_file, to_file) {
             out.push_str("# FROM: ");
@@ -29,6 +67,9 @@ impl TextDiffExporter {
             let key = match change {
                 RegistryChange::KeyAdded(p) => parent_path(p),
                 RegistryChange::KeyDeleted(p) => parent_path(p),
+                RegistryChange::SubtreeAdded(p, _) => parent_path(p),
+                RegistryChange::SubtreeDeleted(p, _) => parent_path(p),
+                RegistryChange::KeyMoved(_, to) | RegistryChange::KeyRenamed(_, to) => parent_path(to),
                 RegistryChange::KeyModified(p, _) => p.clone(),
                 RegistryChange::ValueAdded(k, _, _) => k.clone(),
                 RegistryChange::ValueDeleted(k, _, _) => k.clone(),
@@ -75,6 +116,9 @@ impl TextDiffExporter {
                                 KeyPropertyChange::VolatileChange(old, newv) => {
                                     out.push_str(&format!("~isVolatile:{}->{}\n", old, newv));
                                 }
+                                KeyPropertyChange::TimeChange(old, newv) => {
+                                    out.push_str(&format!("~time:{:x}->{:x}\n", old, newv));
+                                }
                             }
                         }
                     }
@@ -88,6 +132,30 @@ impl TextDiffExporter {
                         out.push_str(&format_value(&name, &value));
                         out.push('\n');
                     }
+                    RegistryChange::SubtreeAdded(_, tree) => {
+                        out.push_str("+tree:");
+                        out.push_str(&tree.name);
+                        out.push('\n');
+                        format_tree(&tree, "", &mut out);
+                    }
+                    RegistryChange::SubtreeDeleted(_, tree) => {
+                        out.push_str("-tree:");
+                        out.push_str(&tree.name);
+                        out.push('\n');
+                        format_tree(&tree, "", &mut out);
+                    }
+                    RegistryChange::KeyRenamed(from, to) => {
+                        out.push_str(&format!("~key:\"{}\"->\"{}\"\n", escape_string(&leaf_name(&from)), escape_string(&leaf_name(&to))));
+                    }
+                    RegistryChange::KeyMoved(from, to) => {
+                        out.push_str(&format!("~move:\"{}\"->\"{}\"\n", escape_string(&from), escape_string(&leaf_name(&to))));
+                    }
+                    RegistryChange::ValueModified(_, name, old, newv)
+                        if options.value_deltas && let Some(delta) = ValueDelta::between(&old, &newv).filter(|d| !d.is_empty()) =>
+                    {
+                        out.push_str(&format!("~\"{}\"={}->edit\n", escape_string(&name), format_value_data(&old)));
+                        format_delta(&delta, &mut out);
+                    }
                     RegistryChange::ValueModified(_, name, old, newv) => {
                         out.push('~');
                         out.push('"');
@@ -114,9 +182,27 @@ impl TextDiffParser {
         let mut path = String::new();
         let mut changes = Vec::new();
         let mut key_props: std::collections::BTreeMap<String, Vec<KeyPropertyChange>> = std::collections::BTreeMap::new();
+        let mut block: Option<TreeBlock> = None;
+        let mut edit: Option<EditBlock> = None;
 
         for (idx, line) in text.lines().enumerate() {
             let trimmed = line.trim();
+            if let Some(rest) = trimmed.strip_prefix('>') {
+                let tree = block.as_mut().ok_or_else(|| format!("line {}: subtree line outside +tree/-tree", idx + 1))?;
+                tree.line(rest).map_err(|e| format!("line {}: {}", idx + 1, e))?;
+                continue;
+            }
+            if let Some(rest) = trimmed.strip_prefix('*') {
+                let delta = edit.as_mut().ok_or_else(|| format!("line {}: edit line outside ->edit", idx + 1))?;
+                delta.line(rest).map_err(|e| format!("line {}: {}", idx + 1, e))?;
+                continue;
+            }
+            if let Some(tree) = block.take() {
+                changes.push(tree.finish());
+            }
+            if let Some(delta) = edit.take() {
+                changes.push(delta.finish()?);
+            }
             if trimmed.is_empty() || trimmed.starts_with('#') {
                 continue;
             }
@@ -127,38 +213,62 @@ impl TextDiffParser {
                 }
                 continue;
             }
-            if trimmed.starts_with("+key:") {
-                let name = trimmed["+key:".len()..].to_string();
+            if let Some(rest) = trimmed.strip_prefix("+key:") {
+                let name = rest.to_string();
                 let full = join_path(&path, &name);
                 changes.push(RegistryChange::KeyAdded(full));
                 continue;
             }
-            if trimmed.starts_with("-key:") {
-                let name = trimmed["-key:".len()..].to_string();
+            if let Some(rest) = trimmed.strip_prefix("-key:") {
+                let name = rest.to_string();
                 let full = join_path(&path, &name);
                 changes.push(RegistryChange::KeyDeleted(full));
                 continue;
             }
-            if trimmed.starts_with("~className:") {
-                let rest = &trimmed["~className:".len()..];
+            if let Some(rest) = trimmed.strip_prefix("+tree:") {
+                block = Some(TreeBlock::new(true, join_path(&path, rest)));
+                continue;
+            }
+            if let Some(rest) = trimmed.strip_prefix("-tree:") {
+                block = Some(TreeBlock::new(false, join_path(&path, rest)));
+                continue;
+            }
+            if let Some(rest) = trimmed.strip_prefix("~key:") {
+                let (old, newv) = split_quoted_names(rest).map_err(|e| format!("line {}: {}", idx + 1, e))?;
+                changes.push(RegistryChange::KeyRenamed(join_path(&path, &old), join_path(&path, &newv)));
+                continue;
+            }
+            if let Some(rest) = trimmed.strip_prefix("~move:") {
+                let (from, newv) = split_quoted_names(rest).map_err(|e| format!("line {}: {}", idx + 1, e))?;
+                changes.push(RegistryChange::KeyMoved(from, join_path(&path, &newv)));
+                continue;
+            }
+            if let Some(rest) = trimmed.strip_prefix("~className:") {
                 let (old, newv) = split_arrow(rest)?;
                 key_props.entry(path.clone()).or_default().push(KeyPropertyChange::ClassNameChange(parse_property_value(old), parse_property_value(newv)));
                 continue;
             }
-            if trimmed.starts_with("~isSymlink:") {
-                let (old, newv) = split_arrow(&trimmed["~isSymlink:".len()..])?;
+            if let Some(rest) = trimmed.strip_prefix("~isSymlink:") {
+                let (old, newv) = split_arrow(rest)?;
                 let old_b = old.trim().parse::<bool>().map_err(|_| format!("line {}", idx + 1))?;
                 let new_b = newv.trim().parse::<bool>().map_err(|_| format!("line {}", idx + 1))?;
                 key_props.entry(path.clone()).or_default().push(KeyPropertyChange::SymlinkChange(old_b, new_b));
                 continue;
             }
-            if trimmed.starts_with("~isVolatile:") {
-                let (old, newv) = split_arrow(&trimmed["~isVolatile:".len()..])?;
+            if let Some(rest) = trimmed.strip_prefix("~isVolatile:") {
+                let (old, newv) = split_arrow(rest)?;
                 let old_b = old.trim().parse::<bool>().map_err(|_| format!("line {}", idx + 1))?;
                 let new_b = newv.trim().parse::<bool>().map_err(|_| format!("line {}", idx + 1))?;
                 key_props.entry(path.clone()).or_default().push(KeyPropertyChange::VolatileChange(old_b, new_b));
                 continue;
             }
+            if let Some(rest) = trimmed.strip_prefix("~time:") {
+                let (old, newv) = split_arrow(rest)?;
+                let old_t = u64::from_str_radix(old.trim(), 16).map_err(|_| format!("line {}", idx + 1))?;
+                let new_t = u64::from_str_radix(newv.trim(), 16).map_err(|_| format!("line {}", idx + 1))?;
+                key_props.entry(path.clone()).or_default().push(KeyPropertyChange::TimeChange(old_t, new_t));
+                continue;
+            }
             if trimmed.starts_with("+\"") || trimmed.starts_with("-\"") {
                 let add = trimmed.starts_with('+');
                 let val_part = &trimmed[1..];
@@ -171,6 +281,11 @@ impl TextDiffParser {
                 changes.push(full_change);
                 continue;
             }
+            if let Some(val_part) = trimmed.strip_prefix('~').and_then(|t| t.strip_suffix("->edit")).filter(|t| t.starts_with('"')) {
+                let (name, old) = parse_value(val_part)?;
+                edit = Some(EditBlock { path: path.clone(), name, old, edits: Vec::new(), ranges: Vec::new() });
+                continue;
+            }
             if trimmed.starts_with("~\"") {
                 let val_part = &trimmed[1..];
                 let (name, old_value, new_value) = parse_value_modification(val_part)?;
@@ -179,6 +294,12 @@ impl TextDiffParser {
             }
         }
 
+        if let Some(tree) = block.take() {
+            changes.push(tree.finish());
+        }
+        if let Some(delta) = edit.take() {
+            changes.push(delta.finish()?);
+        }
         for (path, props) in key_props {
             changes.push(RegistryChange::KeyModified(path, props));
         }
@@ -187,6 +308,171 @@ impl TextDiffParser {
     }
 }
 
+/// A `+tree:`/`-tree:` block being read: the subtree and the key its `>` lines describe.
+struct TreeBlock {
+    added: bool,
+    path: String,
+    tree: KeyTree,
+    current: Vec<String>,
+}
+
+impl TreeBlock {
+    fn new(added: bool, path: String) -> Self {
+        let tree = KeyTree { name: leaf_name(&path), ..KeyTree::default() };
+        Self { added, path, tree, current: Vec::new() }
+    }
+
+    fn line(&mut self, line: &str) -> Result<(), String> {
+        if let Some(rest) = line.strip_prefix('[') {
+            let rel = rest.strip_suffix(']').ok_or("malformed subtree key")?;
+            self.current = rel.split('\\').filter(|s| !s.is_empty()).map(|s| s.to_string()).collect();
+            self.key();
+            return Ok(());
+        }
+        let key = self.key();
+        if let Some(rest) = line.strip_prefix("#time=") {
+            key.modification_time = u64::from_str_radix(rest.trim(), 16).map_err(|e| e.to_string())?;
+        } else if let Some(rest) = line.strip_prefix("#class=") {
+            key.class_name = parse_property_value(rest);
+        } else if line == "#link" {
+            key.is_symlink = true;
+        } else if line == "#volatile" {
+            key.is_volatile = true;
+        } else if line.starts_with('"') {
+            let (name, mut value) = parse_value(line)?;
+            value.name = name;
+            key.values.push(value);
+        } else {
+            return Err(format!("unknown subtree line: {}", line));
+        }
+        Ok(())
+    }
+
+    /// The key named by the last `>[...]` line, created on first use.
+    fn key(&mut self) -> &mut KeyTree {
+        let mut node = &mut self.tree;
+        for name in &self.current {
+            let idx = match node.subkeys.iter().position(|k| k.name.eq_ignore_ascii_case(name)) {
+                Some(idx) => idx,
+                None => {
+                    node.subkeys.push(KeyTree { name: name.clone(), ..KeyTree::default() });
+                    node.subkeys.len() - 1
+                }
+            };
+            node = &mut node.subkeys[idx];
+        }
+        node
+    }
+
+    fn finish(self) -> RegistryChange {
+        match self.added {
+            true => RegistryChange::SubtreeAdded(self.path, self.tree),
+            false => RegistryChange::SubtreeDeleted(self.path, self.tree),
+        }
+    }
+}
+
+/// A `->edit` value modification being read: the old value and its `*` lines.
+struct EditBlock {
+    path: String,
+    name: String,
+    old: RegistryValue,
+    edits: Vec<ListEdit>,
+    ranges: Vec<ByteRange>,
+}
+
+impl EditBlock {
+    /// One `*` line; item edits only fit multi-strings and byte ranges only other data.
+    fn line(&mut self, line: &str) -> Result<(), String> {
+        let multi = matches!(self.old.data, RegistryValueData::MultiString(_));
+        if let Some(rest) = line.strip_prefix('@') {
+            if multi {
+                return Err(format!("byte range under multi-string \"{}\"", self.name));
+            }
+            let (offset, bytes) = rest.split_once(':').ok_or("malformed byte range")?;
+            let (old, new) = split_arrow(bytes)?;
+            let offset = offset.parse().map_err(|_| "bad offset")?;
+            self.ranges.push(ByteRange { offset, old: parse_hex_bytes(old)?, new: parse_hex_bytes(new)? });
+            return Ok(());
+        }
+        if !multi {
+            return Err(format!("item edit under non-multi-string \"{}\"", self.name));
+        }
+        let removed = line.starts_with('-');
+        let (index, item) = line.get(1..).and_then(|l| l.split_once(':')).ok_or("malformed item edit")?;
+        let index = index.parse().map_err(|_| "bad index")?;
+        let item = unescape(item.trim().strip_prefix('"').and_then(|i| i.strip_suffix('"')).ok_or("unquoted item")?);
+        self.edits.push(match removed {
+            true => ListEdit::Removed { index, item },
+            false => ListEdit::Inserted { index, item },
+        });
+        Ok(())
+    }
+
+    fn finish(self) -> Result<RegistryChange, String> {
+        let delta = match &self.old.data {
+            RegistryValueData::MultiString(_) => ValueDelta::MultiString(self.edits),
+            _ => ValueDelta::Binary(self.ranges),
+        };
+        let new = delta.apply(&self.old).ok_or_else(|| format!("edits of \"{}\" do not fit its old data", self.name))?;
+        Ok(RegistryChange::ValueModified(self.path, self.name, self.old, new))
+    }
+}
+
+/// The `*` lines of a `->edit` modification: `*-index:"item"` / `*+index:"item"` for
+/// multi-strings, `*@offset:old bytes->new bytes` for binary data.
+fn format_delta(delta: &ValueDelta, out: &mut String) {
+    let hex = |bytes: &[u8]| bytes.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(",");
+    match delta {
+        ValueDelta::MultiString(edits) => {
+            for edit in edits {
+                match edit {
+                    ListEdit::Removed { index, item } => out.push_str(&format!("*-{}:\"{}\"\n", index, escape_string(item))),
+                    ListEdit::Inserted { index, item } => out.push_str(&format!("*+{}:\"{}\"\n", index, escape_string(item))),
+                }
+            }
+        }
+        ValueDelta::Binary(ranges) => {
+            for range in ranges {
+                out.push_str(&format!("*@{}:{}->{}\n", range.offset, hex(&range.old), hex(&range.new)));
+            }
+        }
+    }
+}
+
+/// The `>` lines of a `+tree:`/`-tree:` block: every key in pre-order as `>[path below the
+/// subtree]` followed by its metadata and values.
+fn format_tree(tree: &KeyTree, rel: &str, out: &mut String) {
+    out.push_str(&format!(">[{}]\n>#time={:x}\n", rel, tree.modification_time));
+    if tree.class_name.is_some() {
+        out.push_str(&format!(">#class={}\n", format_property(&tree.class_name)));
+    }
+    if tree.is_symlink {
+        out.push_str(">#link\n");
+    }
+    if tree.is_volatile {
+        out.push_str(">#volatile\n");
+    }
+    for value in &tree.values {
+        out.push('>');
+        out.push_str(&format_value(&value.name, value));
+        out.push('\n');
+    }
+    for sub in &tree.subkeys {
+        format_tree(sub, &join_path(rel, &sub.name), out);
+    }
+}
+
+fn generated_time(timestamp: Option<u64>) -> String {
+    const FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+    match timestamp.or_else(source_date_epoch) {
+        Some(secs) => chrono::DateTime::from_timestamp(secs as i64, 0)
+            .map(|time| time.format(FORMAT).to_string())
+            .unwrap_or_default(),
+        None => chrono::Local::now().format(FORMAT).to_string(),
+    }
+}
+
 fn parent_path(path: &str) -> String {
     path.rsplit_once('\\').map(|(p, _)| p.to_string()).unwrap_or_else(|| "".into())
 }
@@ -234,6 +520,16 @@ fn format_value_data(value: &RegistryValue) -> String {
             let body = bytes.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(",");
             format!("{}{}", prefix, body)
         }
+        RegistryValueData::Link(v) => format!("link:\"{}\"", escape_string(v)),
+        RegistryValueData::DwordBigEndian(v) => format!("dword_be:{:08x}", v),
+        RegistryValueData::None(_)
+        | RegistryValueData::ResourceList(_)
+        | RegistryValueData::FullResourceDescriptor(_)
+        | RegistryValueData::ResourceRequirementsList(_)
+        | RegistryValueData::RawString(..) => {
+            let body = value.raw_bytes().iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(",");
+            format!("hex({:x}):{}", value.reg_type(), body)
+        }
     }
 }
 
@@ -250,6 +546,31 @@ fn split_arrow(s: &str) -> Result<(&str, &str), String> {
     Ok((&s[..pos], &s[pos + 2..]))
 }
 
+/// `"<old>"->"<new>"`, with the escapes `escape_string` writes, so names may contain `->`.
+fn split_quoted_names(s: &str) -> Result<(String, String), String> {
+    let (old, rest) = take_quoted(s)?;
+    let rest = rest.strip_prefix("->").ok_or("missing ->")?;
+    let (new, rest) = take_quoted(rest)?;
+    if !rest.trim().is_empty() {
+        return Err("unexpected text after name".into());
+    }
+    Ok((old, new))
+}
+
+fn take_quoted(s: &str) -> Result<(String, &str), String> {
+    let body = s.strip_prefix('"').ok_or("unquoted name")?;
+    let mut escaped = false;
+    for (i, c) in body.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => return Ok((unescape(&body[..i]), &body[i + 1..])),
+            _ => {}
+        }
+    }
+    Err("unterminated name".into())
+}
+
 fn parse_property_value(text: &str) -> Option<String> {
     let trimmed = text.trim();
     if trimmed == "null" {
@@ -281,16 +602,16 @@ fn parse_value_modification(line: &str) -> Result<(String, RegistryValue, Regist
 
 fn parse_value_data_part(data: &str) -> Result<RegistryValue, String> {
     let trimmed = data.trim();
-    if trimmed.starts_with("string:") {
-        let s = trimmed["string:".len()..].trim().trim_matches('"').to_string();
+    if let Some(rest) = trimmed.strip_prefix("string:") {
+        let s = rest.trim().trim_matches('"').to_string();
         return Ok(RegistryValue::new("", RegistryValueData::String(unescape(&s))));
     }
-    if trimmed.starts_with("expand_string:") {
-        let s = trimmed["expand_string:".len()..].trim().trim_matches('"').to_string();
+    if let Some(rest) = trimmed.strip_prefix("expand_string:") {
+        let s = rest.trim().trim_matches('"').to_string();
         return Ok(RegistryValue::new("", RegistryValueData::ExpandString(unescape(&s))));
     }
-    if trimmed.starts_with("multi_string:") {
-        let content = trimmed["multi_string:".len()..].trim();
+    if let Some(rest) = trimmed.strip_prefix("multi_string:") {
+        let content = rest.trim();
         let inner = content.trim_matches(['[', ']'].as_ref());
         let mut values = Vec::new();
         if !inner.is_empty() {
@@ -301,22 +622,35 @@ fn parse_value_data_part(data: &str) -> Result<RegistryValue, String> {
         }
         return Ok(RegistryValue::new("", RegistryValueData::MultiString(values)));
     }
-    if trimmed.starts_with("dword:") {
-        let v = u32::from_str_radix(trimmed["dword:".len()..].trim(), 16).map_err(|e| e.to_string())?;
+    if let Some(rest) = trimmed.strip_prefix("dword:") {
+        let v = u32::from_str_radix(rest.trim(), 16).map_err(|e| e.to_string())?;
         return Ok(RegistryValue::new("", RegistryValueData::Dword(v)));
     }
-    if trimmed.starts_with("qword:") {
-        let v = u64::from_str_radix(trimmed["qword:".len()..].trim(), 16).map_err(|e| e.to_string())?;
+    if let Some(rest) = trimmed.strip_prefix("qword:") {
+        let v = u64::from_str_radix(rest.trim(), 16).map_err(|e| e.to_string())?;
         return Ok(RegistryValue::new("", RegistryValueData::Qword(v)));
     }
+    if let Some(rest) = trimmed.strip_prefix("dword_be:") {
+        let v = u32::from_str_radix(rest.trim(), 16).map_err(|e| e.to_string())?;
+        return Ok(RegistryValue::new("", RegistryValueData::DwordBigEndian(v)));
+    }
+    if let Some(rest) = trimmed.strip_prefix("link:") {
+        let s = rest.trim().trim_matches('"').to_string();
+        return Ok(RegistryValue::new("", RegistryValueData::Link(unescape(&s))));
+    }
     if trimmed.starts_with("hex(") {
         let end = trimmed.find("):").ok_or("bad hex")?;
         let ty = u32::from_str_radix(&trimmed[4..end], 16).map_err(|e| e.to_string())?;
         let bytes = parse_hex_bytes(&trimmed[end + 2..])?;
-        return Ok(RegistryValue::new("", RegistryValueData::Binary(bytes, ty)));
+        let data = match ty {
+            // Well-formed QWORDs are exported as `qword:`, so `hex(b):` always came from `Binary`.
+            REG_QWORD => RegistryValueData::Binary(bytes, ty),
+            _ => RegistryValueData::from_raw(ty, bytes),
+        };
+        return Ok(RegistryValue::new("", data));
     }
-    if trimmed.starts_with("hex:") {
-        let bytes = parse_hex_bytes(&trimmed["hex:".len()..])?;
+    if let Some(rest) = trimmed.strip_prefix("hex:") {
+        let bytes = parse_hex_bytes(rest)?;
         return Ok(RegistryValue::new("", RegistryValueData::Binary(bytes, REG_BINARY)));
     }
     Err("unknown value format".into())
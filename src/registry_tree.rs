@@ -0,0 +1,82 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::registry_key::{KeyNode, RegistryKey};
+use crate::registry_value::RegistryValue;
+
+/// Owned, detached copy of a key and all of its descendants.
+///
+/// Unlike `KeyNode` this carries no parent links or shared ownership, so it can be cloned,
+/// compared and (with the `serde` feature) serialized to any serde format.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct KeyTree {
+    pub name: String,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    pub class_name: Option<String>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub modification_time: u64,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub is_symlink: bool,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub is_volatile: bool,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub values: Vec<RegistryValue>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub subkeys: Vec<KeyTree>,
+}
+
+impl KeyTree {
+    /// Capture `node` and everything below it.
+    pub fn from_node(node: &KeyNode) -> Self {
+        let guard = node.borrow();
+        Self {
+            name: guard.name.clone(),
+            class_name: guard.class_name.clone(),
+            modification_time: guard.modification_time,
+            is_symlink: guard.is_symlink,
+            is_volatile: guard.is_volatile,
+            values: guard.values().values().cloned().collect(),
+            subkeys: guard.subkeys().values().map(KeyTree::from_node).collect(),
+        }
+    }
+
+    /// Build a new root holding this tree's metadata, values and subkeys.
+    pub fn to_root(&self) -> KeyNode {
+        let root = RegistryKey::create_root();
+        self.merge_into(&root);
+        root
+    }
+
+    /// Recreate this tree as a subkey of `parent`, merging into an existing key of the same name.
+    pub fn graft_into(&self, parent: &KeyNode) -> KeyNode {
+        let node = RegistryKey::create_subkey(parent, self.name.clone());
+        self.merge_into(&node);
+        node
+    }
+
+    /// Copy this tree's metadata and values onto `node` and graft its subkeys below it.
+    ///
+    /// The tree wins: class name, flags and modification time are replaced, values of the same
+    /// name overwritten, and values or subkeys only `node` has are kept.
+    pub fn merge_into(&self, node: &KeyNode) {
+        {
+            let mut guard = node.borrow_mut();
+            guard.class_name = self.class_name.clone();
+            guard.is_symlink = self.is_symlink;
+            guard.is_volatile = self.is_volatile;
+            for value in &self.values {
+                guard.set_value_for_loading(value.name.clone(), value.clone());
+            }
+        }
+        for sub in &self.subkeys {
+            sub.graft_into(node);
+        }
+        node.borrow_mut().modification_time = self.modification_time;
+    }
+
+    /// Number of keys in the tree, including this one.
+    pub fn key_count(&self) -> usize {
+        1 + self.subkeys.iter().map(KeyTree::key_count).sum::<usize>()
+    }
+}
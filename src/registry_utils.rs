@@ -6,7 +6,13 @@ pub fn timestamp_to_filetime(timestamp: u64) -> u64 {
 }
 
 pub fn filetime_to_timestamp(filetime: u64) -> u64 {
-    (filetime - TICKS_1601_TO_1970) / TICKS_PER_SEC
+    filetime.saturating_sub(TICKS_1601_TO_1970) / TICKS_PER_SEC
+}
+
+/// Convert a `SystemTime` to FILETIME ticks, keeping the 100ns resolution Wine writes in `#time=`.
+pub fn system_time_to_filetime(time: std::time::SystemTime) -> u64 {
+    let since_epoch = time.duration_since(std::time::UNIX_EPOCH).unwrap_or_default();
+    (since_epoch.as_nanos() / 100) as u64 + TICKS_1601_TO_1970
 }
 
 pub fn is_string_type(ty: u32) -> bool {
@@ -20,6 +26,13 @@ pub fn data_type_prefix(ty: u32) -> &'static str {
         crate::REG_MULTI_SZ => "str(7):",
         crate::REG_DWORD => "dword:",
         crate::REG_BINARY => "hex:",
+        crate::REG_NONE => "hex(0):",
+        crate::REG_DWORD_BIG_ENDIAN => "hex(5):",
+        crate::REG_LINK => "hex(6):",
+        crate::REG_RESOURCE_LIST => "hex(8):",
+        crate::REG_FULL_RESOURCE_DESCRIPTOR => "hex(9):",
+        crate::REG_RESOURCE_REQUIREMENTS_LIST => "hex(a):",
+        crate::REG_QWORD => "hex(b):",
         _ => "",
     }
 }
@@ -33,11 +46,9 @@ pub fn hex_digit_value(c: char) -> Option<u8> {
     }
 }
 
+/// Stamp `node` and its whole subtree with the current time of the tree's clock.
 pub fn set_current_time_recursive(node: &crate::registry_key::KeyNode) {
-    let now = timestamp_to_filetime(std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_secs());
+    let now = crate::registry_key::RegistryKey::current_time(node);
     set_time(node, now);
 }
 
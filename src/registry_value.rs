@@ -1,17 +1,27 @@
 use std::fmt;
 
-#[allow(dead_code)]
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use thiserror::Error;
+
+use crate::registry_resource::{FullResourceDescriptor, ResourceList, ResourceRequirementsList};
+
 pub const REG_NONE: u32 = 0;
 pub const REG_SZ: u32 = 1;
 pub const REG_EXPAND_SZ: u32 = 2;
 pub const REG_BINARY: u32 = 3;
 pub const REG_DWORD: u32 = 4;
-#[allow(dead_code)]
+pub const REG_DWORD_BIG_ENDIAN: u32 = 5;
 pub const REG_LINK: u32 = 6;
 pub const REG_MULTI_SZ: u32 = 7;
+pub const REG_RESOURCE_LIST: u32 = 8;
+pub const REG_FULL_RESOURCE_DESCRIPTOR: u32 = 9;
+pub const REG_RESOURCE_REQUIREMENTS_LIST: u32 = 10;
 pub const REG_QWORD: u32 = 11;
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum RegistryValueData {
     String(String),
     ExpandString(String),
@@ -19,9 +29,60 @@ pub enum RegistryValueData {
     Dword(u32),
     Qword(u64),
     Binary(Vec<u8>, u32),
+    None(Vec<u8>),
+    /// `REG_LINK` target, stored on disk as UTF-16LE without a terminator.
+    Link(String),
+    DwordBigEndian(u32),
+    ResourceList(Vec<u8>),
+    FullResourceDescriptor(Vec<u8>),
+    ResourceRequirementsList(Vec<u8>),
+    /// `REG_SZ`, `REG_EXPAND_SZ` or `REG_MULTI_SZ` data that does not decode cleanly (lone
+    /// surrogates, missing terminator, odd length, trailing garbage). The bytes are kept
+    /// exactly; `RegistryValue::lossy_string`/`as_multi` give a decoded view.
+    RawString(Vec<u8>, u32),
+}
+
+impl RegistryValueData {
+    /// Build data of type `ty` from its on-disk bytes, using the dedicated variant when the
+    /// bytes are well formed for it and `Binary` otherwise. String types only decode when
+    /// re-encoding gives back the same bytes, and are kept as `RawString` otherwise. `REG_DWORD`
+    /// is left as `Binary`; Wine only writes it as hex when it is malformed.
+    pub fn from_raw(ty: u32, bytes: Vec<u8>) -> Self {
+        match ty {
+            REG_NONE => RegistryValueData::None(bytes),
+            REG_SZ | REG_EXPAND_SZ | REG_MULTI_SZ => decode_string(ty, bytes),
+            REG_QWORD if bytes.len() == 8 => RegistryValueData::Qword(u64::from_le_bytes(bytes.as_slice().try_into().unwrap())),
+            REG_DWORD_BIG_ENDIAN if bytes.len() == 4 => {
+                RegistryValueData::DwordBigEndian(u32::from_be_bytes(bytes.as_slice().try_into().unwrap()))
+            }
+            REG_LINK if bytes.len().is_multiple_of(2) => {
+                let units: Vec<u16> = bytes.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+                match String::from_utf16(&units) {
+                    Ok(target) => RegistryValueData::Link(target),
+                    Err(_) => RegistryValueData::Binary(bytes, ty),
+                }
+            }
+            REG_RESOURCE_LIST => RegistryValueData::ResourceList(bytes),
+            REG_FULL_RESOURCE_DESCRIPTOR => RegistryValueData::FullResourceDescriptor(bytes),
+            REG_RESOURCE_REQUIREMENTS_LIST => RegistryValueData::ResourceRequirementsList(bytes),
+            _ => RegistryValueData::Binary(bytes, ty),
+        }
+    }
+}
+
+/// Error returned by the typed value accessors.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ValueError {
+    #[error("value '{0}' not found")]
+    Missing(String),
+    #[error("value '{name}' has type {actual}, expected {expected}")]
+    WrongType { name: String, expected: &'static str, actual: u32 },
+    #[error("value '{name}' is not a valid {expected}: {reason}")]
+    Invalid { name: String, expected: &'static str, reason: String },
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct RegistryValue {
     pub name: String,
     pub data: RegistryValueData,
@@ -43,6 +104,13 @@ impl RegistryValue {
             RegistryValueData::Dword(_) => REG_DWORD,
             RegistryValueData::Qword(_) => REG_QWORD,
             RegistryValueData::Binary(_, ty) => ty,
+            RegistryValueData::None(_) => REG_NONE,
+            RegistryValueData::Link(_) => REG_LINK,
+            RegistryValueData::DwordBigEndian(_) => REG_DWORD_BIG_ENDIAN,
+            RegistryValueData::ResourceList(_) => REG_RESOURCE_LIST,
+            RegistryValueData::FullResourceDescriptor(_) => REG_FULL_RESOURCE_DESCRIPTOR,
+            RegistryValueData::ResourceRequirementsList(_) => REG_RESOURCE_REQUIREMENTS_LIST,
+            RegistryValueData::RawString(_, ty) => ty,
         }
     }
 
@@ -64,11 +132,207 @@ impl RegistryValue {
             }
             RegistryValueData::Dword(v) => v.to_le_bytes().to_vec(),
             RegistryValueData::Qword(v) => v.to_le_bytes().to_vec(),
-            RegistryValueData::Binary(v, _) => v.clone(),
+            RegistryValueData::Binary(v, _)
+            | RegistryValueData::None(v)
+            | RegistryValueData::ResourceList(v)
+            | RegistryValueData::FullResourceDescriptor(v)
+            | RegistryValueData::ResourceRequirementsList(v)
+            | RegistryValueData::RawString(v, _) => v.clone(),
+            RegistryValueData::Link(v) => v.encode_utf16().flat_map(|c| c.to_le_bytes()).collect(),
+            RegistryValueData::DwordBigEndian(v) => v.to_be_bytes().to_vec(),
         }
     }
 }
 
+/// Typed accessors. Coercions follow what Wine's own config readers accept.
+impl RegistryValue {
+    /// `REG_SZ` or `REG_EXPAND_SZ` data (the latter is returned unexpanded). Malformed data is
+    /// decoded lossily.
+    pub fn as_string(&self) -> Result<String, ValueError> {
+        self.lossy_string().ok_or_else(|| self.wrong_type("string"))
+    }
+
+    /// Decoded view of `REG_SZ`/`REG_EXPAND_SZ` data. For a `RawString` this stops at the first
+    /// NUL and replaces invalid UTF-16 with U+FFFD; `None` for other types.
+    pub fn lossy_string(&self) -> Option<String> {
+        match &self.data {
+            RegistryValueData::String(v) | RegistryValueData::ExpandString(v) => Some(v.clone()),
+            RegistryValueData::RawString(bytes, REG_SZ | REG_EXPAND_SZ) => {
+                let units: Vec<u16> = utf16_units(bytes).take_while(|u| *u != 0).collect();
+                Some(String::from_utf16_lossy(&units))
+            }
+            _ => None,
+        }
+    }
+
+    /// `REG_DWORD`, a `REG_QWORD` that fits, a 4-byte `REG_BINARY`, or a string holding a
+    /// decimal or `0x`-prefixed hex number.
+    pub fn as_u32(&self) -> Result<u32, ValueError> {
+        match &self.data {
+            RegistryValueData::Dword(v) | RegistryValueData::DwordBigEndian(v) => Ok(*v),
+            RegistryValueData::Qword(v) => {
+                u32::try_from(*v).map_err(|_| self.invalid("u32", format!("{} is out of range", v)))
+            }
+            RegistryValueData::String(_) | RegistryValueData::ExpandString(_) | RegistryValueData::RawString(..) => {
+                let s = self.as_string()?;
+                let v = parse_number(&s).ok_or_else(|| self.invalid("u32", format!("'{}' is not a number", s)))?;
+                u32::try_from(v).map_err(|_| self.invalid("u32", format!("{} is out of range", v)))
+            }
+            RegistryValueData::Binary(bytes, ty) if matches!(*ty, REG_BINARY | REG_DWORD) => {
+                let arr: [u8; 4] = bytes
+                    .as_slice()
+                    .try_into()
+                    .map_err(|_| self.invalid("u32", format!("expected 4 bytes, found {}", bytes.len())))?;
+                Ok(u32::from_le_bytes(arr))
+            }
+            _ => Err(self.wrong_type("u32")),
+        }
+    }
+
+    /// `REG_QWORD`, `REG_DWORD`, a 4- or 8-byte `REG_BINARY`, or a numeric string.
+    pub fn as_u64(&self) -> Result<u64, ValueError> {
+        match &self.data {
+            RegistryValueData::Qword(v) => Ok(*v),
+            RegistryValueData::Dword(v) | RegistryValueData::DwordBigEndian(v) => Ok(*v as u64),
+            RegistryValueData::String(_) | RegistryValueData::ExpandString(_) | RegistryValueData::RawString(..) => {
+                let s = self.as_string()?;
+                parse_number(&s).ok_or_else(|| self.invalid("u64", format!("'{}' is not a number", s)))
+            }
+            RegistryValueData::Binary(bytes, ty) if matches!(*ty, REG_BINARY | REG_DWORD | REG_QWORD) => {
+                match bytes.len() {
+                    4 => Ok(u32::from_le_bytes(bytes.as_slice().try_into().unwrap()) as u64),
+                    8 => Ok(u64::from_le_bytes(bytes.as_slice().try_into().unwrap())),
+                    n => Err(self.invalid("u64", format!("expected 4 or 8 bytes, found {}", n))),
+                }
+            }
+            _ => Err(self.wrong_type("u64")),
+        }
+    }
+
+    /// Non-zero numbers are true. Strings are judged by their first character the way
+    /// Wine's `IS_OPTION_TRUE`/`IS_OPTION_FALSE` do: `y`, `t`, `1` or `n`, `f`, `0`.
+    pub fn as_bool(&self) -> Result<bool, ValueError> {
+        if let Some(s) = self.lossy_string() {
+            return match s.trim_start().chars().next() {
+                Some('y' | 'Y' | 't' | 'T' | '1') => Ok(true),
+                Some('n' | 'N' | 'f' | 'F' | '0') => Ok(false),
+                _ => Err(self.invalid("bool", format!("'{}' is not a boolean", s))),
+            };
+        }
+        match &self.data {
+            RegistryValueData::MultiString(_) | RegistryValueData::RawString(..) => Err(self.wrong_type("bool")),
+            _ => self.as_u64().map(|v| v != 0).map_err(|e| match e {
+                ValueError::WrongType { .. } => self.wrong_type("bool"),
+                other => other,
+            }),
+        }
+    }
+
+    /// `REG_MULTI_SZ` entries; a plain `REG_SZ`/`REG_EXPAND_SZ` is returned as a single entry.
+    /// Malformed multi-strings are read up to the first empty entry, decoded lossily.
+    pub fn as_multi(&self) -> Result<Vec<String>, ValueError> {
+        match &self.data {
+            RegistryValueData::MultiString(v) => Ok(v.clone()),
+            RegistryValueData::RawString(bytes, REG_MULTI_SZ) => {
+                let units: Vec<u16> = utf16_units(bytes).collect();
+                Ok(units
+                    .split(|u| *u == 0)
+                    .take_while(|part| !part.is_empty())
+                    .map(String::from_utf16_lossy)
+                    .collect())
+            }
+            _ => self.lossy_string().map(|s| vec![s]).ok_or_else(|| self.wrong_type("multi-string")),
+        }
+    }
+
+    /// The raw data as stored on disk. Every type can be read as bytes.
+    pub fn as_bytes(&self) -> Vec<u8> {
+        self.raw_bytes()
+    }
+
+    /// Target of a `REG_LINK` value.
+    pub fn as_link(&self) -> Result<String, ValueError> {
+        match &self.data {
+            RegistryValueData::Link(v) => Ok(v.clone()),
+            _ => Err(self.wrong_type("link")),
+        }
+    }
+
+    /// Decoded `REG_RESOURCE_LIST` data.
+    pub fn as_resource_list(&self) -> Result<ResourceList, ValueError> {
+        match &self.data {
+            RegistryValueData::ResourceList(bytes) => ResourceList::parse(bytes).map_err(|e| self.invalid("resource list", e)),
+            _ => Err(self.wrong_type("resource list")),
+        }
+    }
+
+    /// Decoded `REG_FULL_RESOURCE_DESCRIPTOR` data.
+    pub fn as_full_resource_descriptor(&self) -> Result<FullResourceDescriptor, ValueError> {
+        match &self.data {
+            RegistryValueData::FullResourceDescriptor(bytes) => {
+                FullResourceDescriptor::parse(bytes).map_err(|e| self.invalid("full resource descriptor", e))
+            }
+            _ => Err(self.wrong_type("full resource descriptor")),
+        }
+    }
+
+    /// Decoded `REG_RESOURCE_REQUIREMENTS_LIST` data.
+    pub fn as_resource_requirements_list(&self) -> Result<ResourceRequirementsList, ValueError> {
+        match &self.data {
+            RegistryValueData::ResourceRequirementsList(bytes) => {
+                ResourceRequirementsList::parse(bytes).map_err(|e| self.invalid("resource requirements list", e))
+            }
+            _ => Err(self.wrong_type("resource requirements list")),
+        }
+    }
+
+    fn wrong_type(&self, expected: &'static str) -> ValueError {
+        ValueError::WrongType { name: self.name.clone(), expected, actual: self.reg_type() }
+    }
+
+    fn invalid(&self, expected: &'static str, reason: String) -> ValueError {
+        ValueError::Invalid { name: self.name.clone(), expected, reason }
+    }
+}
+
+/// Decode string-typed bytes, keeping them as `RawString` unless the decoded value re-encodes
+/// to exactly the same bytes.
+fn decode_string(ty: u32, bytes: Vec<u8>) -> RegistryValueData {
+    let decoded = match bytes.len() % 2 {
+        0 => String::from_utf16(&utf16_units(&bytes).collect::<Vec<_>>()).ok(),
+        _ => None,
+    };
+    let candidate = decoded.and_then(|text| {
+        let text = text.strip_suffix('\u{0}')?;
+        Some(match ty {
+            REG_SZ => RegistryValueData::String(text.to_string()),
+            REG_EXPAND_SZ => RegistryValueData::ExpandString(text.to_string()),
+            _ => match text {
+                "" => RegistryValueData::MultiString(Vec::new()),
+                _ => RegistryValueData::MultiString(
+                    text.strip_suffix('\u{0}')?.split('\u{0}').map(|s| s.to_string()).collect(),
+                ),
+            },
+        })
+    });
+    match candidate {
+        Some(data) if RegistryValue::new("", data.clone()).raw_bytes() == bytes => data,
+        _ => RegistryValueData::RawString(bytes, ty),
+    }
+}
+
+fn utf16_units(bytes: &[u8]) -> impl Iterator<Item = u16> + '_ {
+    bytes.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]]))
+}
+
+fn parse_number(s: &str) -> Option<u64> {
+    let trimmed = s.trim();
+    match trimmed.strip_prefix("0x").or_else(|| trimmed.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => trimmed.parse::<u64>().ok(),
+    }
+}
+
 impl fmt::Display for RegistryValue {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match &self.data {
@@ -78,6 +342,13 @@ impl fmt::Display for RegistryValue {
             RegistryValueData::Dword(v) => write!(f, "dword:{:#010x}", v),
             RegistryValueData::Qword(v) => write!(f, "qword:{:#018x}", v),
             RegistryValueData::Binary(v, t) => write!(f, "hex({}):{}", t, v.len()),
+            RegistryValueData::None(v) => write!(f, "none:{}", v.len()),
+            RegistryValueData::Link(v) => write!(f, "link:\"{}\"", v),
+            RegistryValueData::DwordBigEndian(v) => write!(f, "dword_be:{:#010x}", v),
+            RegistryValueData::ResourceList(v) => write!(f, "resource_list:{}", v.len()),
+            RegistryValueData::FullResourceDescriptor(v) => write!(f, "full_resource_descriptor:{}", v.len()),
+            RegistryValueData::ResourceRequirementsList(v) => write!(f, "resource_requirements_list:{}", v.len()),
+            RegistryValueData::RawString(v, t) => write!(f, "raw_string({}):{}", t, v.len()),
         }
     }
 }
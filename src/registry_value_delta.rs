@@ -0,0 +1,162 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::registry_comparator::RegistryChange;
+use crate::registry_value::{RegistryValue, RegistryValueData};
+
+/// Largest table the multi-string item diff builds before it falls back to replacing the
+/// differing middle part wholesale.
+const MAX_LCS_CELLS: usize = 4_000_000;
+
+/// What changed inside a modified value, see `ValueDelta::between`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ValueDelta {
+    /// Items removed from and inserted into a `REG_MULTI_SZ`.
+    MultiString(Vec<ListEdit>),
+    /// Changed byte ranges of binary data.
+    Binary(Vec<ByteRange>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ListEdit {
+    /// `item` at `index` of the old list.
+    Removed { index: usize, item: String },
+    /// `item` at `index` of the new list.
+    Inserted { index: usize, item: String },
+}
+
+/// `old` at `offset` of the old data was replaced by `new`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ByteRange {
+    pub offset: usize,
+    pub old: Vec<u8>,
+    pub new: Vec<u8>,
+}
+
+impl ValueDelta {
+    /// Item edits between two multi-strings or changed ranges between two binary values of
+    /// the same type; `None` for other values.
+    pub fn between(old: &RegistryValue, new: &RegistryValue) -> Option<ValueDelta> {
+        match (&old.data, &new.data) {
+            (RegistryValueData::MultiString(a), RegistryValueData::MultiString(b)) => Some(ValueDelta::MultiString(list_edits(a, b))),
+            (RegistryValueData::Binary(a, ta), RegistryValueData::Binary(b, tb)) if ta == tb => Some(ValueDelta::Binary(byte_ranges(a, b))),
+            _ => None,
+        }
+    }
+
+    /// `old` with the delta applied, or `None` if the delta does not fit it.
+    pub fn apply(&self, old: &RegistryValue) -> Option<RegistryValue> {
+        let data = match (self, &old.data) {
+            (ValueDelta::MultiString(edits), RegistryValueData::MultiString(items)) => {
+                let mut items = items.clone();
+                for edit in edits.iter().rev() {
+                    if let ListEdit::Removed { index, item } = edit {
+                        if items.get(*index) != Some(item) {
+                            return None;
+                        }
+                        items.remove(*index);
+                    }
+                }
+                for edit in edits {
+                    if let ListEdit::Inserted { index, item } = edit {
+                        if *index > items.len() {
+                            return None;
+                        }
+                        items.insert(*index, item.clone());
+                    }
+                }
+                RegistryValueData::MultiString(items)
+            }
+            (ValueDelta::Binary(ranges), RegistryValueData::Binary(bytes, ty)) => {
+                let mut bytes = bytes.clone();
+                for range in ranges.iter().rev() {
+                    if bytes.get(range.offset..range.offset + range.old.len()) != Some(&range.old[..]) {
+                        return None;
+                    }
+                    bytes.splice(range.offset..range.offset + range.old.len(), range.new.iter().copied());
+                }
+                RegistryValueData::Binary(bytes, *ty)
+            }
+            _ => return None,
+        };
+        Some(RegistryValue::new(old.name.clone(), data))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        match self {
+            ValueDelta::MultiString(edits) => edits.is_empty(),
+            ValueDelta::Binary(ranges) => ranges.is_empty(),
+        }
+    }
+}
+
+impl RegistryChange {
+    /// The fine-grained delta of a `ValueModified`, see `ValueDelta::between`.
+    pub fn value_delta(&self) -> Option<ValueDelta> {
+        match self {
+            RegistryChange::ValueModified(_, _, old, new) => ValueDelta::between(old, new),
+            _ => None,
+        }
+    }
+}
+
+/// Removals (by old index, ascending) followed by insertions (by new index, ascending)
+/// turning `a` into `b`, from a longest common subsequence.
+fn list_edits(a: &[String], b: &[String]) -> Vec<ListEdit> {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..].iter().rev().zip(b[prefix..].iter().rev()).take_while(|(x, y)| x == y).count();
+    let (mid_a, mid_b) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+
+    let mut keep_a = vec![false; mid_a.len()];
+    let mut keep_b = vec![false; mid_b.len()];
+    if (mid_a.len() + 1) * (mid_b.len() + 1) <= MAX_LCS_CELLS {
+        let width = mid_b.len() + 1;
+        let mut table = vec![0u32; (mid_a.len() + 1) * width];
+        for i in (0..mid_a.len()).rev() {
+            for j in (0..mid_b.len()).rev() {
+                table[i * width + j] = match mid_a[i] == mid_b[j] {
+                    true => table[(i + 1) * width + j + 1] + 1,
+                    false => table[(i + 1) * width + j].max(table[i * width + j + 1]),
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < mid_a.len() && j < mid_b.len() {
+            if mid_a[i] == mid_b[j] {
+                keep_a[i] = true;
+                keep_b[j] = true;
+                i += 1;
+                j += 1;
+            } else if table[(i + 1) * width + j] >= table[i * width + j + 1] {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+    }
+
+    let removed = keep_a.iter().enumerate().filter(|(_, keep)| !**keep).map(|(i, _)| ListEdit::Removed { index: prefix + i, item: mid_a[i].clone() });
+    let inserted = keep_b.iter().enumerate().filter(|(_, keep)| !**keep).map(|(j, _)| ListEdit::Inserted { index: prefix + j, item: mid_b[j].clone() });
+    removed.chain(inserted).collect()
+}
+
+/// Runs of differing bytes when the lengths match (runs less than four bytes apart are
+/// joined), otherwise the one range between the common prefix and suffix.
+fn byte_ranges(a: &[u8], b: &[u8]) -> Vec<ByteRange> {
+    if a.len() != b.len() {
+        let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+        let suffix = a[prefix..].iter().rev().zip(b[prefix..].iter().rev()).take_while(|(x, y)| x == y).count();
+        return vec![ByteRange { offset: prefix, old: a[prefix..a.len() - suffix].to_vec(), new: b[prefix..b.len() - suffix].to_vec() }];
+    }
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for i in (0..a.len()).filter(|&i| a[i] != b[i]) {
+        match ranges.last_mut() {
+            Some((_, end)) if i - *end < 4 => *end = i + 1,
+            _ => ranges.push((i, i + 1)),
+        }
+    }
+    ranges.into_iter().map(|(start, end)| ByteRange { offset: start, old: a[start..end].to_vec(), new: b[start..end].to_vec() }).collect()
+}
@@ -1,16 +1,99 @@
-use std::fs;
+use std::cmp::Ordering;
+use std::io::{self, BufWriter, Write};
 use std::path::Path;
 
 use crate::architecture::Architecture;
-use crate::registry_key::KeyNode;
+use crate::registry_clock::FixedClock;
+use crate::registry_key::{KeyNode, RegistryKey};
+use crate::registry_save::SaveOptions;
 use crate::registry_utils::filetime_to_timestamp;
-use crate::registry_value::{RegistryValueData, REG_BINARY};
+use crate::registry_value::{RegistryValueData, REG_BINARY, REG_EXPAND_SZ, REG_MULTI_SZ, REG_SZ};
 
+#[derive(Clone)]
 pub struct RegistryWriter {
     pub relative_base: String,
     pub architecture: Architecture,
 }
 
+/// A `RegistryWriter` with non-default formatting, see `RegistryWriter::with_options`.
+#[derive(Clone)]
+pub struct FormattedWriter {
+    writer: RegistryWriter,
+    options: WriterOptions,
+}
+
+/// Formatting choices for `RegistryWriter`. The default matches current Wine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WriterOptions {
+    /// Column after which hex data continues on the next line (Wine: 76, 0 never wraps).
+    pub hex_wrap_width: usize,
+    /// Write the `#time=` line after each key (Wine 4.x and older do not).
+    pub emit_time: bool,
+    pub qword_style: QwordStyle,
+    /// Leave out keys without values, subkeys, class or link flag. They are lost on reload.
+    pub skip_empty_keys: bool,
+    pub line_ending: LineEnding,
+    pub key_order: KeyOrder,
+    /// Last-write time (FILETIME) written for every key instead of its own, see `reproducible`.
+    pub fixed_time: Option<u64>,
+}
+
+impl Default for WriterOptions {
+    fn default() -> Self {
+        Self {
+            hex_wrap_width: 76,
+            emit_time: true,
+            qword_style: QwordStyle::Hex,
+            skip_empty_keys: false,
+            line_ending: LineEnding::Lf,
+            key_order: KeyOrder::CaseInsensitive,
+            fixed_time: None,
+        }
+    }
+}
+
+impl WriterOptions {
+    /// Default formatting with every key stamped with the time of
+    /// `FixedClock::reproducible(timestamp)`, so the same tree always gives the same bytes.
+    pub fn reproducible(timestamp: Option<u64>) -> Self {
+        Self {
+            fixed_time: Some(FixedClock::reproducible(timestamp).0),
+            ..Self::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QwordStyle {
+    /// `hex(b):` with the little-endian bytes, as Wine writes them.
+    Hex,
+    /// `qword:%016x`, more readable and accepted by `RegistryParser`, but not by Wine.
+    Qword,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    Lf,
+    CrLf,
+}
+
+/// Order of sibling keys in the output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyOrder {
+    /// Case-insensitive by name, the order Wine keeps keys in.
+    CaseInsensitive,
+    /// By name as written, uppercase before lowercase.
+    CaseSensitive,
+    /// Case-insensitive, with runs of digits compared by value (`Item2` before `Item10`).
+    Natural,
+}
+
+impl Default for RegistryWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl RegistryWriter {
     pub fn new() -> Self {
         Self {
@@ -19,57 +102,155 @@ impl RegistryWriter {
         }
     }
 
+    /// This writer formatting its output according to `options`.
+    pub fn with_options(self, options: WriterOptions) -> FormattedWriter {
+        FormattedWriter { writer: self, options }
+    }
+
+    fn formatted(&self) -> FormattedWriter {
+        self.clone().with_options(WriterOptions::default())
+    }
+
     pub fn write_to_string(&self, root: &KeyNode) -> String {
-        let mut out = String::new();
-        self.write_all(root, &mut out);
-        out
+        self.formatted().write_to_string(root)
     }
 
+    /// Stream the registry to `out` (a file, stdout, a pipe, a compressing encoder, ...) one
+    /// key at a time through a `BufWriter`, without building the whole file in memory.
+    pub fn write_to<W: Write>(&self, root: &KeyNode, out: W) -> io::Result<()> {
+        self.formatted().write_to(root, out)
+    }
+
+    /// Durable save with `SaveOptions::default()` (fsync, no backups), see `save`.
     pub fn write_to_file<P: AsRef<Path>>(&self, root: &KeyNode, path: P) -> std::io::Result<()> {
-        let content = self.write_to_string(root);
-        let mut tmp = path.as_ref().to_path_buf();
-        let file_name = tmp.file_name().map(|s| s.to_string_lossy().to_string()).unwrap_or_else(|| "registry.reg".into());
-        tmp.set_file_name(format!("{}.tmp", file_name));
-        fs::write(&tmp, content.as_bytes())?;
-        fs::rename(tmp, path)?;
-        Ok(())
+        self.formatted().write_to_file(root, path)
+    }
+
+    /// Write the key at `path` and its descendants as a standalone file: keys are written
+    /// relative to it and `;; All keys relative to` is extended by its path, so the file can
+    /// be loaded on its own and put back with `LoadResult::graft_into`. Fails with
+    /// `ErrorKind::NotFound` when the key does not exist.
+    pub fn write_subtree_to<W: Write>(&self, root: &KeyNode, path: &str, out: W) -> io::Result<()> {
+        self.formatted().write_subtree_to(root, path, out)
+    }
+
+    /// `write_subtree_to` into a string; fails with `NotFound` when the key does not exist.
+    pub fn write_subtree_to_string(&self, root: &KeyNode, path: &str) -> io::Result<String> {
+        self.formatted().write_subtree_to_string(root, path)
+    }
+}
+
+impl FormattedWriter {
+    pub fn writer(&self) -> &RegistryWriter {
+        &self.writer
+    }
+
+    pub fn options(&self) -> &WriterOptions {
+        &self.options
+    }
+
+    pub fn options_mut(&mut self) -> &mut WriterOptions {
+        &mut self.options
+    }
+
+    pub fn write_to_string(&self, root: &KeyNode) -> String {
+        let mut out = Vec::new();
+        self.write_all(root, &mut out).expect("writing to a Vec cannot fail");
+        String::from_utf8(out).expect("registry output is UTF-8")
+    }
+
+    /// See `RegistryWriter::write_to`.
+    pub fn write_to<W: Write>(&self, root: &KeyNode, out: W) -> io::Result<()> {
+        let mut out = BufWriter::new(out);
+        self.write_all(root, &mut out)?;
+        out.flush()
+    }
+
+    /// See `RegistryWriter::write_to_file`.
+    pub fn write_to_file<P: AsRef<Path>>(&self, root: &KeyNode, path: P) -> std::io::Result<()> {
+        self.save(root, path, &SaveOptions::default()).map(|_| ())
     }
 
-    fn write_all(&self, root: &KeyNode, out: &mut String) {
+    /// See `RegistryWriter::write_subtree_to`.
+    pub fn write_subtree_to<W: Write>(&self, root: &KeyNode, path: &str, out: W) -> io::Result<()> {
+        let key = RegistryKey::find_key(root, path)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("key '{}' not found", path)))?;
+        let relative_base = match (self.writer.relative_base.as_str(), path_below(&key, root)) {
+            (base, sub) if sub.is_empty() => base.to_string(),
+            ("", sub) => sub,
+            (base, sub) => format!("{}\\\\{}", base, sub),
+        };
+        let mut out = BufWriter::new(out);
+        self.write_hive(&key, &relative_base, &mut out)?;
+        out.flush()
+    }
+
+    /// See `RegistryWriter::write_subtree_to_string`.
+    pub fn write_subtree_to_string(&self, root: &KeyNode, path: &str) -> io::Result<String> {
+        let mut out = Vec::new();
+        self.write_subtree_to(root, path, &mut out)?;
+        Ok(String::from_utf8(out).expect("registry output is UTF-8"))
+    }
+
+    fn write_all<W: Write>(&self, root: &KeyNode, writer: &mut W) -> io::Result<()> {
+        self.write_hive(root, &self.writer.relative_base, writer)
+    }
+
+    /// Write `base` and its descendants as a file whose keys are relative to `relative_base`.
+    fn write_hive<W: Write>(&self, base: &KeyNode, relative_base: &str, writer: &mut W) -> io::Result<()> {
+        let mut out = String::new();
         out.push_str("WINE REGISTRY Version 2\n");
-        if !self.relative_base.is_empty() {
+        if !relative_base.is_empty() {
             out.push_str(";; All keys relative to ");
-            out.push_str(&self.relative_base);
+            out.push_str(relative_base);
             out.push('\n');
         }
-        match self.architecture {
+        match self.writer.architecture {
             Architecture::Win32 => out.push_str("\n#arch=win32\n"),
             Architecture::Win64 => out.push_str("\n#arch=win64\n"),
             Architecture::Unknown => {}
         }
-        self.write_subkeys(root, root, out);
+        self.emit(writer, &out)?;
+        self.write_subkeys(base, base, writer)
     }
 
-    fn write_subkeys(&self, node: &KeyNode, base: &KeyNode, out: &mut String) {
+    fn emit<W: Write>(&self, writer: &mut W, text: &str) -> io::Result<()> {
+        match self.options.line_ending {
+            LineEnding::Lf => writer.write_all(text.as_bytes()),
+            LineEnding::CrLf => writer.write_all(text.replace('\n', "\r\n").as_bytes()),
+        }
+    }
+
+    fn write_subkeys<W: Write>(&self, node: &KeyNode, base: &KeyNode, writer: &mut W) -> io::Result<()> {
         let guard = node.borrow();
         if guard.is_volatile {
-            return;
+            return Ok(());
         }
 
-        let values: Vec<_> = guard.values().iter().map(|(_, v)| v.clone()).collect();
-        let subkeys: Vec<_> = guard.subkeys().values().cloned().collect();
+        let values: Vec<_> = guard.values().values().cloned().collect();
+        let mut subkeys: Vec<_> = guard.subkeys().values().cloned().collect();
+        match self.options.key_order {
+            KeyOrder::CaseInsensitive => {}
+            KeyOrder::CaseSensitive => subkeys.sort_by(|a, b| a.borrow().name.cmp(&b.borrow().name)),
+            KeyOrder::Natural => subkeys.sort_by(|a, b| natural_cmp(&a.borrow().name, &b.borrow().name)),
+        }
         let has_meta = guard.class_name.is_some() || guard.is_symlink;
+        let is_leaf = subkeys.is_empty() && !self.options.skip_empty_keys;
 
-        if !values.is_empty() || subkeys.is_empty() || has_meta {
+        if !values.is_empty() || is_leaf || has_meta {
+            let mut out = String::new();
             out.push('\n');
             out.push('[');
-            dump_path(node, base, out);
+            dump_path(node, base, &mut out);
             out.push_str("] ");
-            out.push_str(&filetime_to_timestamp(guard.modification_time).to_string());
-            out.push('\n');
-            out.push_str("#time=");
-            out.push_str(&format!("{:x}", guard.modification_time));
+            let time = self.options.fixed_time.unwrap_or(guard.modification_time);
+            out.push_str(&filetime_to_timestamp(time).to_string());
             out.push('\n');
+            if self.options.emit_time {
+                out.push_str("#time=");
+                out.push_str(&format!("{:x}", time));
+                out.push('\n');
+            }
             if let Some(class_name) = &guard.class_name {
                 out.push_str("#class=\"");
                 out.push_str(&escape_string(class_name));
@@ -79,24 +260,32 @@ impl RegistryWriter {
                 out.push_str("#link\n");
             }
             for value in values {
-                dump_value(&value, out);
+                dump_value(&value, &self.options, &mut out);
             }
+            self.emit(writer, &out)?;
         }
         drop(guard);
 
         for sub in subkeys {
-            self.write_subkeys(&sub, base, out);
+            self.write_subkeys(&sub, base, writer)?;
         }
+        Ok(())
     }
 }
 
 fn dump_path(node: &KeyNode, base: &KeyNode, out: &mut String) {
-    if Rc::ptr_eq(node, base) {
-        return;
-    }
+    out.push_str(&path_below(node, base));
+}
+
+/// Path of `node` below `base` (or below the tree's root if `base` is not an ancestor), in
+/// the file's `\\`-separated form.
+fn path_below(node: &KeyNode, base: &KeyNode) -> String {
     let mut parts = Vec::new();
     let mut current = Some(node.clone());
     while let Some(n) = current {
+        if Rc::ptr_eq(&n, base) {
+            break;
+        }
         let guard = n.borrow();
         if guard.name.is_empty() {
             current = guard.parent();
@@ -106,10 +295,11 @@ fn dump_path(node: &KeyNode, base: &KeyNode, out: &mut String) {
         current = guard.parent();
     }
     parts.reverse();
-    out.push_str(&parts.join("\\\\"));
+    parts.join("\\\\")
 }
 
-fn dump_value(value: &crate::registry_value::RegistryValue, out: &mut String) {
+fn dump_value(value: &crate::registry_value::RegistryValue, options: &WriterOptions, out: &mut String) {
+    let line_start = out.len();
     if value.name.is_empty() {
         out.push_str("@=");
     } else {
@@ -130,67 +320,162 @@ fn dump_value(value: &crate::registry_value::RegistryValue, out: &mut String) {
             out.push('"');
         }
         RegistryValueData::MultiString(values) => {
+            // The data's final terminator is implied; an empty list is just that terminator.
             out.push_str("str(7):\"");
-            let mut combined = String::new();
-            for (idx, part) in values.iter().enumerate() {
-                if idx > 0 {
-                    combined.push_str("\\0");
-                }
-                combined.push_str(&escape_string(part));
+            if !values.is_empty() {
+                let mut combined = values.join("\0");
+                combined.push('\0');
+                out.push_str(&escape_string(&combined));
             }
-            combined.push_str("\\0");
-            out.push_str(&combined);
             out.push('"');
         }
         RegistryValueData::Dword(v) => {
             out.push_str(&format!("dword:{:08x}", v));
         }
-        RegistryValueData::Qword(v) => {
-            let bytes = v.to_le_bytes();
-            out.push_str("hex(b):");
-            write_hex_bytes(&bytes, out, 5);
-            return;
-        }
+        RegistryValueData::Qword(v) => match options.qword_style {
+            QwordStyle::Hex => {
+                out.push_str("hex(b):");
+                write_hex_bytes(&v.to_le_bytes(), out, line_start, options.hex_wrap_width);
+                return;
+            }
+            QwordStyle::Qword => out.push_str(&format!("qword:{:016x}", v)),
+        },
         RegistryValueData::Binary(bytes, ty) => {
             if *ty == REG_BINARY {
                 out.push_str("hex:");
             } else {
                 out.push_str(&format!("hex({:x}):", ty));
             }
-            write_hex_bytes(bytes, out, if *ty == REG_BINARY { 4 } else { 6 });
+            write_hex_bytes(bytes, out, line_start, options.hex_wrap_width);
+            return;
+        }
+        RegistryValueData::None(_)
+        | RegistryValueData::Link(_)
+        | RegistryValueData::DwordBigEndian(_)
+        | RegistryValueData::ResourceList(_)
+        | RegistryValueData::FullResourceDescriptor(_)
+        | RegistryValueData::ResourceRequirementsList(_) => {
+            out.push_str(&format!("hex({:x}):", value.reg_type()));
+            write_hex_bytes(&value.raw_bytes(), out, line_start, options.hex_wrap_width);
             return;
         }
+        RegistryValueData::RawString(bytes, ty) => {
+            // Like Wine, anything properly terminated is written as a string and the rest as hex.
+            let units: Vec<u16> = bytes.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+            match units.split_last() {
+                Some((0, text)) if bytes.len().is_multiple_of(2) && matches!(*ty, REG_SZ | REG_EXPAND_SZ | REG_MULTI_SZ) => {
+                    out.push_str(match *ty {
+                        REG_EXPAND_SZ => "str(2):\"",
+                        REG_MULTI_SZ => "str(7):\"",
+                        _ => "\"",
+                    });
+                    out.push_str(&escape_units(text));
+                    out.push('"');
+                }
+                _ => {
+                    out.push_str(&format!("hex({:x}):", ty));
+                    write_hex_bytes(bytes, out, line_start, options.hex_wrap_width);
+                    return;
+                }
+            }
+        }
     }
     out.push('\n');
 }
 
-fn write_hex_bytes(bytes: &[u8], out: &mut String, mut line_count: usize) {
+/// Append `bytes` as hex, wrapping the way Wine's `dump_value` does: the count includes the
+/// value name and type prefix written since `line_start`, and a line is continued once a
+/// comma takes it past `width`.
+fn write_hex_bytes(bytes: &[u8], out: &mut String, line_start: usize, width: usize) {
+    let mut count = out.len() - line_start;
     for (idx, b) in bytes.iter().enumerate() {
         out.push_str(&format!("{:02x}", b));
-        line_count += 2;
+        count += 2;
         if idx + 1 != bytes.len() {
             out.push(',');
-            line_count += 1;
-        }
-        if line_count >= 76 && idx + 1 != bytes.len() {
-            out.push_str("\\\n  ");
-            line_count = 2;
+            count += 1;
+            if width > 0 && count > width {
+                out.push_str("\\\n  ");
+                count = 2;
+            }
         }
     }
     out.push('\n');
 }
 
+/// Case-insensitive comparison treating runs of ASCII digits as numbers.
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a.chars().peekable(), b.chars().peekable());
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let x = take_number(&mut a);
+                let y = take_number(&mut b);
+                let x = x.trim_start_matches('0');
+                let y = y.trim_start_matches('0');
+                let ord = x.len().cmp(&y.len()).then_with(|| x.cmp(y));
+                if ord != Ordering::Equal {
+                    return ord;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let ord = x.to_uppercase().cmp(y.to_uppercase());
+                if ord != Ordering::Equal {
+                    return ord;
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+fn take_number(chars: &mut std::iter::Peekable<std::str::Chars>) -> String {
+    let mut digits = String::new();
+    while let Some(c) = chars.next_if(|c| c.is_ascii_digit()) {
+        digits.push(c);
+    }
+    digits
+}
+
 fn escape_string(s: &str) -> String {
+    let units: Vec<u16> = s.encode_utf16().collect();
+    escape_units(&units)
+}
+
+/// Escape UTF-16 text the way Wine's `dump_strW` does: C escapes or octal for control
+/// characters and `\x` for anything above ASCII (including lone surrogates), padded when the
+/// next character could be read as part of the escape.
+fn escape_units(units: &[u16]) -> String {
+    const ESCAPES: &[u8; 32] = b".......abtnvfr.............e....";
     let mut out = String::new();
-    for ch in s.chars() {
-        match ch {
-            '\\' => out.push_str("\\\\"),
-            '"' => out.push_str("\\\""),
-            '\n' => out.push_str("\\n"),
-            '\r' => out.push_str("\\r"),
-            '\t' => out.push_str("\\t"),
-            '\u{0}' => out.push_str("\\0"),
-            _ => out.push(ch),
+    for (idx, &unit) in units.iter().enumerate() {
+        let next = units.get(idx + 1).copied().unwrap_or(0);
+        if unit > 127 {
+            if next < 128 && (next as u8).is_ascii_hexdigit() {
+                out.push_str(&format!("\\x{:04x}", unit));
+            } else {
+                out.push_str(&format!("\\x{:x}", unit));
+            }
+        } else if unit < 32 {
+            let escape = ESCAPES[unit as usize];
+            if escape != b'.' {
+                out.push('\\');
+                out.push(escape as char);
+            } else if (b'0' as u16..=b'7' as u16).contains(&next) {
+                out.push_str(&format!("\\{:03o}", unit));
+            } else {
+                out.push_str(&format!("\\{:o}", unit));
+            }
+        } else {
+            let ch = unit as u8 as char;
+            if ch == '\\' || ch == '"' {
+                out.push('\\');
+            }
+            out.push(ch);
         }
     }
     out
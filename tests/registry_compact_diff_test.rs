@@ -0,0 +1,70 @@
+use winereg::*;
+
+fn load(body: &str) -> KeyNode {
+    RegistryParser.load_from_text(&format!("WINE REGISTRY Version 2\n{}", body)).expect("parse").root_key
+}
+
+const BEFORE: &str = "\n[Software\\\\Old\\\\Cache] 1700000000\n\"Size\"=dword:00000010\n\
+                      \n[Software\\\\Keep] 1700000000\n\"v\"=\"1\"\n";
+const AFTER: &str = "\n[Software\\\\Keep] 1700000000\n\"v\"=\"1\"\n\
+                     \n[Software\\\\Vendor\\\\App] 1700000000\n#time=1d9f0a0b0c0d0e0\n#class=\"AppClass\"\n\"Name\"=\"app\"\n\"Paths\"=str(7):\"a\\0b\\0\"\n\
+                     \n[Software\\\\Vendor\\\\App\\\\Plugins\\\\One] 1700000000\n\"Data\"=hex:01,02,03\n\
+                     \n[Software\\\\Vendor\\\\App\\\\MRU] 1700000000\n\"a\"=\"x\"\n";
+
+fn compact() -> CompareOptions {
+    CompareOptions { compact_subtrees: true, ..CompareOptions::default() }
+}
+
+#[test]
+fn added_and_deleted_subtrees_are_single_changes() {
+    let (before, after) = (load(BEFORE), load(AFTER));
+    let flat = RegistryComparator.compare_registries(&before, &after);
+    assert_eq!(flat.changes.len(), 16);
+
+    let diff = RegistryComparator.compare_registries_with_options(&before, &after, &compact());
+    assert_eq!(diff.changes.len(), 2);
+    match &diff.changes[..] {
+        [RegistryChange::SubtreeDeleted(old, removed), RegistryChange::SubtreeAdded(new, tree)] => {
+            assert_eq!(old, "SOFTWARE\\OLD");
+            assert_eq!(removed.key_count(), 2);
+            assert_eq!(new, "SOFTWARE\\VENDOR");
+            assert_eq!(tree.key_count(), 5);
+            assert_eq!(tree.subkeys[0].class_name.as_deref(), Some("AppClass"));
+        }
+        other => panic!("unexpected changes {:?}", other),
+    }
+    assert_eq!(diff.added_keys().len(), 1);
+    // Expanded, the flat changes come back along with the keys' metadata, as in a flat diff.
+    let expanded = diff.expand_subtrees().changes;
+    assert_eq!(expanded.len(), flat.changes.len());
+    assert!(expanded.iter().any(|c| matches!(c, RegistryChange::KeyModified(_, p)
+        if p.iter().any(|p| matches!(p, KeyPropertyChange::ClassNameChange(None, Some(c)) if c == "AppClass")))));
+
+    let ignoring = CompareOptions { ignore_keys: vec!["*\\MRU".into()], ..compact() };
+    let diff = RegistryComparator.compare_registries_with_options(&before, &after, &ignoring);
+    assert!(matches!(&diff.changes[1], RegistryChange::SubtreeAdded(_, tree) if tree.key_count() == 4));
+}
+
+#[test]
+fn compact_diff_round_trips_through_text_and_patches() {
+    let (before, after) = (load(BEFORE), load(AFTER));
+    let diff = RegistryComparator.compare_registries_with_options(&before, &after, &compact());
+    let text = TextDiffExporter.export_with_options(&diff, None, None, &TextDiffOptions::default().timestamp(0));
+    assert!(text.contains("[SOFTWARE]\n-tree:Old\n>[]\n"));
+    assert!(text.contains("+tree:Vendor\n>[]\n>#time="));
+    assert!(text.contains(">[App]\n>#time=1d9f0a0b0c0d0e0\n>#class=\"AppClass\"\n>\"Name\"=string:\"app\"\n"));
+    assert!(text.contains(">[App\\Plugins\\One]\n"));
+
+    let parsed = TextDiffParser.parse(&text).expect("parse diff");
+    assert_eq!(parsed.changes.len(), 2);
+    assert_eq!(TextDiffExporter.export_with_options(&parsed, None, None, &TextDiffOptions::default().timestamp(0)), text);
+
+    let target = load(BEFORE);
+    let result = RegistryPatcher.apply_patch(&target, &parsed, PatchOptions::default());
+    assert!(result.is_success(), "{:?}", result.failed);
+    assert!(!RegistryComparator.compare_registries(&target, &after).has_changes());
+    let app = RegistryKey::find_key(&target, "Software\\Vendor\\App").unwrap();
+    assert_eq!(app.borrow().modification_time, 0x1d9f0a0b0c0d0e0);
+    assert_eq!(app.borrow().name, "App");
+    assert_eq!(app.borrow().parent().unwrap().borrow().name, "Vendor");
+}
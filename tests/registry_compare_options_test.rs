@@ -0,0 +1,87 @@
+use winereg::*;
+
+fn load(body: &str) -> KeyNode {
+    RegistryParser.load_from_text(&format!("WINE REGISTRY Version 2\n{}", body)).expect("parse").root_key
+}
+
+fn paths(diff: &DiffResult) -> Vec<String> {
+    diff.changes
+        .iter()
+        .map(|c| match c {
+            RegistryChange::KeyAdded(p) => format!("+{}", p),
+            RegistryChange::KeyDeleted(p) => format!("-{}", p),
+            RegistryChange::KeyModified(p, _) => format!("~{}", p),
+            RegistryChange::ValueAdded(k, n, _) => format!("+{}:{}", k, n),
+            RegistryChange::ValueDeleted(k, n, _) => format!("-{}:{}", k, n),
+            RegistryChange::ValueModified(k, n, _, _) => format!("~{}:{}", k, n),
+            RegistryChange::SubtreeAdded(p, _) => format!("+{}/", p),
+            RegistryChange::SubtreeDeleted(p, _) => format!("-{}/", p),
+            RegistryChange::KeyMoved(from, to) | RegistryChange::KeyRenamed(from, to) => format!("{}->{}", from, to),
+        })
+        .collect()
+}
+
+#[test]
+fn ignore_patterns_drop_keys_and_values() {
+    let left = load(
+        "\n[Software\\\\Wine\\\\MSHTML] 1\n\"Version\"=\"1\"\n\
+         \n[Software\\\\App\\\\RecentMRU] 1\n\"a\"=\"x\"\n\
+         \n[Software\\\\App] 1\n\"LastRun\"=dword:00000001\n\"Size\"=dword:00000001\n",
+    );
+    let right = load(
+        "\n[Software\\\\Wine\\\\MSHTML] 1\n\"Version\"=\"2\"\n\
+         \n[Software\\\\App\\\\RecentMRU] 1\n\"a\"=\"y\"\n\
+         \n[Software\\\\App\\\\MRU\\\\Sub] 1\n\"b\"=\"z\"\n\
+         \n[Software\\\\App] 1\n\"LastRun\"=dword:00000002\n\"Size\"=dword:00000002\n\"LastSeen\"=dword:00000002\n",
+    );
+    assert_eq!(RegistryComparator.compare_registries(&left, &right).changes.len(), 9);
+
+    let options = CompareOptions {
+        ignore_keys: vec!["Software\\Wine\\MSHTML".into(), "*\\*MRU*".into()],
+        ignore_values: vec!["Last*".into()],
+        ..CompareOptions::default()
+    };
+    let diff = RegistryComparator.compare_registries_with_options(&left, &right, &options);
+    assert_eq!(paths(&diff), ["~SOFTWARE\\APP:Size"]);
+
+    // A pattern with a backslash names the key too.
+    let options = CompareOptions { ignore_values: vec!["*\\App\\Size".into()], ..options };
+    let diff = RegistryComparator.compare_registries_with_options(&left, &right, &options);
+    assert_eq!(paths(&diff), ["+SOFTWARE\\APP:LastSeen", "~SOFTWARE\\APP:LastRun"]);
+}
+
+#[test]
+fn tolerances_for_case_and_types() {
+    let left = load("\n[K] 1\n\"Path\"=\"C:\\\\Windows\"\n\"Num\"=dword:00000010\n\"Big\"=hex(b):01,00,00,00,00,00,00,00\n\"Exp\"=\"%PATH%\"\n");
+    let right = load("\n[K] 1\n\"Path\"=\"c:\\\\windows\"\n\"Num\"=hex:10,00,00,00\n\"Big\"=hex:01,00,00,00,00,00,00,00\n\"Exp\"=str(2):\"%PATH%\"\n");
+    assert_eq!(RegistryComparator.compare_registries(&left, &right).changes.len(), 4);
+
+    let case = CompareOptions { case_insensitive_strings: true, ..CompareOptions::default() };
+    assert_eq!(paths(&RegistryComparator.compare_registries_with_options(&left, &right, &case)).len(), 3);
+    let types = CompareOptions { type_tolerant: true, ..CompareOptions::default() };
+    assert_eq!(paths(&RegistryComparator.compare_registries_with_options(&left, &right, &types)), ["~K:Path"]);
+    let both = CompareOptions { type_tolerant: true, case_insensitive_strings: true, ..CompareOptions::default() };
+    assert!(!RegistryComparator.compare_registries_with_options(&left, &right, &both).has_changes());
+}
+
+#[test]
+fn timestamp_changes_are_reported_exported_and_applied() {
+    let left = load("\n[A] 1700000000\n#time=1d9f0a0b0c0d0e0\n\"v\"=\"1\"\n");
+    let right = load("\n[A] 1700000001\n#time=1d9f0a0b0c0d0e1\n\"v\"=\"2\"\n");
+    assert_eq!(RegistryComparator.compare_registries(&left, &right).changes.len(), 1);
+
+    let options = CompareOptions { report_timestamps: true, ..CompareOptions::default() };
+    let diff = RegistryComparator.compare_registries_with_options(&left, &right, &options);
+    assert_eq!(paths(&diff), ["~A", "~A:v"]);
+    assert!(matches!(
+        &diff.changes[0],
+        RegistryChange::KeyModified(_, props) if matches!(props[..], [KeyPropertyChange::TimeChange(0x1d9f0a0b0c0d0e0, 0x1d9f0a0b0c0d0e1)])
+    ));
+
+    let text = TextDiffExporter.export(&diff, None, None);
+    assert!(text.contains("~time:1d9f0a0b0c0d0e0->1d9f0a0b0c0d0e1\n"));
+    let parsed = TextDiffParser.parse(&text).expect("parse diff");
+    let result = RegistryPatcher.apply_patch(&left, &parsed, PatchOptions::default());
+    assert!(result.is_success());
+    assert!(!RegistryComparator.compare_registries_with_options(&left, &right, &options).has_changes());
+}
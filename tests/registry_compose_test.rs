@@ -0,0 +1,92 @@
+use winereg::*;
+
+fn load(body: &str) -> KeyNode {
+    RegistryParser.load_from_text(&format!("WINE REGISTRY Version 2\n{}", body)).expect("parse").root_key
+}
+
+fn diff(a: &KeyNode, b: &KeyNode) -> DiffResult {
+    RegistryComparator.compare_registries(a, b)
+}
+
+#[test]
+fn composed_diff_equals_applying_both() {
+    let v1 = load("\n[App] 1\n\"Mode\"=dword:00000001\n\"Keep\"=\"k\"\n\n[App\\\\Old] 1\n\"x\"=\"1\"\n");
+    let v2 = load("\n[App] 1\n\"Mode\"=dword:00000002\n\"Keep\"=\"k\"\n\"Temp\"=\"t\"\n\n[App\\\\Scratch] 1\n\"s\"=\"1\"\n");
+    let v3 = load("\n[App] 1\n\"Mode\"=dword:00000003\n\"Keep\"=\"k\"\n\n[App\\\\New] 1\n\"n\"=\"1\"\n");
+
+    let composed = diff(&v1, &v2).compose(&diff(&v2, &v3)).expect("compose");
+    let direct = diff(&v1, &v3);
+    assert_eq!(composed.changes.len(), direct.changes.len());
+    // Added then deleted: gone entirely.
+    assert!(!composed.changes.iter().any(|c| format!("{:?}", c).contains("Temp") || format!("{:?}", c).contains("SCRATCH")));
+    // Two modifications collapse into one from the first old value to the last new one.
+    assert!(composed.changes.iter().any(|c| matches!(c, RegistryChange::ValueModified(_, n, old, new)
+        if n == "Mode" && old.as_u32().unwrap() == 1 && new.as_u32().unwrap() == 3)));
+
+    let target = load("\n[App] 1\n\"Mode\"=dword:00000001\n\"Keep\"=\"k\"\n\n[App\\\\Old] 1\n\"x\"=\"1\"\n");
+    let patch = PatchOptions { delete_empty_keys: false, ..PatchOptions::default() };
+    assert!(RegistryPatcher.apply_patch(&target, &composed, patch).is_success());
+    assert!(!diff(&target, &v3).has_changes());
+
+    // A diff composed with its inverse does nothing.
+    let forward = diff(&v1, &v3);
+    assert!(!forward.compose(&forward.invert()).unwrap().has_changes());
+}
+
+#[test]
+fn second_diff_must_start_where_the_first_ends() {
+    let v1 = load("\n[App] 1\n\"Mode\"=dword:00000001\n");
+    let v2 = load("\n[App] 1\n\"Mode\"=dword:00000002\n");
+    let other = load("\n[App] 1\n\"Mode\"=dword:00000005\n\n[Gone] 1\n\"g\"=\"1\"\n");
+    let unrelated = load("\n[App] 1\n\"Mode\"=dword:00000006\n");
+
+    let err = diff(&v1, &v2).compose(&diff(&other, &unrelated)).unwrap_err();
+    assert_eq!(err.0.len(), 1);
+    assert!(matches!(&err.0[0], RegistryChange::ValueModified(_, n, _, _) if n == "Mode"));
+
+    // Values can't be added to a key the first diff deleted.
+    let deleted = diff(&other, &v1);
+    let readd = DiffResult { changes: vec![RegistryChange::ValueAdded("GONE".into(), "h".into(), RegistryValue::new("h", RegistryValueData::Dword(1)))] };
+    assert!(deleted.compose(&readd).is_err());
+    let readd_key = DiffResult { changes: [vec![RegistryChange::KeyAdded("Gone".into())], readd.changes].concat() };
+    assert!(deleted.compose(&readd_key).is_ok());
+}
+
+#[test]
+fn moves_are_kept_and_later_changes_follow_them() {
+    let v1 = load("\n[Soft\\\\App 1] 1\n\"a\"=\"1\"\n\"b\"=\"2\"\n\"c\"=\"3\"\n");
+    let v2 = load("\n[Soft\\\\App 1] 1\n\"a\"=\"1\"\n\"b\"=\"2\"\n\"c\"=\"4\"\n");
+    let v3 = load("\n[Soft\\\\App 2] 1\n\"a\"=\"1\"\n\"b\"=\"2\"\n\"c\"=\"4\"\n\"d\"=\"5\"\n");
+    let options = CompareOptions { move_threshold: Some(0.5), ..CompareOptions::default() };
+    let second = RegistryComparator.compare_registries_with_options(&v2, &v3, &options);
+    let composed = diff(&v1, &v2).compose(&second).expect("compose");
+    assert!(matches!(&composed.changes[0], RegistryChange::KeyRenamed(..)));
+    assert!(composed.changes.iter().any(|c| matches!(c, RegistryChange::ValueModified(k, n, _, _) if k == "SOFT\\App 2" && n == "c")));
+
+    let target = load("\n[Soft\\\\App 1] 1\n\"a\"=\"1\"\n\"b\"=\"2\"\n\"c\"=\"3\"\n");
+    assert!(RegistryPatcher.apply_patch(&target, &composed, PatchOptions::default()).is_success());
+    assert!(!diff(&target, &v3).has_changes());
+}
+
+#[test]
+fn composed_subtrees_keep_their_metadata() {
+    let v1 = load("\n[App] 1\n\"Keep\"=\"k\"\n");
+    let v2 = load("\n[App] 1\n\"Keep\"=\"k\"\n\n[App\\\\Plugin] 1700000000\n#time=1d9f0a0b0c0d0e0\n#class=\"PluginClass\"\n\"On\"=dword:00000001\n");
+    let v3 = load("\n[App] 1\n\"Keep\"=\"k\"\n\n[App\\\\Plugin] 1700000000\n#time=1d9f0a0b0c0d0e0\n#class=\"PluginClass\"\n\"On\"=dword:00000000\n");
+    let compact = CompareOptions { compact_subtrees: true, report_timestamps: true, ..CompareOptions::default() };
+    let first = RegistryComparator.compare_registries_with_options(&v1, &v2, &compact);
+    let second = RegistryComparator.compare_registries_with_options(&v2, &v3, &compact);
+    assert!(matches!(first.changes[..], [RegistryChange::SubtreeAdded(..)]));
+
+    let composed = first.compose(&second).expect("compose");
+    let target = load("\n[App] 1\n\"Keep\"=\"k\"\n");
+    assert!(RegistryPatcher.apply_patch(&target, &composed, PatchOptions::default()).is_success());
+    // The patcher stamps `App` itself; the added key comes out exactly as in `v3`.
+    let plugin = KeyTree::from_node(&RegistryKey::find_key(&target, "App\\Plugin").unwrap());
+    assert_eq!(plugin, KeyTree::from_node(&RegistryKey::find_key(&v3, "App\\Plugin").unwrap()));
+    assert_eq!(plugin.class_name.as_deref(), Some("PluginClass"));
+
+    // Added and then deleted again leaves nothing behind, metadata included.
+    let back = RegistryComparator.compare_registries_with_options(&v3, &v1, &compact);
+    assert!(!composed.compose(&back).unwrap().has_changes());
+}
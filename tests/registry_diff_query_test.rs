@@ -0,0 +1,80 @@
+use winereg::*;
+
+fn load(body: &str) -> KeyNode {
+    RegistryParser.load_from_text(&format!("WINE REGISTRY Version 2\n{}", body)).expect("parse").root_key
+}
+
+fn sample() -> DiffResult {
+    let old = load(
+        "\n[Software\\\\Wine\\\\Fonts] 1\n\"Arial\"=\"arial.ttf\"\n\"Tahoma\"=\"tahoma.ttf\"\n\
+         \n[Software\\\\App] 1\n\"Mode\"=dword:00000001\n\n[System\\\\Setup] 1\n\"a\"=\"1\"\n",
+    );
+    let new = load(
+        "\n[Software\\\\Wine\\\\Fonts] 1\n\"Arial\"=\"arial2.ttf\"\n\"Courier\"=\"cour.ttf\"\n\
+         \n[Software\\\\Wine\\\\Fonts\\\\Replacements] 1\n\"MS Sans\"=\"Tahoma\"\n\
+         \n[Software\\\\App] 1\n\"Mode\"=dword:00000002\n\n[System\\\\Setup] 1\n#class=\"x\"\n\"a\"=\"1\"\n",
+    );
+    RegistryComparator.compare_registries(&old, &new)
+}
+
+#[test]
+fn filter_by_kind_prefix_and_pattern() {
+    let diff = sample();
+    assert_eq!(diff.of_kind(&[ChangeKind::ValueModified]).changes.len(), 2);
+    assert_eq!(diff.of_kind(&[ChangeKind::KeyAdded, ChangeKind::KeyModified]).changes.len(), 3);
+
+    let fonts = diff.under("software\\wine\\fonts");
+    assert_eq!(fonts.changes.len(), 6);
+    assert!(diff.under("Software\\Wine\\Font").changes.is_empty());
+    assert_eq!(diff.under("").changes.len(), diff.changes.len());
+
+    assert_eq!(diff.matching("*\\Fonts\\Tahoma").changes.len(), 1);
+    assert_eq!(diff.matching("*\\Replacements").changes.len(), 3);
+
+    let (values, rest) = diff.partition(|c| c.value_name().is_some());
+    assert_eq!(values.changes.len() + rest.changes.len(), diff.changes.len());
+    assert!(rest.changes.iter().all(|c| matches!(c.kind(), ChangeKind::KeyAdded | ChangeKind::KeyModified)));
+
+    let mut only_app = diff.clone();
+    only_app.retain(|c| c.path().ends_with("APP"));
+    assert_eq!(only_app.changes.len(), 1);
+}
+
+#[test]
+fn grouping_summaries_and_splits() {
+    let diff = sample();
+    let groups = diff.group_by_key();
+    assert_eq!(groups["SOFTWARE\\WINE\\FONTS"].len(), 3);
+    assert_eq!(groups.len(), 4);
+
+    let summary = diff.summary();
+    assert_eq!(summary.keys().collect::<Vec<_>>(), ["SOFTWARE", "SYSTEM"]);
+    let software = summary["SOFTWARE"];
+    assert_eq!((software.keys_added, software.values_added, software.values_deleted, software.values_modified), (1, 2, 1, 2));
+    // The added key's metadata counts as a modification.
+    assert_eq!(software.keys_modified, 1);
+    assert_eq!(software.total(), 7);
+    assert_eq!(summary["SYSTEM"].keys_modified, 1);
+
+    let parts = diff.split_by(|c| c.kind());
+    assert_eq!(parts[&ChangeKind::ValueModified].changes.len(), 2);
+    assert_eq!(parts.values().map(|d| d.changes.len()).sum::<usize>(), diff.changes.len());
+}
+
+#[test]
+fn subtree_rebases_paths_for_an_exported_hive() {
+    let diff = sample().subtree("Software\\Wine");
+    assert!(diff.changes.iter().all(|c| c.path().starts_with("FONTS")));
+    assert_eq!(diff.changes.len(), 6);
+
+    let root = load("\n[Software\\\\Wine\\\\Fonts] 1\n\"Arial\"=\"arial.ttf\"\n\"Tahoma\"=\"tahoma.ttf\"\n");
+    let text = RegistryWriter::new().write_subtree_to_string(&root, "Software\\Wine").unwrap();
+    let hive = RegistryParser.load_from_text(&text).unwrap().root_key;
+    assert!(RegistryPatcher.apply_patch(&hive, &diff, PatchOptions::default()).is_success());
+    let replacements = RegistryKey::find_key(&hive, "Fonts\\Replacements").unwrap();
+    assert_eq!(replacements.borrow().get_string("MS Sans").unwrap(), "Tahoma");
+
+    // The key itself keeps its values and metadata but can't be added or deleted below itself.
+    let fonts = sample().subtree("Software\\Wine\\Fonts\\Replacements");
+    assert!(fonts.changes.iter().all(|c| matches!(c, RegistryChange::ValueAdded(k, _, _) | RegistryChange::KeyModified(k, _) if k.is_empty())));
+}
@@ -0,0 +1,76 @@
+#![cfg(unix)]
+
+use std::fs;
+use std::os::unix::fs::symlink;
+use std::path::PathBuf;
+
+use winereg::*;
+
+fn fake_prefix(name: &str) -> PathBuf {
+    let prefix = std::env::temp_dir().join(format!("winereg-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&prefix);
+    fs::create_dir_all(prefix.join("dosdevices")).unwrap();
+    fs::create_dir_all(prefix.join("drive_c/Program Files/MyApp")).unwrap();
+    fs::write(prefix.join("drive_c/Program Files/MyApp/app.exe"), b"MZ").unwrap();
+    symlink("../drive_c", prefix.join("dosdevices/c:")).unwrap();
+    symlink("/", prefix.join("dosdevices/z:")).unwrap();
+    symlink("/dev/sr0", prefix.join("dosdevices/d::")).unwrap();
+    prefix
+}
+
+#[test]
+fn maps_paths_through_dosdevices_links() {
+    let prefix = fake_prefix("map");
+    let devices = DosDevices::load(&prefix).unwrap();
+    assert_eq!(devices.drives().map(|(letter, _)| letter).collect::<Vec<_>>(), vec!['c', 'z']);
+
+    let exe = prefix.join("drive_c/Program Files/MyApp/app.exe");
+    assert_eq!(devices.to_unix("C:\\Program Files\\MyApp\\app.exe").unwrap(), exe);
+    assert_eq!(devices.to_unix("\\??\\c:/Program Files/./Other/../MyApp/app.exe").unwrap(), exe);
+    assert_eq!(devices.to_unix("Z:\\tmp").unwrap(), PathBuf::from("/tmp"));
+    assert_eq!(devices.to_windows(&exe).unwrap(), "C:\\Program Files\\MyApp\\app.exe");
+    assert_eq!(devices.to_windows("/usr/bin").unwrap(), "Z:\\usr\\bin");
+
+    assert_eq!(devices.find_existing("c:\\PROGRAM FILES\\myapp\\APP.EXE"), Some(exe));
+    assert_eq!(devices.find_existing("C:\\Program Files\\MyApp\\missing.exe"), None);
+    assert_eq!(devices.to_unix("E:\\x"), Err(DosPathError::UnknownDrive('E')));
+    assert!(matches!(devices.to_unix("relative\\path"), Err(DosPathError::NotDrivePath(_))));
+
+    fs::remove_dir_all(&prefix).unwrap();
+}
+
+#[test]
+fn relocates_host_paths_in_values() {
+    let mut devices = DosDevices::new();
+    devices.insert('C', "/home/new/.wine/drive_c");
+    devices.insert('Z', "/");
+
+    let root = RegistryKey::create_root();
+    let key = RegistryKey::create_key_recursive(&root, "Software\\MyApp");
+    let set = |name: &str, data: RegistryValueData| key.borrow_mut().set_value(name, RegistryValue::new(name, data));
+    set("Data", RegistryValueData::String("Z:\\home\\old\\.wine\\drive_c\\data".into()));
+    set("Tools", RegistryValueData::String("Z:\\home\\old\\tools".into()));
+    set("Install", RegistryValueData::String("C:\\Program Files\\MyApp".into()));
+    set("Command", RegistryValueData::String("\"Z:\\home\\old\\tools\\run.exe\" %1".into()));
+    // Missing its terminator, so kept as `RawString`.
+    let raw = "Z:\\home\\old\\.wine\\drive_c\\raw".encode_utf16().flat_map(|u| u.to_le_bytes()).collect();
+    set("Raw", RegistryValueData::RawString(raw, REG_SZ));
+    // A lone surrogate in one entry: that entry and the odd trailing byte are kept as they are.
+    let mut list: Vec<u8> = "Z:\\home\\old\\.wine\\drive_c\\a".encode_utf16().flat_map(|u| u.to_le_bytes()).collect();
+    let bad = [0x00, 0x00, 0x5a, 0x00, 0x3a, 0x00, 0x00, 0xd8, 0x00, 0x00, 0x00, 0x00, 0x07];
+    list.extend_from_slice(&bad);
+    set("List", RegistryValueData::RawString(list, REG_MULTI_SZ));
+
+    assert_eq!(RegistryKey::relocate_paths(&root, &devices, "/home/old/.wine", "/home/new/.wine"), 3);
+    assert_eq!(RegistryKey::relocate_paths(&root, &devices, "/home/old", "/home/new"), 1);
+    let guard = key.borrow();
+    assert_eq!(guard.get_string("Data").unwrap(), "C:\\data");
+    let raw = "C:\\raw".encode_utf16().flat_map(|u| u.to_le_bytes()).collect();
+    assert_eq!(guard.get_value("Raw").unwrap().data, RegistryValueData::RawString(raw, REG_SZ));
+    let mut list: Vec<u8> = "C:\\a".encode_utf16().flat_map(|u| u.to_le_bytes()).collect();
+    list.extend_from_slice(&bad);
+    assert_eq!(guard.get_value("List").unwrap().data, RegistryValueData::RawString(list, REG_MULTI_SZ));
+    assert_eq!(guard.get_string("Tools").unwrap(), "Z:\\home\\new\\tools");
+    assert_eq!(guard.get_string("Install").unwrap(), "C:\\Program Files\\MyApp");
+    assert_eq!(guard.get_string("Command").unwrap(), "\"Z:\\home\\old\\tools\\run.exe\" %1");
+}
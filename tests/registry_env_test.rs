@@ -0,0 +1,49 @@
+use winereg::*;
+
+fn resource_path(name: &str) -> String {
+    format!("{}/tests/resources/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+#[test]
+fn expands_from_hives_defaults_and_supplied_variables() {
+    let system = RegistryParser.load_from_file(resource_path("system.reg")).expect("parse system.reg");
+    let user = RegistryParser.load_from_file(resource_path("user.reg")).expect("parse user.reg");
+
+    let mut env = Environment::wine_defaults("me", Architecture::Win64);
+    assert!(env.extend_from_system_hive(&system));
+    assert!(env.extend_from_user_hive(&user.root_key));
+    assert!(!env.extend_from_user_hive(&system.root_key));
+
+    let expanded = env.expand("%ComSpec% /c %ProgramFiles(x86)%");
+    assert_eq!(expanded.text, "C:\\windows\\system32\\cmd.exe /c C:\\Program Files (x86)");
+    assert!(expanded.is_complete());
+    assert_eq!(env.expand("%temp%").text, "C:\\users\\me\\AppData\\Local\\Temp");
+    assert!(env.expand("%PATH%").text.starts_with("C:\\windows\\system32;C:\\windows;"));
+
+    env.set("USERPROFILE", "D:\\profiles\\me");
+    assert_eq!(env.expand("%UserProfile%\\Desktop").text, "D:\\profiles\\me\\Desktop");
+
+    let key = RegistryKey::find_key(&system.root_key, SYSTEM_ENVIRONMENT_PATH).unwrap();
+    let windir = key.borrow().get_expanded("windir", &env).unwrap();
+    assert_eq!(windir.text, "C:\\windows");
+    let os = key.borrow().get_expanded("OS", &Environment::new()).unwrap();
+    assert_eq!(os.text, "Windows_NT");
+}
+
+#[test]
+fn reports_unresolved_and_recursive_variables() {
+    let mut env = Environment::new();
+    env.set("Loop", "%LOOP%x");
+    env.set("App", "MyApp");
+
+    let expanded = env.expand("%Missing%\\%App%\\%loop%\\100%");
+    assert_eq!(expanded.text, "%Missing%\\MyApp\\%LOOP%x\\100%");
+    assert_eq!(expanded.unresolved, vec!["Missing".to_string(), "LOOP".to_string()]);
+
+    let value = RegistryValue::new("Path", RegistryValueData::ExpandString("%App%".into()));
+    assert_eq!(value.expand(&env).unwrap().text, "MyApp");
+    let plain = RegistryValue::new("Path", RegistryValueData::String("%App%".into()));
+    assert_eq!(plain.expand(&env).unwrap().text, "%App%");
+    let dword = RegistryValue::new("Count", RegistryValueData::Dword(1));
+    assert!(matches!(dword.expand(&env), Err(ValueError::WrongType { .. })));
+}
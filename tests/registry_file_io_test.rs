@@ -99,6 +99,43 @@ fn parser_handles_minimal_valid_text() {
     );
 }
 
+/// Accepts `limit` bytes, then fails like a closed pipe.
+struct BrokenPipe {
+    written: Vec<u8>,
+    limit: usize,
+}
+
+impl std::io::Write for BrokenPipe {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.written.len() + buf.len() > self.limit {
+            return Err(std::io::Error::new(std::io::ErrorKind::BrokenPipe, "closed"));
+        }
+        self.written.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn streaming_writer_matches_string_output_and_reports_errors() {
+    let loaded = RegistryParser.load_from_file(resource_path("system.reg")).expect("parse system.reg");
+    let writer = RegistryWriter {
+        relative_base: loaded.relative_base.clone(),
+        architecture: loaded.architecture,
+    };
+    let mut streamed = Vec::new();
+    writer.write_to(&loaded.root_key, &mut streamed).expect("stream");
+    assert_eq!(String::from_utf8(streamed).unwrap(), writer.write_to_string(&loaded.root_key));
+
+    let mut pipe = BrokenPipe { written: Vec::new(), limit: 100_000 };
+    let err = writer.write_to(&loaded.root_key, &mut pipe).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::BrokenPipe);
+    assert!(!pipe.written.is_empty());
+}
+
 fn count_keys(node: &KeyNode) -> usize {
     let mut total = 1;
     for child in node.borrow().subkeys().values() {
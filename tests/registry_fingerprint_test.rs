@@ -0,0 +1,60 @@
+use winereg::*;
+
+const TEXT: &str = "WINE REGISTRY Version 2\n\
+                    \n[Software\\\\App] 1700000000\n#time=1d9f0a0b0c0d0e0\n#class=\"c\"\n\"Name\"=\"app\"\n\"List\"=str(7):\"a\\0b\\0\"\n\
+                    \n[Software\\\\App\\\\Deep\\\\Deeper] 1700000000\n#time=1d9f0a0b0c0d0e0\n\"Data\"=hex:01,02,03,04,05,06,07,08,09\n\
+                    \n[Software\\\\Copy\\\\Deeper] 1700000000\n#time=1d9f0a0b0c0d0e0\n\"Data\"=hex:01,02,03,04,05,06,07,08,09\n";
+
+fn load() -> KeyNode {
+    RegistryParser.load_from_text(TEXT).expect("parse").root_key
+}
+
+#[test]
+fn fingerprints_follow_content_and_times() {
+    let (a, b) = (load(), load());
+    assert_eq!(Fingerprint::of(&a), Fingerprint::of(&b));
+    // The subtree's own name doesn't count.
+    let deep = RegistryKey::find_key(&a, "Software\\App\\Deep").unwrap();
+    let copy = RegistryKey::find_key(&a, "Software\\Copy").unwrap();
+    assert_eq!(Fingerprint::of(&deep).content, Fingerprint::of(&copy).content);
+
+    let deeper = RegistryKey::find_key(&b, "Software\\App\\Deep\\Deeper").unwrap();
+    deeper.borrow_mut().modification_time += 1;
+    let (fa, fb) = (Fingerprint::of(&a), Fingerprint::of(&b));
+    assert_eq!(fa.content, fb.content);
+    assert_ne!(fa.with_times, fb.with_times);
+
+    deeper.borrow_mut().set_value("Data", RegistryValue::new("Data", RegistryValueData::Binary(vec![1, 2, 3, 4, 5, 6, 7, 8, 10], REG_BINARY)));
+    assert_ne!(Fingerprint::of(&a).content, Fingerprint::of(&b).content);
+    let class = RegistryKey::find_key(&a, "Software\\App").unwrap();
+    class.borrow_mut().class_name = None;
+    assert_ne!(Fingerprint::of(&a).content, fa.content);
+}
+
+#[test]
+fn cache_is_reused_and_invalidated() {
+    let (a, b) = (load(), load());
+    let mut cache = FingerprintCache::new();
+    assert!(!RegistryComparator.compare_registries_cached(&a, &b, &CompareOptions::default(), &mut cache).has_changes());
+    assert_eq!(cache.len(), 2 * 7);
+    assert_eq!(cache.get(&a), Some(Fingerprint::of(&a)));
+
+    let deeper = RegistryKey::find_key(&b, "Software\\App\\Deep\\Deeper").unwrap();
+    deeper.borrow_mut().set_value("Extra", RegistryValue::new("Extra", RegistryValueData::Dword(1)));
+    deeper.borrow_mut().modification_time += 1;
+    // Stale entries hide the change until the key is invalidated.
+    assert!(!RegistryComparator.compare_registries_cached(&a, &b, &CompareOptions::default(), &mut cache).has_changes());
+    cache.invalidate(&deeper);
+    assert!(cache.get(&b).is_none());
+    assert!(cache.get(&a).is_some());
+    let diff = RegistryComparator.compare_registries_cached(&a, &b, &CompareOptions::default(), &mut cache);
+    assert!(matches!(&diff.changes[..], [RegistryChange::ValueAdded(k, n, _)] if k == "SOFTWARE\\APP\\DEEP\\DEEPER" && n == "Extra"));
+
+    // Time-only differences are not skipped when they are reported.
+    deeper.borrow_mut().delete_value("Extra");
+    deeper.borrow_mut().modification_time += 1;
+    let options = CompareOptions { report_timestamps: true, ..CompareOptions::default() };
+    let diff = RegistryComparator.compare_registries_with_options(&a, &b, &options);
+    assert!(matches!(&diff.changes[..], [RegistryChange::KeyModified(_, props)] if matches!(props[..], [KeyPropertyChange::TimeChange(..)])));
+    assert!(!RegistryComparator.compare_registries(&a, &b).has_changes());
+}
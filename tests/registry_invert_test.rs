@@ -0,0 +1,58 @@
+use winereg::*;
+
+fn load(body: &str) -> KeyNode {
+    RegistryParser.load_from_text(&format!("WINE REGISTRY Version 2\n{}", body)).expect("parse").root_key
+}
+
+const OLD: &str = "\n[Software\\\\App 1.0] 1700000000\n#time=1d9f0a0b0c0d0e0\n#class=\"old\"\n\"Path\"=\"C:\\\\App\"\n\"Lang\"=\"en\"\n\"Mode\"=dword:00000001\n\
+                   \n[Software\\\\App 1.0\\\\Plugins] 1700000000\n#time=1d9f0a0b0c0d0e0\n\"Spell\"=dword:00000001\n\
+                   \n[Software\\\\Removed] 1700000000\n#time=1d9f0a0b0c0d0e0\n#class=\"gone\"\n\"x\"=\"1\"\n\
+                   \n[Software\\\\Removed\\\\Child] 1700000000\n#time=1d9f0a0b0c0d0e0\n\"y\"=hex:01,02\n";
+const NEW: &str = "\n[Software\\\\App 2.0] 1700000000\n#time=1d9f0a0b0c0d0e0\n#class=\"new\"\n\"Path\"=\"C:\\\\App\"\n\"Lang\"=\"en\"\n\"Mode\"=dword:00000002\n\"Extra\"=\"1\"\n\
+                   \n[Software\\\\App 2.0\\\\Plugins] 1700000000\n#time=1d9f0a0b0c0d0e0\n\"Spell\"=dword:00000001\n\
+                   \n[Software\\\\Added] 1700000000\n#time=1d9f0a0b0c0d0e0\n\"z\"=\"3\"\n";
+
+#[test]
+fn inverted_diff_rolls_back_every_change() {
+    let (old, new) = (load(OLD), load(NEW));
+    for options in [
+        CompareOptions::default(),
+        CompareOptions { compact_subtrees: true, ..CompareOptions::default() },
+        CompareOptions { compact_subtrees: true, move_threshold: Some(0.5), ..CompareOptions::default() },
+    ] {
+        let diff = RegistryComparator.compare_registries_with_options(&old, &new, &options);
+        let target = load(NEW);
+        // Flat deletions list every key, so keys must not vanish with their last value.
+        let patch = PatchOptions { delete_empty_keys: false, ..PatchOptions::default() };
+        let result = RegistryPatcher.apply_patch(&target, &diff.invert(), patch);
+        assert!(result.is_success(), "{:?}", result.failed);
+        let left = RegistryComparator.compare_registries_with_options(&target, &old, &options);
+        assert!(!left.has_changes(), "{:?}", left);
+        // Flat and compact deletions both carry the metadata the recreated keys need.
+        let removed = RegistryKey::find_key(&target, "Software\\Removed").unwrap();
+        assert_eq!(removed.borrow().class_name.as_deref(), Some("gone"));
+        assert_eq!(removed.borrow().modification_time, 0x1d9f0a0b0c0d0e0);
+        // Inverting twice gives the original changes.
+        let twice = diff.invert().invert();
+        assert_eq!(format!("{:?}", twice.changes), format!("{:?}", diff.changes));
+    }
+}
+
+#[test]
+fn inverse_of_a_rename_restores_the_old_name() {
+    let (old, new) = (load(OLD), load(NEW));
+    let options = CompareOptions { move_threshold: Some(0.5), ..CompareOptions::default() };
+    let inverse = RegistryComparator.compare_registries_with_options(&old, &new, &options).invert();
+    assert!(inverse.changes.iter().any(|c| matches!(c, RegistryChange::KeyRenamed(from, to) if from == "SOFTWARE\\App 2.0" && to == "SOFTWARE\\App 1.0")));
+    // Changes below the renamed key refer to its old name.
+    assert!(inverse.changes.iter().any(|c| matches!(c, RegistryChange::ValueDeleted(k, n, _) if k == "SOFTWARE\\App 1.0" && n == "Extra")));
+    assert!(matches!(
+        inverse.changes.iter().find(|c| matches!(c, RegistryChange::KeyModified(p, _) if p == "SOFTWARE\\App 1.0")),
+        Some(RegistryChange::KeyModified(_, props)) if matches!(&props[0], KeyPropertyChange::ClassNameChange(Some(a), Some(b)) if a == "new" && b == "old")
+    ));
+
+    let target = load(NEW);
+    let patch = PatchOptions { delete_empty_keys: false, ..PatchOptions::default() };
+    assert!(RegistryPatcher.apply_patch(&target, &inverse, patch).is_success());
+    assert_eq!(RegistryKey::find_key(&target, "Software\\App 1.0").unwrap().borrow().name, "App 1.0");
+}
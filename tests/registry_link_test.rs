@@ -0,0 +1,109 @@
+use winereg::*;
+
+fn resource_path(name: &str) -> String {
+    format!("{}/tests/resources/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+fn machine_options() -> LinkOptions {
+    LinkOptions {
+        relative_base: "REGISTRY\\\\Machine".into(),
+        ..LinkOptions::default()
+    }
+}
+
+#[test]
+fn follows_chained_links_in_real_hive() {
+    let loaded = RegistryParser.load_from_file(resource_path("system.reg")).expect("parse system.reg");
+    let options = LinkOptions {
+        relative_base: loaded.relative_base.clone(),
+        ..LinkOptions::default()
+    };
+    let root = &loaded.root_key;
+    assert!(RegistryKey::find_key(root, "Software\\Wow6432Node\\Classes\\AppId").is_none());
+
+    let resolved = RegistryKey::find_key_following_links(root, "Software\\Wow6432Node\\Classes\\AppId", &options)
+        .expect("resolve through two links");
+    let direct = RegistryKey::find_key(root, "Software\\Classes\\AppId").unwrap();
+    assert!(std::rc::Rc::ptr_eq(&resolved, &direct));
+
+    let link = RegistryKey::find_key_following_links(
+        root,
+        "Software\\Wow6432Node\\Classes",
+        &LinkOptions { open_link: true, ..options.clone() },
+    )
+    .unwrap();
+    assert!(link.borrow().is_symlink);
+    assert_eq!(
+        RegistryKey::link_target(&link).as_deref(),
+        Some("\\Registry\\Machine\\Software\\Classes\\Wow6432Node")
+    );
+}
+
+#[test]
+fn created_links_resolve_and_round_trip() {
+    let root = RegistryKey::create_root();
+    let options = machine_options();
+    let target = RegistryKey::create_key_recursive(&root, "System\\ControlSet001\\Control");
+    RegistryKey::create_link(&root, "System\\CurrentControlSet", "System\\ControlSet001", &options);
+
+    let resolved = RegistryKey::find_key_following_links(&root, "System\\CurrentControlSet\\Control", &options).unwrap();
+    assert!(std::rc::Rc::ptr_eq(&resolved, &target));
+
+    let writer = RegistryWriter { relative_base: options.relative_base.clone(), architecture: Architecture::Win64 };
+    let reparsed = RegistryParser.load_from_text(&writer.write_to_string(&root)).expect("reparse");
+    let link = RegistryKey::find_key(&reparsed.root_key, "System\\CurrentControlSet").unwrap();
+    assert!(link.borrow().is_symlink);
+    assert_eq!(
+        RegistryKey::link_target(&link).as_deref(),
+        Some("\\REGISTRY\\Machine\\System\\ControlSet001")
+    );
+}
+
+#[test]
+fn reports_loops_dangling_and_foreign_links() {
+    let root = RegistryKey::create_root();
+    let options = machine_options();
+    RegistryKey::create_link(&root, "Loop\\A", "Loop\\B", &options);
+    RegistryKey::create_link(&root, "Loop\\B", "Loop\\A", &options);
+    RegistryKey::create_link(&root, "Broken", "Does\\Not\\Exist", &options);
+    let foreign = RegistryKey::create_link(&root, "Foreign", "Anything", &options);
+    {
+        let bytes = "\\Registry\\User\\.Default".encode_utf16().flat_map(|c| c.to_le_bytes()).collect();
+        foreign.borrow_mut().set_value(
+            SYMBOLIC_LINK_VALUE,
+            RegistryValue::new(SYMBOLIC_LINK_VALUE, RegistryValueData::Binary(bytes, REG_LINK)),
+        );
+    }
+
+    assert!(matches!(
+        RegistryKey::find_key_following_links(&root, "Loop\\A\\Child", &options),
+        Err(LinkError::Loop { .. })
+    ));
+    // Passing through the same link twice is a chain, not a loop.
+    let software = RegistryKey::create_key_recursive(&root, "System\\ControlSet001\\Hardware Profiles\\0001\\Software");
+    RegistryKey::create_link(&root, "System\\CurrentControlSet", "System\\ControlSet001", &options);
+    RegistryKey::create_link(&root, "System\\ControlSet001\\Hardware Profiles\\Current", "System\\CurrentControlSet\\Hardware Profiles\\0001", &options);
+    let found = RegistryKey::find_key_following_links(&root, "System\\CurrentControlSet\\Hardware Profiles\\Current\\Software", &options);
+    assert!(found.is_ok_and(|key| std::rc::Rc::ptr_eq(&key, &software)));
+    assert!(matches!(
+        RegistryKey::find_key_following_links(&root, "Broken", &options),
+        Err(LinkError::Dangling { .. })
+    ));
+    assert!(matches!(
+        RegistryKey::find_key_following_links(&root, "Missing", &options),
+        Err(LinkError::NotFound(_))
+    ));
+    // A link to the hive base itself: later segments are the caller's, not the link's.
+    RegistryKey::create_link(&root, "Base", "", &options);
+    let found = RegistryKey::find_key_following_links(&root, "Base\\System\\ControlSet001", &options);
+    assert!(found.is_ok_and(|key| key.borrow().name == "ControlSet001"));
+    assert!(matches!(
+        RegistryKey::find_key_following_links(&root, "Base\\Missing", &options),
+        Err(LinkError::NotFound(_))
+    ));
+
+    let dangling = RegistryKey::find_dangling_links(&root, &options);
+    let paths: Vec<&str> = dangling.iter().map(|d| d.path.as_str()).collect();
+    assert_eq!(paths, vec!["Broken", "Foreign", "Loop\\A", "Loop\\B"]);
+    assert!(matches!(dangling[1].error, LinkError::OutsideHive { .. }));
+}
@@ -0,0 +1,76 @@
+use winereg::*;
+
+fn load(body: &str) -> KeyNode {
+    RegistryParser.load_from_text(&format!("WINE REGISTRY Version 2\n{}", body)).expect("parse").root_key
+}
+
+fn string(root: &KeyNode, path: &str, name: &str) -> Option<String> {
+    RegistryKey::find_key(root, path).and_then(|k| k.borrow().get_string(name).ok())
+}
+
+const BASE: &str = "\n[Software\\\\App] 1\n\"Theme\"=\"light\"\n\"Lang\"=\"en\"\n\"Size\"=dword:00000001\n\
+                    \n[Software\\\\Legacy] 1\n\"Old\"=\"1\"\n";
+
+#[test]
+fn independent_changes_merge_and_conflicts_are_reported() {
+    let base = load(BASE);
+    let ours = load(
+        "\n[Software\\\\App] 1\n\"Theme\"=\"dark\"\n\"Lang\"=\"en\"\n\"Size\"=dword:00000002\n\
+         \n[Software\\\\Legacy] 1\n\"Old\"=\"1\"\n\n[Software\\\\TeamA] 1\n\"x\"=\"a\"\n",
+    );
+    let theirs = load(
+        "\n[Software\\\\App] 1\n\"Theme\"=\"blue\"\n\"Lang\"=\"de\"\n\"Size\"=dword:00000002\n\
+         \n[Software\\\\Legacy] 1\n\"Old\"=\"1\"\n",
+    );
+
+    let result = RegistryMerger.merge(&base, &ours, &theirs, &MergeOptions::default());
+    assert_eq!(result.conflicts.len(), 1);
+    let conflict = &result.conflicts[0];
+    assert_eq!(conflict.path, "SOFTWARE\\APP");
+    assert!(matches!(&conflict.kind, ConflictKind::Value { name, base: Some(_), ours: Some(_), theirs: Some(_) } if name == "Theme"));
+    assert_eq!(result.unresolved().len(), 1);
+    assert!(!result.has_failures(), "{:?}", result.failed);
+
+    let merged = &result.tree;
+    assert_eq!(string(merged, "Software\\App", "Theme").as_deref(), Some("light"));
+    assert_eq!(string(merged, "Software\\App", "Lang").as_deref(), Some("de"));
+    assert_eq!(RegistryKey::find_key(merged, "Software\\App").unwrap().borrow().get_u32("Size").unwrap(), 2);
+    assert_eq!(RegistryKey::find_key(merged, "Software\\TeamA").unwrap().borrow().name, "TeamA");
+    // The inputs are left alone.
+    assert_eq!(string(&base, "Software\\App", "Lang").as_deref(), Some("en"));
+
+    let options = MergeOptions { strategy: MergeStrategy::Theirs, ..MergeOptions::default() };
+    let result = RegistryMerger.merge(&base, &ours, &theirs, &options);
+    assert!(result.unresolved().is_empty());
+    assert_eq!(string(&result.tree, "Software\\App", "Theme").as_deref(), Some("blue"));
+}
+
+#[test]
+fn delete_against_modify_and_class_names() {
+    let base = load(BASE);
+    let ours = load("\n[Software\\\\App] 1\n#class=\"ours\"\n\"Theme\"=\"light\"\n\"Lang\"=\"en\"\n\"Size\"=dword:00000001\n");
+    let theirs = load(
+        "\n[Software\\\\App] 1\n#class=\"theirs\"\n\"Theme\"=\"light\"\n\"Lang\"=\"en\"\n\"Size\"=dword:00000001\n\
+         \n[Software\\\\Legacy] 1\n\"Old\"=\"1\"\n\"New\"=\"2\"\n",
+    );
+
+    let mut seen = Vec::new();
+    let result = RegistryMerger.merge_with(&base, &ours, &theirs, &CompareOptions::default(), |conflict| {
+        seen.push(conflict.path.clone());
+        match conflict.kind {
+            ConflictKind::DeleteModify { deleted_by: MergeSide::Ours, .. } => MergeStrategy::Ours,
+            _ => MergeStrategy::Theirs,
+        }
+    });
+    assert_eq!(seen, ["SOFTWARE\\APP", "SOFTWARE\\LEGACY"]);
+    assert!(matches!(&result.conflicts[0].kind, ConflictKind::KeyProperty { ours: KeyPropertyChange::ClassNameChange(None, Some(_)), .. }));
+    assert!(matches!(&result.conflicts[1].kind, ConflictKind::DeleteModify { changes, .. } if changes.len() == 1));
+
+    let app = RegistryKey::find_key(&result.tree, "Software\\App").unwrap();
+    assert_eq!(app.borrow().class_name.as_deref(), Some("theirs"));
+    assert!(RegistryKey::find_key(&result.tree, "Software\\Legacy").is_none());
+
+    let kept = RegistryMerger.merge(&base, &ours, &theirs, &MergeOptions::default());
+    assert_eq!(string(&kept.tree, "Software\\Legacy", "Old").as_deref(), Some("1"));
+    assert_eq!(string(&kept.tree, "Software\\Legacy", "New"), None);
+}
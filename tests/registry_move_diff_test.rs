@@ -0,0 +1,88 @@
+use winereg::*;
+
+fn load(body: &str) -> KeyNode {
+    RegistryParser.load_from_text(&format!("WINE REGISTRY Version 2\n{}", body)).expect("parse").root_key
+}
+
+fn moves() -> CompareOptions {
+    CompareOptions { move_threshold: Some(0.5), ..CompareOptions::default() }
+}
+
+const OLD: &str = "\n[Software\\\\Vendor\\\\App 1.0] 1\n\"Path\"=\"C:\\\\App\"\n\"Version\"=\"1.0\"\n\"Lang\"=\"en\"\n\
+                   \n[Software\\\\Vendor\\\\App 1.0\\\\Plugins] 1\n\"Spell\"=dword:00000001\n\
+                   \n[Software\\\\Old\\\\Tools] 1\n\"Editor\"=\"vi\"\n\"Pager\"=\"less\"\n\
+                   \n[Software\\\\Old] 1\n\"Keep\"=\"1\"\n\n[Software\\\\New] 1\n\"Keep\"=\"1\"\n\
+                   \n[Software\\\\Gone] 1\n\"x\"=\"1\"\n";
+const NEW: &str = "\n[Software\\\\Vendor\\\\App 2.0] 1\n\"Path\"=\"C:\\\\App\"\n\"Version\"=\"2.0\"\n\"Lang\"=\"en\"\n\
+                   \n[Software\\\\Vendor\\\\App 2.0\\\\Plugins] 1\n\"Spell\"=dword:00000001\n\
+                   \n[Software\\\\New\\\\Tools] 1\n\"Editor\"=\"vi\"\n\"Pager\"=\"less\"\n\
+                   \n[Software\\\\Old] 1\n\"Keep\"=\"1\"\n\n[Software\\\\New] 1\n\"Keep\"=\"1\"\n\
+                   \n[Software\\\\Fresh] 1\n\"y\"=\"2\"\n";
+
+#[test]
+fn similar_subtrees_are_reported_as_moves_and_renames() {
+    let (old, new) = (load(OLD), load(NEW));
+    let plain = RegistryComparator.compare_registries(&old, &new);
+    assert!(!plain.changes.iter().any(|c| matches!(c, RegistryChange::KeyMoved(..) | RegistryChange::KeyRenamed(..))));
+
+    let diff = RegistryComparator.compare_registries_with_options(&old, &new, &moves());
+    let renamed = diff.changes.iter().position(|c| matches!(c, RegistryChange::KeyRenamed(from, to)
+        if from == "SOFTWARE\\VENDOR\\App 1.0" && to == "SOFTWARE\\VENDOR\\App 2.0"));
+    let renamed = renamed.expect("rename reported");
+    assert!(matches!(&diff.changes[renamed + 1], RegistryChange::ValueModified(k, n, _, _)
+        if k == "SOFTWARE\\VENDOR\\App 2.0" && n == "Version"));
+    assert!(diff.changes.iter().any(|c| matches!(c, RegistryChange::KeyMoved(from, to)
+        if from == "SOFTWARE\\OLD\\Tools" && to == "SOFTWARE\\NEW\\Tools")));
+    // Nothing in common: still a deletion and an addition.
+    assert!(diff.changes.iter().any(|c| matches!(c, RegistryChange::KeyDeleted(p) if p == "SOFTWARE\\GONE")));
+    assert!(diff.changes.iter().any(|c| matches!(c, RegistryChange::KeyAdded(p) if p == "SOFTWARE\\FRESH")));
+
+    let strict = CompareOptions { move_threshold: Some(0.9), ..CompareOptions::default() };
+    let diff = RegistryComparator.compare_registries_with_options(&old, &new, &strict);
+    assert!(!diff.changes.iter().any(|c| matches!(c, RegistryChange::KeyRenamed(..))));
+    assert!(diff.changes.iter().any(|c| matches!(c, RegistryChange::KeyMoved(..))));
+
+    // Out-of-range thresholds are clamped and NaN turns pairing off.
+    let compare = |threshold| {
+        let options = CompareOptions { move_threshold: Some(threshold), ..CompareOptions::default() };
+        format!("{:?}", RegistryComparator.compare_registries_with_options(&old, &new, &options).changes)
+    };
+    assert_eq!(compare(2.0), compare(1.0));
+    assert_eq!(compare(f64::NAN), format!("{:?}", plain.changes));
+}
+
+#[test]
+fn names_with_arrows_survive_the_text_format() {
+    let old = load("\n[Apps\\\\a->b] 1\n\"Id\"=\"1\"\n\"Name\"=\"x\"\n\n[Apps\\\\\"x\"->y] 1\n\"Id\"=\"2\"\n\n[Other] 1\n");
+    let new = load("\n[Apps\\\\b->c] 1\n\"Id\"=\"1\"\n\"Name\"=\"x\"\n\n[Other\\\\\"x\"->y] 1\n\"Id\"=\"2\"\n");
+    let diff = RegistryComparator.compare_registries_with_options(&old, &new, &moves());
+    let text = TextDiffExporter.export(&diff, None, None);
+    assert!(text.contains("[APPS]\n~key:\"a->b\"->\"b->c\"\n"), "{}", text);
+    assert!(text.contains("[OTHER]\n~move:\"APPS\\\\\\\"x\\\"->y\"->\"\\\"x\\\"->y\"\n"), "{}", text);
+
+    let parsed = TextDiffParser.parse(&text).expect("parse diff");
+    assert!(parsed.changes.iter().any(|c| matches!(c, RegistryChange::KeyRenamed(from, to)
+        if from == "APPS\\a->b" && to == "APPS\\b->c")));
+    assert!(parsed.changes.iter().any(|c| matches!(c, RegistryChange::KeyMoved(from, to)
+        if from == "APPS\\\"x\"->y" && to == "OTHER\\\"x\"->y")));
+    assert!(TextDiffParser.parse("[APPS]\n~key:a->b\n").is_err());
+}
+
+#[test]
+fn moves_round_trip_through_text_and_patch() {
+    let (old, new) = (load(OLD), load(NEW));
+    let options = CompareOptions { compact_subtrees: true, ..moves() };
+    let diff = RegistryComparator.compare_registries_with_options(&old, &new, &options);
+    let text = TextDiffExporter.export(&diff, None, None);
+    assert!(text.contains("[SOFTWARE\\VENDOR]\n~key:\"App 1.0\"->\"App 2.0\"\n"));
+    assert!(text.contains("[SOFTWARE\\NEW]\n~move:\"SOFTWARE\\\\OLD\\\\Tools\"->\"Tools\"\n"));
+
+    let parsed = TextDiffParser.parse(&text).expect("parse diff");
+    let target = load(OLD);
+    let result = RegistryPatcher.apply_patch(&target, &parsed, PatchOptions::default());
+    assert!(result.is_success(), "{:?}", result.failed);
+    assert!(!RegistryComparator.compare_registries(&target, &new).has_changes());
+    let app = RegistryKey::find_key(&target, "Software\\Vendor\\App 2.0").unwrap();
+    assert_eq!(app.borrow().name, "App 2.0");
+    assert!(RegistryKey::find_key(&target, "Software\\Old\\Tools").is_none());
+}
@@ -0,0 +1,74 @@
+use winereg::*;
+
+/// Trees with many top-level keys, some equal, some changed, moved, added or deleted.
+fn trees() -> (KeyNode, KeyNode) {
+    let (left, right) = (RegistryKey::create_root(), RegistryKey::create_root());
+    for tree in [&left, &right] {
+        tree.borrow_mut().set_value("Root", RegistryValue::new("Root", RegistryValueData::Dword(1)));
+    }
+    right.borrow_mut().set_value("Root", RegistryValue::new("Root", RegistryValueData::Dword(2)));
+    for i in 0..24 {
+        for (tree, side) in [(&left, 0), (&right, 1)] {
+            let app = RegistryKey::create_key_recursive(tree, &format!("Top{:02}\\App", i));
+            app.borrow_mut().set_value("Same", RegistryValue::new("Same", RegistryValueData::String(format!("s{}", i))));
+            app.borrow_mut().set_value("Keep", RegistryValue::new("Keep", RegistryValueData::Dword(i)));
+            if i % 3 != 0 {
+                app.borrow_mut().set_value("Version", RegistryValue::new("Version", RegistryValueData::Dword(side)));
+                app.borrow_mut().modification_time = 1_700_000_000 + side as u64;
+            }
+            // A key renamed below each changed top-level key and one moved between two of them.
+            let (old, new) = (format!("Top{:02}\\Old", i), format!("Top{:02}\\New", i));
+            let leaf = RegistryKey::create_key_recursive(tree, if side == 0 || i % 3 == 0 { &old } else { &new });
+            leaf.borrow_mut().set_value("Id", RegistryValue::new("Id", RegistryValueData::Dword(i)));
+            leaf.borrow_mut().set_value("Name", RegistryValue::new("Name", RegistryValueData::String("leaf".into())));
+            // Identical on both sides below a key that differs.
+            let shared = RegistryKey::create_key_recursive(tree, &format!("Top{:02}\\Shared\\Deep", i));
+            shared.borrow_mut().set_value("Same", RegistryValue::new("Same", RegistryValueData::Dword(i)));
+        }
+        if i % 4 == 1 {
+            RegistryKey::create_key_recursive(&right, &format!("Added{:02}\\Sub", i));
+        } else if i % 4 == 2 {
+            let gone = RegistryKey::create_key_recursive(&left, &format!("Deleted{:02}\\Sub", i));
+            gone.borrow_mut().set_value("x", RegistryValue::new("x", RegistryValueData::Dword(i)));
+        }
+    }
+    let moved = RegistryKey::create_key_recursive(&left, "Top01\\Moving");
+    moved.borrow_mut().set_value("Payload", RegistryValue::new("Payload", RegistryValueData::String("p".into())));
+    let moved = RegistryKey::create_key_recursive(&right, "Top05\\Moving");
+    moved.borrow_mut().set_value("Payload", RegistryValue::new("Payload", RegistryValueData::String("p".into())));
+    (left, right)
+}
+
+#[test]
+fn parallel_comparison_matches_sequential() {
+    let (left, right) = trees();
+    let option_sets = [
+        CompareOptions::default(),
+        CompareOptions { compact_subtrees: true, ..CompareOptions::default() },
+        CompareOptions { move_threshold: Some(0.5), ..CompareOptions::default() },
+        CompareOptions { move_threshold: Some(0.5), compact_subtrees: true, ..CompareOptions::default() },
+        CompareOptions { report_timestamps: true, ..CompareOptions::default() },
+        CompareOptions { ignore_keys: vec!["Top0*".into(), "*\\Old".into()], ignore_values: vec!["Vers*".into()], ..CompareOptions::default() },
+    ];
+    for options in option_sets {
+        let sequential = RegistryComparator.compare_registries_with_options(&left, &right, &options);
+        assert!(sequential.has_changes());
+        for threads in [2, 3, 8, 64] {
+            let parallel = RegistryComparator.compare_registries_with_options(&left, &right, &CompareOptions { threads, ..options.clone() });
+            assert_eq!(format!("{:?}", parallel), format!("{:?}", sequential), "threads = {}", threads);
+        }
+    }
+}
+
+#[test]
+fn parallel_comparison_of_equal_trees_is_empty() {
+    let (left, _) = trees();
+    let (copy, _) = trees();
+    let options = CompareOptions { threads: 4, ..CompareOptions::default() };
+    assert!(!RegistryComparator.compare_registries_with_options(&left, &copy, &options).has_changes());
+
+    let moved = CompareOptions { move_threshold: Some(0.5), ..options };
+    let diff = RegistryComparator.compare_registries_with_options(&left, &trees().1, &moved);
+    assert!(diff.changes.iter().any(|c| matches!(c, RegistryChange::KeyMoved(from, _) if from.starts_with("TOP01\\"))));
+    assert!(diff.changes.iter().any(|c| matches!(c, RegistryChange::KeyRenamed(from, to) if from == "TOP02\\Old" && to == "TOP02\\New")));
+}
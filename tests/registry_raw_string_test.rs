@@ -0,0 +1,68 @@
+use winereg::*;
+
+fn load(body: &str) -> LoadResult {
+    let text = format!("WINE REGISTRY Version 2\n\n[Strings] 1700000000\n#time=1d9f0a0b0c0d0e0\n{}", body);
+    RegistryParser.load_from_text(&text).expect("parse")
+}
+
+fn value(loaded: &LoadResult, name: &str) -> RegistryValue {
+    let key = RegistryKey::find_key(&loaded.root_key, "Strings").unwrap();
+    let guard = key.borrow();
+    guard.get_value(name).unwrap().clone()
+}
+
+#[test]
+fn malformed_hex_strings_keep_their_bytes() {
+    let body = "\"Odd\"=hex(1):41,00,42\n\
+                \"NoNul\"=hex(2):41,00,42,00\n\
+                \"Garbage\"=hex(1):41,00,00,00,ff,ff\n\
+                \"Multi\"=hex(7):61,00,00,00,62,00\n";
+    let loaded = load(body);
+
+    let odd = value(&loaded, "Odd");
+    assert_eq!(odd.data, RegistryValueData::RawString(vec![0x41, 0, 0x42], REG_SZ));
+    assert_eq!(odd.as_string().unwrap(), "A");
+    assert_eq!(value(&loaded, "NoNul").lossy_string().as_deref(), Some("AB"));
+    assert_eq!(value(&loaded, "Garbage").as_string().unwrap(), "A");
+    assert_eq!(value(&loaded, "Garbage").raw_bytes(), vec![0x41, 0, 0, 0, 0xff, 0xff]);
+    assert_eq!(value(&loaded, "Multi").as_multi().unwrap(), vec!["a", "b"]);
+
+    let written = RegistryWriter::new().write_to_string(&loaded.root_key);
+    for line in body.lines() {
+        assert!(written.contains(line), "missing {line} in {written}");
+    }
+}
+
+#[test]
+fn lone_surrogates_round_trip_through_escapes() {
+    let loaded = load("\"Lone\"=\"a\\xd800b\"\n\"Multi\"=str(7):\"x\\xdc00\\0\"\n");
+    let lone = value(&loaded, "Lone");
+    assert_eq!(lone.raw_bytes(), vec![0x61, 0, 0x00, 0xd8, 0x62, 0, 0, 0]);
+    assert_eq!(lone.as_string().unwrap(), "a\u{fffd}b");
+    assert!(matches!(value(&loaded, "Multi").data, RegistryValueData::RawString(_, REG_MULTI_SZ)));
+
+    let written = RegistryWriter::new().write_to_string(&loaded.root_key);
+    assert!(written.contains("\"Lone\"=\"a\\xd800b\"\n"));
+    assert!(written.contains("\"Multi\"=str(7):\"x\\xdc00\\0\"\n"));
+
+    let reparsed = RegistryParser.load_from_text(&written).unwrap();
+    assert!(RegistryComparator.compare_registries(&loaded.root_key, &reparsed.root_key).changes.is_empty());
+}
+
+#[test]
+fn wine_escapes_are_decoded_and_written_back() {
+    let body = "\"Currency\"=\"\\xa5\"\n\
+                \"Date\"=\"yyyy\\x5e74M\\x6708d\\x65e5\"\n\
+                \"Digits\"=str(7):\"a\\0001\\0\"\n\
+                \"Bell\"=\"\\a\\1\"\n";
+    let loaded = load(body);
+    assert_eq!(value(&loaded, "Currency").as_string().unwrap(), "¥");
+    assert_eq!(value(&loaded, "Date").as_string().unwrap(), "yyyy年M月d日");
+    assert_eq!(value(&loaded, "Digits").as_multi().unwrap(), vec!["a", "1"]);
+    assert_eq!(value(&loaded, "Bell").as_string().unwrap(), "\u{7}\u{1}");
+
+    let written = RegistryWriter::new().write_to_string(&loaded.root_key);
+    for line in body.lines() {
+        assert!(written.contains(line), "missing {line} in {written}");
+    }
+}
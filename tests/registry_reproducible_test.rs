@@ -0,0 +1,54 @@
+use winereg::*;
+
+fn build(timestamp: Option<u64>) -> RegistryResult {
+    registry(|ctx| {
+        ctx.reproducible(timestamp);
+        ctx.key("Software\\App", |k| {
+            k.value("Name", "app");
+            k.dword("Version", 3);
+        });
+    })
+}
+
+fn export(old: &RegistryResult, new: &RegistryResult, options: &TextDiffOptions) -> String {
+    let diff = RegistryComparator.compare_registries(&old.root_key, &new.root_key);
+    TextDiffExporter.export_with_options(&diff, Some("old.reg"), Some("new.reg"), options)
+}
+
+#[test]
+fn supplied_timestamp_gives_identical_bytes() {
+    let first = build(Some(1_700_000_000));
+    std::thread::sleep(std::time::Duration::from_millis(5));
+    let second = build(Some(1_700_000_000));
+    let text = first.write_to_string();
+    assert_eq!(text, second.write_to_string());
+    assert!(text.contains("[Software\\\\App] 1700000000\n#time=1da1747c66d0000\n"));
+
+    let empty = registry(|ctx| ctx.reproducible(Some(1_700_000_000)));
+    let options = TextDiffOptions::default().timestamp(1_700_000_000);
+    let diff = export(&empty, &first, &options);
+    assert!(diff.contains("# Generated: 2023-11-14 22:13:20\n"));
+    assert_eq!(diff, export(&empty, &second, &options));
+
+    // The writer can override the times of a tree built with the wall clock.
+    let loaded = RegistryParser.load_from_text(&text).unwrap();
+    RegistryKey::create_key_recursive(&loaded.root_key, "Software\\Other");
+    let writer = RegistryWriter::new().with_options(WriterOptions::reproducible(Some(1_600_000_000)));
+    let written = writer.write_to_string(&loaded.root_key);
+    assert_eq!(written.matches("] 1600000000\n").count(), 2);
+    assert!(!written.contains("1700000000"));
+}
+
+#[test]
+fn source_date_epoch_is_the_fallback() {
+    assert_eq!(source_date_epoch_from(Some("86400")), Some(86400));
+    assert_eq!(source_date_epoch_from(Some(" 86400\n")), Some(86400));
+    assert_eq!(source_date_epoch_from(Some("yesterday")), None);
+    assert_eq!(source_date_epoch_from(None), None);
+
+    // Whatever the environment holds only matters when no timestamp is given.
+    let fallback = source_date_epoch().unwrap_or(0);
+    assert_eq!(FixedClock::reproducible(None), FixedClock::from_unix(fallback));
+    assert_eq!(FixedClock::reproducible(Some(5)), FixedClock::from_unix(5));
+    assert!(build(None).write_to_string().contains(&format!("[Software\\\\App] {}\n", fallback)));
+}
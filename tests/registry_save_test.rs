@@ -0,0 +1,134 @@
+use std::fs;
+use std::path::PathBuf;
+
+use winereg::*;
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("winereg-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn tree(version: u32) -> KeyNode {
+    registry(|ctx| {
+        ctx.key("Software\\MyApp", |k| {
+            k.dword("Version", version as i32);
+        });
+    })
+    .root_key
+}
+
+#[test]
+fn save_rotates_backups_and_restores_them() {
+    let dir = temp_dir("save");
+    let path = dir.join("user.reg");
+    let writer = RegistryWriter::new();
+    let options = SaveOptions { backups: 2, ..SaveOptions::default() };
+    for version in 1..=4 {
+        writer.save(&tree(version), &path, &options).unwrap();
+    }
+
+    let version_of = |p: PathBuf| {
+        let loaded = RegistryParser.load_from_file(p).unwrap();
+        let key = RegistryKey::find_key(&loaded.root_key, "Software\\MyApp").unwrap();
+        key.borrow().get_u32("Version").unwrap()
+    };
+    assert_eq!(version_of(path.clone()), 4);
+    assert_eq!(version_of(backup_path(&path, 1)), 3);
+    assert_eq!(version_of(backup_path(&path, 2)), 2);
+    assert!(!backup_path(&path, 3).exists());
+    assert!(!dir.join("user.reg.tmp").exists());
+
+    restore_backup(&path, 2).unwrap();
+    assert_eq!(version_of(path.clone()), 2);
+    assert_eq!(version_of(backup_path(&path, 2)), 2);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn save_keeps_permissions_of_replaced_file() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = temp_dir("perms");
+    let path = dir.join("system.reg");
+    let mode_of = |path: &std::path::Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+    // What a new file gets under the current umask, and a mode different from it.
+    fs::File::create(dir.join("fresh")).unwrap();
+    let fresh = mode_of(&dir.join("fresh"));
+    let custom = if fresh == 0o640 { 0o600 } else { 0o640 };
+
+    let writer = RegistryWriter::new();
+    writer.write_to_file(&tree(1), &path).unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(custom)).unwrap();
+
+    writer.write_to_file(&tree(2), &path).unwrap();
+    assert_eq!(mode_of(&path), custom);
+
+    let plain = SaveOptions { preserve_permissions: false, fsync: false, ..SaveOptions::default() };
+    writer.save(&tree(3), &path, &plain).unwrap();
+    assert_eq!(mode_of(&path), fresh);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn lock_file_blocks_concurrent_saves_and_recovers_stale_locks() {
+    let dir = temp_dir("lock");
+    let path = dir.join("user.reg");
+    let writer = RegistryWriter::new();
+    let locked = SaveOptions { lock: true, ..SaveOptions::default() };
+
+    let held = LockFile::acquire(&path).unwrap();
+    let err = writer.save(&tree(1), &path, &locked).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::WouldBlock);
+    assert!(!path.exists());
+    drop(held);
+
+    writer.save(&tree(1), &path, &locked).unwrap();
+    assert!(!dir.join("user.reg.lock").exists());
+
+    if std::path::Path::new("/proc/self").exists() {
+        fs::write(dir.join("user.reg.lock"), format!("{}\n", u32::MAX)).unwrap();
+        writer.save(&tree(2), &path, &locked).unwrap();
+    }
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn save_detects_running_wineserver() {
+    use std::os::unix::net::UnixListener;
+
+    let dir = temp_dir("server");
+    let prefix = dir.join("prefix");
+    let socket_root = dir.join("sockets");
+    fs::create_dir_all(&prefix).unwrap();
+    let server_dir = wineserver_dir(&prefix, Some(&socket_root)).unwrap();
+    assert!(server_dir.file_name().unwrap().to_string_lossy().starts_with("server-"));
+    fs::create_dir_all(&server_dir).unwrap();
+
+    let path = prefix.join("system.reg");
+    let writer = RegistryWriter::new();
+    let mut check = WineServerCheck { prefix: prefix.clone(), socket_root: Some(socket_root.clone()), refuse: true };
+    let options = |check: &WineServerCheck| SaveOptions { wineserver: Some(check.clone()), ..SaveOptions::default() };
+
+    assert_eq!(writer.save(&tree(1), &path, &options(&check)).unwrap(), SaveReport { wineserver_running: false });
+
+    let listener = UnixListener::bind(server_dir.join("socket")).unwrap();
+    assert!(wineserver_running(&prefix, Some(&socket_root)).unwrap());
+    let err = writer.save(&tree(2), &path, &options(&check)).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::ResourceBusy);
+
+    check.refuse = false;
+    assert!(writer.save(&tree(2), &path, &options(&check)).unwrap().wineserver_running);
+
+    // A socket left behind by a dead server is not a running server.
+    drop(listener);
+    assert!(!wineserver_running(&prefix, Some(&socket_root)).unwrap());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
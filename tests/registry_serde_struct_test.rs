@@ -0,0 +1,219 @@
+#![cfg(feature = "serde")]
+
+use serde::{Deserialize, Serialize};
+use winereg::*;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Direct3D {
+    #[serde(rename = "renderer")]
+    renderer: String,
+    #[serde(rename = "VideoMemorySize")]
+    video_memory_size: u32,
+    #[serde(rename = "csmt")]
+    csmt: bool,
+    #[serde(rename = "MaxShaderModelVS")]
+    max_shader_model_vs: Option<u32>,
+    #[serde(rename = "OffscreenRenderingMode")]
+    offscreen_mode: Mode,
+    #[serde(rename = "Shaders")]
+    shaders: Vec<String>,
+    #[serde(rename = "Adapters")]
+    adapters: Vec<Adapter>,
+    #[serde(rename = "Debug")]
+    debug: DebugSettings,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum Mode {
+    #[serde(rename = "fbo")]
+    Fbo,
+    #[serde(rename = "backbuffer")]
+    Backbuffer,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Adapter {
+    #[serde(rename = "Name")]
+    name: String,
+    #[serde(rename = "VideoMemory")]
+    video_memory: u64,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct DebugSettings {
+    #[serde(rename = "Level")]
+    level: i32,
+}
+
+#[test]
+fn deserializes_struct_from_key_with_coercions() {
+    let reg_text = r#"WINE REGISTRY Version 2
+;; All keys relative to REGISTRY\\User\\S-1-5-21-0-0-0-1000
+
+[Software\\Wine\\Direct3D] 1700000000
+"csmt"=dword:00000001
+"OffscreenRenderingMode"="backbuffer"
+"renderer"="vulkan"
+"Shaders"=str(7):"glsl\0spirv\0"
+"VideoMemorySize"="2048"
+
+[Software\\Wine\\Direct3D\\Adapters\\0] 1700000000
+"Name"="Primary"
+"VideoMemory"=hex(b):00,00,00,80,00,00,00,00
+
+[Software\\Wine\\Direct3D\\Adapters\\1] 1700000000
+"Name"="Secondary"
+"VideoMemory"=dword:00001000
+
+[Software\\Wine\\Direct3D\\Debug] 1700000000
+"Level"=dword:ffffffff
+"#;
+    let loaded = RegistryParser.load_from_text(reg_text).expect("parse text");
+    let key = RegistryKey::find_key(&loaded.root_key, "Software\\Wine\\Direct3D").unwrap();
+    let settings: Direct3D = from_key(&key).expect("deserialize settings");
+    assert_eq!(
+        settings,
+        Direct3D {
+            renderer: "vulkan".into(),
+            video_memory_size: 2048,
+            csmt: true,
+            max_shader_model_vs: None,
+            offscreen_mode: Mode::Backbuffer,
+            shaders: vec!["glsl".into(), "spirv".into()],
+            adapters: vec![
+                Adapter { name: "Primary".into(), video_memory: 0x8000_0000 },
+                Adapter { name: "Secondary".into(), video_memory: 0x1000 },
+            ],
+            debug: DebugSettings { level: -1 },
+        }
+    );
+}
+
+#[test]
+fn serializes_struct_into_key_and_reads_it_back() {
+    let settings = Direct3D {
+        renderer: "gl".into(),
+        video_memory_size: 512,
+        csmt: false,
+        max_shader_model_vs: Some(5),
+        offscreen_mode: Mode::Fbo,
+        shaders: vec!["glsl".into()],
+        adapters: vec![Adapter { name: "Only".into(), video_memory: 1 << 33 }],
+        debug: DebugSettings { level: 2 },
+    };
+    let root = RegistryKey::create_root();
+    let key = RegistryKey::create_key_recursive(&root, "Software\\Wine\\Direct3D");
+    to_key(&settings, &key).expect("serialize settings");
+
+    {
+        let guard = key.borrow();
+        assert_eq!(guard.get_value("VideoMemorySize").unwrap().reg_type(), REG_DWORD);
+        assert_eq!(guard.get_value("Shaders").unwrap().reg_type(), REG_MULTI_SZ);
+        assert_eq!(guard.get_string("OffscreenRenderingMode").unwrap(), "fbo");
+    }
+    let adapter = RegistryKey::find_key(&key, "Adapters\\0").expect("numbered subkey");
+    assert_eq!(adapter.borrow().get_value("VideoMemory").unwrap().reg_type(), REG_QWORD);
+
+    let read_back: Direct3D = from_key(&key).expect("deserialize settings");
+    assert_eq!(read_back, settings);
+}
+
+#[test]
+fn reports_missing_and_mistyped_fields() {
+    let root = RegistryKey::create_root();
+    let result: Result<DebugSettings, _> = from_key(&root);
+    assert!(result.unwrap_err().to_string().contains("Level"));
+
+    root.borrow_mut().set_value(
+        "Level",
+        RegistryValue::new("Level", RegistryValueData::MultiString(vec!["x".into()])),
+    );
+    let result: Result<DebugSettings, _> = from_key(&root);
+    assert!(matches!(result, Err(RegistrySerdeError::Value(ValueError::WrongType { .. }))));
+}
+
+#[test]
+fn dwords_read_as_signed_for_every_signed_width() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Narrow {
+        #[serde(rename = "Offset")]
+        offset: i32,
+    }
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Wide {
+        #[serde(rename = "Offset")]
+        offset: i64,
+    }
+
+    let root = RegistryKey::create_root();
+    to_key(&Narrow { offset: -1 }, &root).unwrap();
+    assert_eq!(root.borrow().get_value("Offset").unwrap().data, RegistryValueData::Dword(0xffff_ffff));
+    assert_eq!(from_key::<Narrow>(&root).unwrap(), Narrow { offset: -1 });
+    assert_eq!(from_key::<Wide>(&root).unwrap(), Wide { offset: -1 });
+}
+
+#[test]
+fn rewriting_a_key_replaces_the_old_form_of_each_field() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Profile {
+        #[serde(rename = "Debug")]
+        debug: Option<DebugSettings>,
+        #[serde(rename = "Adapters")]
+        adapters: Vec<Adapter>,
+    }
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Flat {
+        #[serde(rename = "Adapters")]
+        adapters: Vec<String>,
+    }
+    #[derive(Serialize)]
+    #[serde(untagged)]
+    enum Item {
+        Name(String),
+        Adapter(Adapter),
+    }
+    #[derive(Serialize)]
+    struct Mixed {
+        #[serde(rename = "Adapters")]
+        adapters: Vec<Item>,
+    }
+
+    let root = RegistryKey::create_root();
+    let adapter = |name: &str| Adapter { name: name.into(), video_memory: 1 };
+    let full = Profile { debug: Some(DebugSettings { level: 1 }), adapters: vec![adapter("A"), adapter("B")] };
+    to_key(&full, &root).unwrap();
+    RegistryKey::find_key(&root, "Debug").unwrap().borrow_mut()
+        .set_value("Stale", RegistryValue::new("Stale", RegistryValueData::String("x".into())));
+
+    // A list rejected for mixing values and structs leaves the tree as it was.
+    let before = KeyTree::from_node(&root);
+    let mix = Mixed { adapters: vec![Item::Adapter(adapter("C")), Item::Name("D".into())] };
+    assert!(matches!(to_key(&mix, &root), Err(RegistrySerdeError::Unsupported(_))));
+    assert_eq!(KeyTree::from_node(&root), before);
+
+    let shorter = Profile { debug: Some(DebugSettings { level: 2 }), adapters: vec![adapter("Z")] };
+    to_key(&shorter, &root).unwrap();
+    assert!(RegistryKey::find_key(&root, "Debug").unwrap().borrow().get_value("Stale").is_none());
+    assert!(RegistryKey::find_key(&root, "Adapters\\1").is_none());
+    assert_eq!(from_key::<Profile>(&root).unwrap(), shorter);
+
+    to_key(&Profile { debug: None, adapters: vec![] }, &root).unwrap();
+    assert!(RegistryKey::find_key(&root, "Debug").is_none());
+    assert_eq!(from_key::<Profile>(&root).unwrap().debug, None);
+
+    to_key(&Profile { debug: None, adapters: vec![adapter("A")] }, &root).unwrap();
+    to_key(&Flat { adapters: vec!["A".into()] }, &root).unwrap();
+    assert!(RegistryKey::find_key(&root, "Adapters").is_none());
+    assert_eq!(from_key::<Flat>(&root).unwrap(), Flat { adapters: vec!["A".into()] });
+    to_key(&Profile { debug: None, adapters: vec![adapter("B")] }, &root).unwrap();
+    assert!(root.borrow().get_value("Adapters").is_none());
+    assert_eq!(from_key::<Profile>(&root).unwrap().adapters, vec![adapter("B")]);
+}
+
+#[test]
+fn malformed_strings_still_parse_as_signed_numbers() {
+    let root = RegistryKey::create_root();
+    let bytes: Vec<u8> = "-7".encode_utf16().flat_map(u16::to_le_bytes).chain([0, 0, 0x41]).collect();
+    root.borrow_mut().set_value("Level", RegistryValue::new("Level", RegistryValueData::RawString(bytes, REG_SZ)));
+    assert_eq!(from_key::<DebugSettings>(&root).unwrap(), DebugSettings { level: -7 });
+}
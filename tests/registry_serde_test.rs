@@ -0,0 +1,52 @@
+#![cfg(feature = "serde")]
+
+use winereg::*;
+
+fn sample_registry() -> KeyNode {
+    let root = RegistryKey::create_root();
+    let app = RegistryKey::create_key_recursive(&root, "Software\\App");
+    {
+        let mut guard = app.borrow_mut();
+        guard.class_name = Some("AppClass".into());
+        guard.set_value("Name", RegistryValue::new("Name", RegistryValueData::String("demo".into())));
+        guard.set_value("Count", RegistryValue::new("Count", RegistryValueData::Dword(3)));
+        guard.set_value(
+            "Blob",
+            RegistryValue::new("Blob", RegistryValueData::Binary(vec![1, 2, 3], 0xffff0007)),
+        );
+    }
+    RegistryKey::create_key_recursive(&root, "Software\\App\\Child");
+    app.borrow_mut().modification_time = 0x1db3821063cb374;
+    root
+}
+
+#[test]
+fn key_tree_round_trips_through_json() {
+    let root = sample_registry();
+    let tree = KeyTree::from_node(&root);
+    let json = serde_json::to_string(&tree).expect("serialize tree");
+    let decoded: KeyTree = serde_json::from_str(&json).expect("deserialize tree");
+    assert_eq!(tree, decoded);
+
+    let rebuilt = decoded.to_root();
+    assert!(!RegistryComparator.compare_registries(&root, &rebuilt).has_changes());
+    let app = RegistryKey::find_key(&rebuilt, "Software\\App").unwrap();
+    assert_eq!(app.borrow().modification_time, 0x1db3821063cb374);
+    assert_eq!(app.borrow().class_name.as_deref(), Some("AppClass"));
+}
+
+#[test]
+fn diff_and_patch_result_round_trip_through_json() {
+    let diff = RegistryComparator.compare_registries(&RegistryKey::create_root(), &sample_registry());
+    let json = serde_json::to_string(&diff).expect("serialize diff");
+    let decoded: DiffResult = serde_json::from_str(&json).expect("deserialize diff");
+    assert_eq!(diff.changes.len(), decoded.changes.len());
+
+    let target = RegistryKey::create_root();
+    let result = RegistryPatcher.apply_patch(&target, &decoded, PatchOptions::default());
+    let report: PatchResult =
+        serde_json::from_str(&serde_json::to_string(&result).unwrap()).expect("round trip report");
+    assert_eq!(report.applied_count(), result.applied_count());
+    let app = RegistryKey::find_key(&target, "Software\\App").unwrap();
+    assert_eq!(app.borrow().get_u32("Count").unwrap(), 3);
+}
@@ -0,0 +1,63 @@
+use winereg::*;
+
+fn resource_path(name: &str) -> String {
+    format!("{}/tests/resources/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+#[test]
+fn exported_subtree_is_rebased_and_grafts_back() {
+    let loaded = RegistryParser.load_from_file(resource_path("user.reg")).expect("parse user.reg");
+    let writer = RegistryWriter {
+        relative_base: loaded.relative_base.clone(),
+        architecture: loaded.architecture,
+    };
+    let text = writer.write_subtree_to_string(&loaded.root_key, "software\\wine").expect("export");
+    assert!(text.contains(";; All keys relative to REGISTRY\\\\User\\\\S-1-5-21-0-0-0-1000\\\\Software\\\\Wine\n"));
+    assert!(!text.contains("[Software"));
+    let missing = writer.write_subtree_to_string(&loaded.root_key, "Software\\Missing").unwrap_err();
+    assert_eq!(missing.kind(), std::io::ErrorKind::NotFound);
+
+    let hive = RegistryParser.load_from_text(&text).expect("reparse");
+    assert_eq!(hive.path_below(&loaded.relative_base).as_deref(), Some("Software\\Wine"));
+    assert_eq!(hive.path_below("REGISTRY\\\\Machine"), None);
+
+    let target = RegistryKey::create_root();
+    RegistryKey::create_key_recursive(&target, "Software\\Other");
+    let path = hive.path_below(&loaded.relative_base).unwrap();
+    let grafted = hive.graft_into(&target, &path);
+    assert!(RegistryKey::find_key(&target, "Software\\Other").is_some());
+
+    let original = KeyTree::from_node(&RegistryKey::find_key(&loaded.root_key, "Software\\Wine").unwrap());
+    let copy = KeyTree::from_node(&grafted);
+    assert_eq!(original.subkeys, copy.subkeys);
+    assert_eq!(original.values, copy.values);
+}
+
+#[test]
+fn subtree_root_values_are_written_under_empty_path() {
+    let root = RegistryKey::create_root();
+    let app = RegistryKey::create_key_recursive(&root, "Software\\Vendor\\App");
+    app.borrow_mut().set_value("Version", RegistryValue::new("Version", RegistryValueData::Dword(2)));
+    app.borrow_mut().class_name = Some("AppClass".into());
+    RegistryKey::create_key_recursive(&app, "Plugins");
+    app.borrow_mut().modification_time = 1_700_000_000;
+
+    let text = RegistryWriter::new().write_subtree_to_string(&root, "Software\\Vendor\\App").unwrap();
+    assert!(text.starts_with("WINE REGISTRY Version 2\n;; All keys relative to Software\\\\Vendor\\\\App\n"));
+    assert!(text.contains("\n[] "));
+    assert!(text.contains("\n[Plugins] "));
+
+    let hive = RegistryParser.load_from_text(&text).unwrap();
+    let other = RegistryKey::create_root();
+    let existing = RegistryKey::create_key_recursive(&other, "Apps\\App");
+    existing.borrow_mut().set_value("Keep", RegistryValue::new("Keep", RegistryValueData::String("yes".into())));
+    hive.graft_into(&other, "Apps\\App");
+
+    let app = RegistryKey::find_key(&other, "Apps\\App").unwrap();
+    assert_eq!(app.borrow().get_u32("Version").unwrap(), 2);
+    assert_eq!(app.borrow().get_string("Keep").unwrap(), "yes");
+    // The grafted top key follows the same rule as every key below it.
+    assert_eq!(app.borrow().class_name.as_deref(), Some("AppClass"));
+    assert_eq!(app.borrow().modification_time, 1_700_000_000);
+    assert!(RegistryKey::find_key(&other, "Apps\\App\\Plugins").is_some());
+}
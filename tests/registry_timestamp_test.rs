@@ -0,0 +1,87 @@
+use std::cell::Cell;
+use std::rc::Rc;
+
+use winereg::*;
+
+#[derive(Debug, Default)]
+struct StepClock(Cell<u64>);
+
+impl Clock for StepClock {
+    fn now(&self) -> u64 {
+        self.0.set(self.0.get() + 1);
+        self.0.get()
+    }
+}
+
+fn time_of(node: &KeyNode) -> u64 {
+    node.borrow().modification_time
+}
+
+#[test]
+fn mutations_stamp_the_affected_key_only() {
+    let root = RegistryKey::create_root();
+    RegistryKey::set_clock(&root, Rc::new(StepClock::default()));
+
+    let app = RegistryKey::create_key_recursive(&root, "Software\\App");
+    let software = RegistryKey::find_key(&root, "Software").unwrap();
+    assert_eq!(time_of(&software), 2);
+    assert_eq!(time_of(&app), 2);
+
+    app.borrow_mut().set_value("A", RegistryValue::new("A", RegistryValueData::Dword(1)));
+    assert_eq!(time_of(&app), 3);
+    assert_eq!(time_of(&software), 2);
+
+    let child = RegistryKey::create_subkey(&app, "Child");
+    assert_eq!(time_of(&app), 4);
+    assert_eq!(time_of(&child), 4);
+
+    assert!(app.borrow_mut().delete_value("A"));
+    assert_eq!(time_of(&app), 5);
+    assert!(!app.borrow_mut().delete_value("A"));
+    assert_eq!(time_of(&app), 5);
+
+    assert!(RegistryKey::delete_subkey(&app, "Child", true));
+    assert_eq!(time_of(&app), 6);
+    assert_eq!(time_of(&software), 2);
+
+    // Only the mutated key is stamped, but every ancestor is still flagged dirty.
+    root.borrow_mut().is_dirty = false;
+    software.borrow_mut().is_dirty = false;
+    app.borrow_mut().set_value("B", RegistryValue::new("B", RegistryValueData::Dword(2)));
+    assert_eq!(time_of(&app), 7);
+    assert_eq!(time_of(&software), 2);
+    assert!(software.borrow().is_dirty && root.borrow().is_dirty);
+}
+
+#[test]
+fn fixed_clock_drives_dsl_and_recursive_updates() {
+    let clock = FixedClock::from_unix(1_700_000_000);
+    let root = RegistryKey::create_root();
+    RegistryKey::set_clock(&root, Rc::new(clock));
+    let key = RegistryKey::create_key_recursive(&root, "Software\\Fixed");
+    assert_eq!(time_of(&key), clock.0);
+
+    key.borrow_mut().modification_time = 0;
+    set_current_time_recursive(&root);
+    assert_eq!(time_of(&key), clock.0);
+
+    let text = RegistryWriter::new().write_to_string(&root);
+    assert!(text.contains("[Software\\\\Fixed] 1700000000"));
+}
+
+#[test]
+fn loading_preserves_timestamps_from_file() {
+    let reg_text = r#"WINE REGISTRY Version 2
+
+[Software] 1600000000
+#time=1d68d3f2a7e8000
+"Value"="x"
+
+[Software\\Child] 1600000001
+"#;
+    let loaded = RegistryParser.load_from_text(reg_text).expect("parse text");
+    let software = RegistryKey::find_key(&loaded.root_key, "Software").unwrap();
+    assert_eq!(time_of(&software), 0x1d68d3f2a7e8000);
+    let child = RegistryKey::find_key(&loaded.root_key, "Software\\Child").unwrap();
+    assert_eq!(time_of(&child), timestamp_to_filetime(1_600_000_001));
+}
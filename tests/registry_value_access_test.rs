@@ -0,0 +1,71 @@
+use winereg::*;
+
+fn key_with(values: Vec<(&str, RegistryValueData)>) -> KeyNode {
+    let root = RegistryKey::create_root();
+    {
+        let mut guard = root.borrow_mut();
+        for (name, data) in values {
+            guard.set_value(name, RegistryValue::new(name, data));
+        }
+    }
+    root
+}
+
+#[test]
+fn typed_getters_read_native_types() {
+    let key = key_with(vec![
+        ("Name", RegistryValueData::String("wine".into())),
+        ("Count", RegistryValueData::Dword(7)),
+        ("Size", RegistryValueData::Qword(1 << 40)),
+        ("List", RegistryValueData::MultiString(vec!["a".into(), "b".into()])),
+        ("Blob", RegistryValueData::Binary(vec![1, 2, 3], REG_BINARY)),
+    ]);
+    let guard = key.borrow();
+    assert_eq!(guard.get_string("name").unwrap(), "wine");
+    assert_eq!(guard.get_u32("Count").unwrap(), 7);
+    assert_eq!(guard.get_u64("Size").unwrap(), 1 << 40);
+    assert_eq!(guard.get_multi("List").unwrap(), vec!["a", "b"]);
+    assert_eq!(guard.get_bytes("Blob").unwrap(), vec![1, 2, 3]);
+    assert_eq!(guard.get_bytes("Count").unwrap(), vec![7, 0, 0, 0]);
+}
+
+#[test]
+fn typed_getters_apply_documented_coercions() {
+    let key = key_with(vec![
+        ("Flag", RegistryValueData::String("1".into())),
+        ("Hex", RegistryValueData::String("0x20".into())),
+        ("Packed", RegistryValueData::Binary(vec![0x10, 0, 0, 0], REG_BINARY)),
+        ("Yes", RegistryValueData::String("Y".into())),
+        ("Off", RegistryValueData::String("false".into())),
+        ("Enabled", RegistryValueData::Dword(2)),
+        ("Single", RegistryValueData::String("only".into())),
+    ]);
+    let guard = key.borrow();
+    assert_eq!(guard.get_u32("Flag").unwrap(), 1);
+    assert_eq!(guard.get_u32("Hex").unwrap(), 0x20);
+    assert_eq!(guard.get_u32("Packed").unwrap(), 0x10);
+    assert_eq!(guard.get_u64("Packed").unwrap(), 0x10);
+    assert!(guard.get_bool("Flag").unwrap());
+    assert!(guard.get_bool("Yes").unwrap());
+    assert!(!guard.get_bool("Off").unwrap());
+    assert!(guard.get_bool("Enabled").unwrap());
+    assert_eq!(guard.get_multi("Single").unwrap(), vec!["only"]);
+}
+
+#[test]
+fn typed_getters_distinguish_missing_wrong_type_and_invalid() {
+    let key = key_with(vec![
+        ("Count", RegistryValueData::Dword(7)),
+        ("Text", RegistryValueData::String("abc".into())),
+        ("Short", RegistryValueData::Binary(vec![1, 2], REG_BINARY)),
+    ]);
+    let guard = key.borrow();
+    assert_eq!(guard.get_u32("Absent"), Err(ValueError::Missing("Absent".into())));
+    assert!(matches!(
+        guard.get_string("Count"),
+        Err(ValueError::WrongType { actual: REG_DWORD, .. })
+    ));
+    assert!(matches!(guard.get_u32("Text"), Err(ValueError::Invalid { .. })));
+    assert!(matches!(guard.get_u32("Short"), Err(ValueError::Invalid { .. })));
+    assert!(matches!(guard.get_bool("Text"), Err(ValueError::Invalid { .. })));
+}
@@ -0,0 +1,81 @@
+use winereg::*;
+
+fn multi(items: &[&str]) -> RegistryValue {
+    RegistryValue::new("Fonts", RegistryValueData::MultiString(items.iter().map(|s| s.to_string()).collect()))
+}
+
+fn binary(bytes: &[u8]) -> RegistryValue {
+    RegistryValue::new("Data", RegistryValueData::Binary(bytes.to_vec(), REG_BINARY))
+}
+
+#[test]
+fn item_and_byte_range_deltas() {
+    let old: Vec<String> = (0..200).map(|i| format!("Font {}", i)).collect();
+    let mut new = old.clone();
+    new[120] = "Replaced".into();
+    new.insert(5, "Inserted".into());
+    let (old, new) = (multi(&old.iter().map(String::as_str).collect::<Vec<_>>()), multi(&new.iter().map(String::as_str).collect::<Vec<_>>()));
+    let delta = ValueDelta::between(&old, &new).unwrap();
+    assert_eq!(
+        delta,
+        ValueDelta::MultiString(vec![
+            ListEdit::Removed { index: 120, item: "Font 120".into() },
+            ListEdit::Inserted { index: 5, item: "Inserted".into() },
+            ListEdit::Inserted { index: 121, item: "Replaced".into() },
+        ])
+    );
+    assert_eq!(delta.apply(&old).unwrap().data, new.data);
+    assert!(delta.apply(&multi(&["a"])).is_none());
+
+    let a = binary(&[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]);
+    let b = binary(&[0, 0xff, 2, 0xfe, 4, 5, 6, 7, 8, 9, 10, 11, 12, 0xfd, 14, 15]);
+    let delta = ValueDelta::between(&a, &b).unwrap();
+    assert_eq!(
+        delta,
+        ValueDelta::Binary(vec![
+            ByteRange { offset: 1, old: vec![1, 2, 3], new: vec![0xff, 2, 0xfe] },
+            ByteRange { offset: 13, old: vec![13], new: vec![0xfd] },
+        ])
+    );
+    assert_eq!(delta.apply(&a).unwrap().data, b.data);
+    let longer = binary(&[0, 1, 2, 0xaa, 0xbb, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]);
+    let delta = ValueDelta::between(&a, &longer).unwrap();
+    assert_eq!(delta, ValueDelta::Binary(vec![ByteRange { offset: 3, old: vec![], new: vec![0xaa, 0xbb] }]));
+    assert_eq!(delta.apply(&a).unwrap().data, longer.data);
+
+    assert!(ValueDelta::between(&a, &multi(&["x"])).is_none());
+    let change = RegistryChange::ValueModified("K".into(), "Data".into(), a, b);
+    assert!(matches!(change.value_delta(), Some(ValueDelta::Binary(r)) if r.len() == 2));
+}
+
+#[test]
+fn text_export_writes_and_reads_edits() {
+    let root = RegistryKey::create_root();
+    let key = RegistryKey::create_key_recursive(&root, "System\\Session Manager");
+    key.borrow_mut().set_value("Pending", RegistryValue::new("Pending", RegistryValueData::MultiString(vec!["a".into(), "C:\\b".into(), "c".into()])));
+    key.borrow_mut().set_value("Blob", RegistryValue::new("Blob", RegistryValueData::Binary(vec![1, 2, 3, 4, 5, 6, 7, 8], REG_BINARY)));
+    let after = KeyTree::from_node(&root).to_root();
+    let key2 = RegistryKey::find_key(&after, "System\\Session Manager").unwrap();
+    key2.borrow_mut().set_value("Pending", RegistryValue::new("Pending", RegistryValueData::MultiString(vec!["a".into(), "c".into(), "d".into()])));
+    key2.borrow_mut().set_value("Blob", RegistryValue::new("Blob", RegistryValueData::Binary(vec![1, 2, 3, 9, 5, 6, 7, 8], REG_BINARY)));
+
+    let diff = RegistryComparator.compare_registries(&root, &after);
+    let options = TextDiffOptions::default().value_deltas(true);
+    let text = TextDiffExporter.export_with_options(&diff, None, None, &options);
+    assert!(text.contains("~\"Blob\"=hex:01,02,03,04,05,06,07,08->edit\n*@3:04->09\n"));
+    assert!(text.contains("->edit\n*-1:\"C:\\\\b\"\n*+2:\"d\"\n"));
+    assert!(!TextDiffExporter.export(&diff, None, None).contains("->edit"));
+
+    let parsed = TextDiffParser.parse(&text).expect("parse");
+    let target = KeyTree::from_node(&root).to_root();
+    assert!(RegistryPatcher.apply_patch(&target, &parsed, PatchOptions::default()).is_success());
+    assert!(!RegistryComparator.compare_registries(&target, &after).has_changes());
+
+    let broken = text.replace("*@3:04->09", "*@3:05->09");
+    assert!(TextDiffParser.parse(&broken).is_err());
+    // Edit lines of the other kind are rejected, not dropped.
+    let err = TextDiffParser.parse(&text.replace("*@3:04->09", "*+0:\"x\"")).unwrap_err();
+    assert!(err.contains("item edit under non-multi-string \"Blob\""), "{}", err);
+    let err = TextDiffParser.parse(&text.replace("*+2:\"d\"", "*+2:\"d\"\n*@0:61->62")).unwrap_err();
+    assert!(err.contains("byte range under multi-string \"Pending\""), "{}", err);
+}
@@ -0,0 +1,116 @@
+use winereg::*;
+
+/// A `CM_RESOURCE_LIST` with one ISA descriptor claiming I/O ports 0x3f8-0x3ff.
+fn serial_port_resource_list() -> Vec<u8> {
+    let mut bytes = Vec::new();
+    bytes.extend(1u32.to_le_bytes());
+    bytes.extend(1i32.to_le_bytes());
+    bytes.extend(0u32.to_le_bytes());
+    bytes.extend(1u16.to_le_bytes());
+    bytes.extend(1u16.to_le_bytes());
+    bytes.extend(1u32.to_le_bytes());
+    bytes.extend([CM_RESOURCE_TYPE_PORT, 1]);
+    bytes.extend(0x11u16.to_le_bytes());
+    bytes.extend(0x3f8u64.to_le_bytes());
+    bytes.extend(8u32.to_le_bytes());
+    bytes
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(",")
+}
+
+#[test]
+fn parses_and_writes_every_value_type() {
+    let list = hex(&serial_port_resource_list());
+    let text = format!(
+        "WINE REGISTRY Version 2\n\n[Hardware\\\\Types] 1700000000\n#time=1d9f0a0b0c0d0e0\n\
+         \"A_None\"=hex(0):01,02\n\
+         \"B_Be\"=hex(5):12,34,56,78\n\
+         \"C_Link\"=hex(6):5c,00,52,00,65,00,67,00\n\
+         \"D_Res\"=hex(8):{list}\n\
+         \"E_Full\"=hex(9):{full}\n\
+         \"F_Odd\"=hex(6):5c,00,52\n",
+        full = hex(&serial_port_resource_list()[4..]),
+    );
+    let loaded = RegistryParser.load_from_text(&text).expect("parse");
+    let key = RegistryKey::find_key(&loaded.root_key, "Hardware\\Types").unwrap();
+    let guard = key.borrow();
+
+    assert_eq!(guard.get_value("A_None").unwrap().data, RegistryValueData::None(vec![1, 2]));
+    let be = guard.get_value("B_Be").unwrap();
+    assert_eq!(be.data, RegistryValueData::DwordBigEndian(0x12345678));
+    assert_eq!(be.as_u32().unwrap(), 0x12345678);
+    assert_eq!(guard.get_value("C_Link").unwrap().as_link().unwrap(), "\\Reg");
+    assert_eq!(guard.get_value("F_Odd").unwrap().data, RegistryValueData::Binary(vec![0x5c, 0, 0x52], REG_LINK));
+
+    let resources = guard.get_value("D_Res").unwrap().as_resource_list().unwrap();
+    assert_eq!(resources.descriptors.len(), 1);
+    let port = &resources.descriptors[0].partial_descriptors[0];
+    assert_eq!(port.resource_type, CM_RESOURCE_TYPE_PORT);
+    assert_eq!(port.range(), Some((0x3f8, 8)));
+    let full = guard.get_value("E_Full").unwrap().as_full_resource_descriptor().unwrap();
+    assert_eq!(full, resources.descriptors[0]);
+    drop(guard);
+
+    let written = RegistryWriter::new().write_to_string(&loaded.root_key);
+    assert!(written.contains("\"A_None\"=hex(0):01,02\n"));
+    assert!(written.contains("\"B_Be\"=hex(5):12,34,56,78\n"));
+    assert!(written.contains("\"C_Link\"=hex(6):5c,00,52,00,65,00,67,00\n"));
+    let reparsed = RegistryParser.load_from_text(&written).expect("reparse");
+    let diff = RegistryComparator.compare_registries(&loaded.root_key, &reparsed.root_key);
+    assert!(diff.changes.is_empty(), "{:?}", diff.changes);
+}
+
+#[test]
+fn resource_requirements_list_checks_its_size() {
+    let mut bytes = Vec::new();
+    bytes.extend(0u32.to_le_bytes());
+    bytes.extend(1i32.to_le_bytes());
+    bytes.extend([0u8; 20]);
+    bytes.extend(1u32.to_le_bytes());
+    bytes.extend(1u16.to_le_bytes());
+    bytes.extend(1u16.to_le_bytes());
+    bytes.extend(1u32.to_le_bytes());
+    bytes.extend([0, CM_RESOURCE_TYPE_INTERRUPT, 1, 0]);
+    bytes.extend([0u8; 28]);
+    let len = bytes.len() as u32;
+    bytes[..4].copy_from_slice(&len.to_le_bytes());
+
+    let value = RegistryValue::new("Req", RegistryValueData::from_raw(REG_RESOURCE_REQUIREMENTS_LIST, bytes.clone()));
+    let list = value.as_resource_requirements_list().unwrap();
+    assert_eq!(list.interface_type, 1);
+    assert_eq!(list.alternative_lists[0].descriptors[0].resource_type, CM_RESOURCE_TYPE_INTERRUPT);
+
+    bytes.push(0);
+    let value = RegistryValue::new("Req", RegistryValueData::from_raw(REG_RESOURCE_REQUIREMENTS_LIST, bytes));
+    assert!(matches!(value.as_resource_requirements_list(), Err(ValueError::Invalid { .. })));
+}
+
+#[test]
+fn text_diff_round_trips_new_types() {
+    let left = RegistryKey::create_root();
+    let right = RegistryKey::create_root();
+    let key = RegistryKey::create_key_recursive(&right, "Hardware");
+    let values = [
+        ("Be", RegistryValueData::DwordBigEndian(7)),
+        ("Link", RegistryValueData::Link("\\Registry\\Machine\\Software".into())),
+        ("None", RegistryValueData::None(vec![])),
+        ("Res", RegistryValueData::ResourceList(serial_port_resource_list())),
+    ];
+    for (name, data) in values {
+        key.borrow_mut().set_value(name, RegistryValue::new(name, data));
+    }
+
+    let diff = RegistryComparator.compare_registries(&left, &right);
+    let text = TextDiffExporter.export(&diff, None, None);
+    assert!(text.contains("+\"Be\"=dword_be:00000007"));
+    assert!(text.contains("+\"Link\"=link:\"\\\\Registry\\\\Machine\\\\Software\""));
+    assert!(text.contains("+\"None\"=hex(0):"));
+
+    let parsed = TextDiffParser.parse(&text).expect("parse diff");
+    let target = RegistryKey::create_root();
+    assert!(RegistryPatcher.apply_patch(&target, &parsed, PatchOptions::default()).is_success());
+    assert!(RegistryComparator.compare_registries(&target, &right).changes.is_empty());
+    assert_eq!(data_type_prefix(REG_QWORD), "hex(b):");
+}
@@ -0,0 +1,79 @@
+use winereg::*;
+
+fn resource_path(name: &str) -> String {
+    format!("{}/tests/resources/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+fn sample() -> KeyNode {
+    let text = "WINE REGISTRY Version 2\n\n\
+                [Items\\\\Item10] 1700000000\n#time=1d9f0a0b0c0d0e0\n\"Size\"=hex(b):01,02,00,00,00,00,00,00\n\n\
+                [Items\\\\Item2] 1700000000\n#time=1d9f0a0b0c0d0e0\n\"Data\"=hex:00,11,22,33,44,55,66,77,88,99,aa,bb,cc,dd,ee,ff,\\\n  00,11,22,33,44,55,66,77,88,99,aa,bb,cc,dd,ee,ff\n\n\
+                [Items\\\\alpha] 1700000000\n#time=1d9f0a0b0c0d0e0\n";
+    RegistryParser.load_from_text(text).expect("parse").root_key
+}
+
+fn write(root: &KeyNode, options: WriterOptions) -> String {
+    RegistryWriter::new().with_options(options).write_to_string(root)
+}
+
+#[test]
+fn default_output_matches_wine_line_for_line() {
+    let original = std::fs::read_to_string(resource_path("system.reg")).expect("read system.reg");
+    let loaded = RegistryParser.load_from_text(&original).expect("parse system.reg");
+    let writer = RegistryWriter {
+        relative_base: loaded.relative_base.clone(),
+        architecture: loaded.architecture,
+    };
+    let written = writer.write_to_string(&loaded.root_key);
+    // Hex wrapping and empty multi-strings are byte-identical; only sibling order may differ.
+    let mut expected: Vec<&str> = original.lines().collect();
+    let mut actual: Vec<&str> = written.lines().collect();
+    expected.sort_unstable();
+    actual.sort_unstable();
+    assert_eq!(expected, actual);
+}
+
+#[test]
+fn options_change_time_qword_wrap_and_line_endings() {
+    let root = sample();
+    let default = write(&root, WriterOptions::default());
+    assert!(default.contains("#time=1d9f0a0b0c0d0e0\n"));
+    assert!(default.contains("\"Size\"=hex(b):01,02,00,00,00,00,00,00\n"));
+    assert!(default.contains("44,55,\\\n  66,77"));
+
+    let options = WriterOptions {
+        emit_time: false,
+        qword_style: QwordStyle::Qword,
+        hex_wrap_width: 0,
+        line_ending: LineEnding::CrLf,
+        ..WriterOptions::default()
+    };
+    let custom = write(&root, options);
+    assert!(!custom.contains("#time="));
+    assert!(custom.contains("\"Size\"=qword:0000000000000201\r\n"));
+    assert!(!custom.contains("\\\r\n"));
+    assert!(!custom.replace("\r\n", "").contains('\n'));
+
+    let reparsed = RegistryParser.load_from_text(&custom).expect("reparse");
+    let item = RegistryKey::find_key(&reparsed.root_key, "Items\\Item10").unwrap();
+    assert_eq!(item.borrow().get_u64("Size").unwrap(), 0x201);
+    let data = RegistryKey::find_key(&reparsed.root_key, "Items\\Item2").unwrap();
+    assert_eq!(data.borrow().get_value("Data").unwrap().raw_bytes().len(), 32);
+}
+
+#[test]
+fn key_order_and_empty_keys() {
+    let root = sample();
+    let order = |options: WriterOptions| -> Vec<String> {
+        write(&root, options).lines().filter(|l| l.starts_with('[')).map(|l| l.split(']').next().unwrap()[1..].to_string()).collect()
+    };
+
+    assert_eq!(order(WriterOptions::default()), ["Items\\\\alpha", "Items\\\\Item10", "Items\\\\Item2"]);
+    let natural = WriterOptions { key_order: KeyOrder::Natural, ..WriterOptions::default() };
+    assert_eq!(order(natural), ["Items\\\\alpha", "Items\\\\Item2", "Items\\\\Item10"]);
+    let case_sensitive = WriterOptions { key_order: KeyOrder::CaseSensitive, ..WriterOptions::default() };
+    assert_eq!(order(case_sensitive), ["Items\\\\Item10", "Items\\\\Item2", "Items\\\\alpha"]);
+
+    let skip = WriterOptions { skip_empty_keys: true, ..WriterOptions::default() };
+    assert_eq!(order(skip), ["Items\\\\Item10", "Items\\\\Item2"]);
+}